@@ -0,0 +1,481 @@
+//! Conversion of records into Apache Arrow record batches and back, for analysing them with DataFusion, Polars and
+//! other Arrow-based tools.
+//!
+//! A batch has one row per record and the columns of `schema()`:
+//!
+//! | column        | type                                          | nullable |
+//! |---------------|-----------------------------------------------|----------|
+//! | `level`       | `Dictionary(Int32, Utf8)`, see `RecordLevel::as_str` | no |
+//! | `args`        | `Utf8`                                        | no       |
+//! | `target`      | `Dictionary(Int32, Utf8)`                     | no       |
+//! | `module_path` | `Dictionary(Int32, Utf8)`                     | yes      |
+//! | `file`        | `Dictionary(Int32, Utf8)`                     | yes      |
+//! | `line`        | `UInt32`                                      | yes      |
+//! | `timestamp`   | `Timestamp(Nanosecond, "UTC")`                | yes      |
+//! | `thread_name` | `Utf8`                                        | yes      |
+//! | `thread_id`   | `UInt64`                                      | yes      |
+//! | `process_id`  | `UInt32`                                      | yes      |
+//! | `key_values`  | `Map(Utf8, Utf8)`                             | no       |
+//!
+//! Key-values are stored as their text, nested key-values flattened with dots as in `http.status`, so they come back
+//! as string key-values.
+//!
+//! Reading a batch only requires the `level`, `args` and `target` columns. Text columns may also be plain or large
+//! strings or string views, dictionaries may have any key type and timestamps any unit, so batches that went through
+//! other tools can be read back as well. A null `args` or `target` is an error, a null or empty `level` is read as an
+//! empty `Unknown` level, which the level filter of the `parquet` module leaves out as well.
+//!
+//! ```rust
+//! use arrow_array::{cast::AsArray, types::Int32Type};
+//! use log::Level;
+//! use serializable_log_record::{arrow, SerializableLogRecord};
+//!
+//! let mut records = vec![
+//!     SerializableLogRecord::new(Level::Info, "Started".into(), "app".into(), Some("app".into()), Some("src/main.rs".into()), Some(3))
+//!         .with_timestamp(Some(1_700_000_000_000_000_000)),
+//!     SerializableLogRecord::new(Level::Warn, "Disk full".into(), "app::disk".into(), None, None, None),
+//!     SerializableLogRecord::new(Level::Info, "Stopped".into(), "app".into(), None, None, None),
+//! ];
+//! records[1].key_values.push("free", "0");
+//!
+//! let batch = arrow::to_record_batch(&records).unwrap();
+//! assert_eq!(batch.num_rows(), 3);
+//! assert_eq!(batch.schema(), arrow::schema());
+//! // Repeated values are only stored once.
+//! assert_eq!(batch.column_by_name("target").unwrap().as_dictionary::<Int32Type>().values().len(), 2);
+//!
+//! assert_eq!(arrow::from_record_batch(&batch).unwrap(), records);
+//! ```
+
+use crate::{
+    kv::{KeyValues, Value},
+    level::{LevelFallback, RecordLevel},
+    SerializableLogRecord,
+};
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use arrow_array::{
+    builder::{MapBuilder, StringBuilder, StringDictionaryBuilder},
+    cast::AsArray,
+    types::{
+        ArrowPrimitiveType, Int32Type, TimestampMicrosecondType, TimestampMillisecondType, TimestampNanosecondType,
+        TimestampSecondType, UInt32Type, UInt64Type,
+    },
+    Array, ArrayRef, RecordBatch, StringArray, TimestampNanosecondArray, UInt32Array, UInt64Array,
+};
+use arrow_schema::{ArrowError, DataType, Field, Fields, Schema, SchemaRef, TimeUnit};
+use core::{convert::TryFrom, fmt};
+use log::Level;
+
+/// The schema of the batches returned by `to_record_batch`, see the module documentation.
+#[must_use]
+pub fn schema() -> SchemaRef {
+    let dictionary = DataType::Dictionary(DataType::Int32.into(), DataType::Utf8.into());
+    let entries = Fields::from(vec![
+        Field::new("keys", DataType::Utf8, false),
+        Field::new("values", DataType::Utf8, true),
+    ]);
+    Arc::new(Schema::new(vec![
+        Field::new("level", dictionary.clone(), false),
+        Field::new("args", DataType::Utf8, false),
+        Field::new("target", dictionary.clone(), false),
+        Field::new("module_path", dictionary.clone(), true),
+        Field::new("file", dictionary, true),
+        Field::new("line", DataType::UInt32, true),
+        Field::new(
+            "timestamp",
+            DataType::Timestamp(TimeUnit::Nanosecond, Some("UTC".into())),
+            true,
+        ),
+        Field::new("thread_name", DataType::Utf8, true),
+        Field::new("thread_id", DataType::UInt64, true),
+        Field::new("process_id", DataType::UInt32, true),
+        Field::new(
+            "key_values",
+            DataType::Map(Field::new("entries", DataType::Struct(entries), false).into(), false),
+            false,
+        ),
+    ]))
+}
+
+/// Convert records into a batch with the columns of `schema()`.
+///
+/// # Errors
+/// Returns an error if a timestamp lies after the year 2262, which Arrow timestamps cannot represent.
+pub fn to_record_batch<'a>(
+    records: impl IntoIterator<Item = &'a SerializableLogRecord>,
+) -> Result<RecordBatch, RecordBatchError> {
+    let mut level = StringDictionaryBuilder::<Int32Type>::new();
+    let mut args = Vec::new();
+    let mut target = StringDictionaryBuilder::<Int32Type>::new();
+    let mut module_path = StringDictionaryBuilder::<Int32Type>::new();
+    let mut file = StringDictionaryBuilder::<Int32Type>::new();
+    let mut line = Vec::new();
+    let mut timestamp = Vec::new();
+    let mut thread_name = Vec::new();
+    let mut thread_id = Vec::new();
+    let mut process_id = Vec::new();
+    let mut key_values = MapBuilder::new(None, StringBuilder::new(), StringBuilder::new());
+    for record in records {
+        level.append_value(record.level.as_str());
+        args.push(record.args.as_str());
+        target.append_value(&record.target);
+        module_path.append_option(record.module_path.as_deref());
+        file.append_option(record.file.as_deref());
+        line.push(record.line);
+        timestamp.push(
+            record
+                .timestamp
+                .map(|nanos| i64::try_from(nanos).map_err(|_| RecordBatchError::TimestampOutOfRange))
+                .transpose()?,
+        );
+        thread_name.push(record.thread_name.as_deref());
+        thread_id.push(record.thread_id);
+        process_id.push(record.process_id);
+        append_key_values(&mut key_values, "", &record.key_values);
+        key_values.append(true).map_err(RecordBatchError::Arrow)?;
+    }
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(level.finish()),
+        Arc::new(StringArray::from(args)),
+        Arc::new(target.finish()),
+        Arc::new(module_path.finish()),
+        Arc::new(file.finish()),
+        Arc::new(UInt32Array::from(line)),
+        Arc::new(TimestampNanosecondArray::from(timestamp).with_timezone("UTC")),
+        Arc::new(StringArray::from(thread_name)),
+        Arc::new(UInt64Array::from(thread_id)),
+        Arc::new(UInt32Array::from(process_id)),
+        Arc::new(key_values.finish()),
+    ];
+    RecordBatch::try_new(schema(), columns).map_err(RecordBatchError::Arrow)
+}
+
+fn append_key_values(builder: &mut MapBuilder<StringBuilder, StringBuilder>, prefix: &str, key_values: &KeyValues) {
+    for (key, value) in key_values.iter() {
+        let key = if prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{prefix}.{key}")
+        };
+        match value {
+            Value::Nested(nested) => append_key_values(builder, &key, nested),
+            value => {
+                builder.keys().append_value(&key);
+                builder.values().append_value(value.to_string());
+            }
+        }
+    }
+}
+
+/// Convert a batch back into records, see the module documentation.
+///
+/// # Errors
+/// Returns an error if a required column is missing, `args` or `target` is null, a column has an unsupported type or a
+/// timestamp lies before the Unix epoch.
+pub fn from_record_batch(batch: &RecordBatch) -> Result<Vec<SerializableLogRecord>, RecordBatchError> {
+    let column = |name| batch.column_by_name(name).map(AsRef::as_ref);
+    let required = |name| column(name).ok_or(RecordBatchError::MissingColumn(name));
+    let optional_strings = |name| column(name).map(|array| strings(array, name)).transpose();
+    let level = strings(required("level")?, "level")?;
+    let args = strings(required("args")?, "args")?;
+    let target = strings(required("target")?, "target")?;
+    let module_path = optional_strings("module_path")?;
+    let file = optional_strings("file")?;
+    let thread_name = optional_strings("thread_name")?;
+    let line = column("line").map(|array| numbers::<UInt32Type>(array, "line")).transpose()?;
+    let thread_id = column("thread_id")
+        .map(|array| numbers::<UInt64Type>(array, "thread_id"))
+        .transpose()?;
+    let process_id = column("process_id")
+        .map(|array| numbers::<UInt32Type>(array, "process_id"))
+        .transpose()?;
+    let timestamp = column("timestamp").map(timestamps).transpose()?;
+    let key_values = column("key_values").map(key_values).transpose()?;
+
+    (0..batch.num_rows())
+        .map(|row| {
+            let mut record = SerializableLogRecord::new(
+                Level::Info,
+                args[row].ok_or(RecordBatchError::NullValue("args"))?.into(),
+                target[row].ok_or(RecordBatchError::NullValue("target"))?.into(),
+                cell(module_path.as_deref(), row).map(String::from),
+                cell(file.as_deref(), row).map(String::from),
+                cell(line.as_deref(), row),
+            );
+            let level = level[row].unwrap_or_default();
+            record.level = RecordLevel::parse(level, LevelFallback::Keep).unwrap_or_else(|_| RecordLevel::Unknown(level.into()));
+            record.timestamp = cell(timestamp.as_deref(), row);
+            record.thread_name = cell(thread_name.as_deref(), row).map(String::from);
+            record.thread_id = cell(thread_id.as_deref(), row);
+            record.process_id = cell(process_id.as_deref(), row);
+            if let Some(key_values) = &key_values {
+                record.key_values = key_values[row].iter().copied().collect();
+            }
+            Ok(record)
+        })
+        .collect()
+}
+
+/// The value of an optional column in a row.
+fn cell<T: Copy>(column: Option<&[Option<T>]>, row: usize) -> Option<T> {
+    column.and_then(|column| column[row])
+}
+
+/// The values of a text column of any string or dictionary type.
+pub(crate) fn strings<'a>(array: &'a dyn Array, name: &'static str) -> Result<Vec<Option<&'a str>>, RecordBatchError> {
+    if let Some(dictionary) = array.as_any_dictionary_opt() {
+        let values = strings(dictionary.values().as_ref(), name)?;
+        if values.is_empty() {
+            return Ok(vec![None; array.len()]);
+        }
+        let keys = dictionary.normalized_keys().into_iter().enumerate();
+        return Ok(keys
+            .map(|(row, key)| if array.is_null(row) { None } else { values[key] })
+            .collect());
+    }
+    match array.data_type() {
+        DataType::Utf8 => Ok(array.as_string::<i32>().iter().collect()),
+        DataType::LargeUtf8 => Ok(array.as_string::<i64>().iter().collect()),
+        DataType::Utf8View => Ok(array.as_string_view().iter().collect()),
+        _ => Err(RecordBatchError::InvalidColumnType(name)),
+    }
+}
+
+fn numbers<T: ArrowPrimitiveType>(array: &dyn Array, name: &'static str) -> Result<Vec<Option<T::Native>>, RecordBatchError> {
+    let array = array
+        .as_primitive_opt::<T>()
+        .ok_or(RecordBatchError::InvalidColumnType(name))?;
+    Ok(array.iter().collect())
+}
+
+/// The nanoseconds since the Unix epoch of a timestamp column of any unit.
+fn timestamps(array: &dyn Array) -> Result<Vec<Option<u64>>, RecordBatchError> {
+    let (values, nanos_per_unit) = match array.data_type() {
+        DataType::Timestamp(TimeUnit::Second, _) => (numbers::<TimestampSecondType>(array, "timestamp")?, 1_000_000_000),
+        DataType::Timestamp(TimeUnit::Millisecond, _) => (numbers::<TimestampMillisecondType>(array, "timestamp")?, 1_000_000),
+        DataType::Timestamp(TimeUnit::Microsecond, _) => (numbers::<TimestampMicrosecondType>(array, "timestamp")?, 1000),
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => (numbers::<TimestampNanosecondType>(array, "timestamp")?, 1),
+        _ => return Err(RecordBatchError::InvalidColumnType("timestamp")),
+    };
+    values
+        .into_iter()
+        .map(|value| {
+            value
+                .map(|value| {
+                    u64::try_from(value)
+                        .ok()
+                        .and_then(|value| value.checked_mul(nanos_per_unit))
+                        .ok_or(RecordBatchError::TimestampOutOfRange)
+                })
+                .transpose()
+        })
+        .collect()
+}
+
+/// The key-value pairs of every row of a map column.
+fn key_values(array: &dyn Array) -> Result<Vec<Vec<(&str, &str)>>, RecordBatchError> {
+    let map = array.as_map_opt().ok_or(RecordBatchError::InvalidColumnType("key_values"))?;
+    let keys = strings(map.keys().as_ref(), "key_values")?;
+    let values = strings(map.values().as_ref(), "key_values")?;
+    let offsets = map.value_offsets();
+    Ok(offsets
+        .windows(2)
+        .enumerate()
+        .map(|(row, range)| {
+            if map.is_null(row) {
+                return Vec::new();
+            }
+            // Offsets are never negative.
+            let range = usize::try_from(range[0]).unwrap_or_default()..usize::try_from(range[1]).unwrap_or_default();
+            range
+                .filter_map(|entry| Some((keys[entry]?, values[entry].unwrap_or_default())))
+                .collect()
+        })
+        .collect())
+}
+
+/// The error returned when records cannot be converted into a batch or back.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum RecordBatchError {
+    Arrow(ArrowError),
+    /// A required column is missing from the batch.
+    MissingColumn(&'static str),
+    /// A column that records cannot leave empty has a null value.
+    NullValue(&'static str),
+    /// A column has a type that cannot be read into its record field.
+    InvalidColumnType(&'static str),
+    /// A timestamp lies before the Unix epoch or after the year 2262.
+    TimestampOutOfRange,
+}
+
+impl fmt::Display for RecordBatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Arrow(error) => write!(f, "Arrow error: {error}"),
+            Self::MissingColumn(name) => write!(f, "the record batch has no {name} column"),
+            Self::NullValue(name) => write!(f, "the {name} column has a null value"),
+            Self::InvalidColumnType(name) => write!(f, "the {name} column has an unsupported type"),
+            Self::TimestampOutOfRange => f.write_str("a timestamp is out of the range of Arrow nanosecond timestamps"),
+        }
+    }
+}
+
+impl core::error::Error for RecordBatchError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Arrow(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::{
+        types::{Int8Type, UInt16Type},
+        DictionaryArray, Int32Array, LargeStringArray, StringViewArray, TimestampMicrosecondArray, TimestampMillisecondArray,
+        TimestampSecondArray,
+    };
+
+    fn batch(columns: Vec<(&str, ArrayRef)>) -> RecordBatch {
+        RecordBatch::try_from_iter(columns).unwrap()
+    }
+
+    fn string_array(values: &[Option<&str>]) -> ArrayRef {
+        Arc::new(StringArray::from(values.to_vec()))
+    }
+
+    fn minimal(level: Option<&str>, args: Option<&str>, target: Option<&str>) -> Vec<(&'static str, ArrayRef)> {
+        vec![
+            ("level", string_array(&[level])),
+            ("args", string_array(&[args])),
+            ("target", string_array(&[target])),
+        ]
+    }
+
+    #[test]
+    fn flattens_nested_key_values() {
+        let mut record = SerializableLogRecord::new(Level::Info, "Hi".into(), "app".into(), None, None, None);
+        let mut http = KeyValues::new();
+        http.push("status", 404_u64);
+        record.key_values.push("http", Value::Nested(http));
+        record.key_values.push("ok", false);
+
+        let records = from_record_batch(&to_record_batch([&record]).unwrap()).unwrap();
+        let key_values: Vec<_> = records[0]
+            .key_values
+            .iter()
+            .map(|(key, value)| (key, value.to_string()))
+            .collect();
+        assert_eq!(key_values, [("http.status", "404".into()), ("ok", "false".into())]);
+    }
+
+    #[test]
+    fn reads_other_string_types() {
+        let target: DictionaryArray<Int8Type> = vec!["app"].into_iter().collect();
+        let file = DictionaryArray::<UInt16Type>::new(vec![0_u16].into(), Arc::new(LargeStringArray::from(vec!["src/main.rs"])));
+        let batch = batch(vec![
+            ("level", Arc::new(LargeStringArray::from(vec!["warn"]))),
+            ("args", Arc::new(StringViewArray::from(vec!["Hi"]))),
+            ("target", Arc::new(target)),
+            ("file", Arc::new(file)),
+            ("thread_name", Arc::new(StringViewArray::from(vec![None::<&str>]))),
+        ]);
+        let record = &from_record_batch(&batch).unwrap()[0];
+        assert_eq!(record.level, RecordLevel::Warn);
+        assert_eq!((record.args.as_str(), record.target.as_str()), ("Hi", "app"));
+        assert_eq!(record.file.as_deref(), Some("src/main.rs"));
+        assert_eq!(record.thread_name, None);
+    }
+
+    #[test]
+    fn reads_all_timestamp_units() {
+        let columns: [(ArrayRef, u64); 4] = [
+            (Arc::new(TimestampSecondArray::from(vec![2])), 2_000_000_000),
+            (Arc::new(TimestampMillisecondArray::from(vec![2])), 2_000_000),
+            (Arc::new(TimestampMicrosecondArray::from(vec![2])), 2000),
+            (Arc::new(TimestampNanosecondArray::from(vec![2]).with_timezone("UTC")), 2),
+        ];
+        for (timestamp, nanos) in columns {
+            let mut columns = minimal(Some("INFO"), Some("Hi"), Some("app"));
+            columns.push(("timestamp", timestamp));
+            assert_eq!(from_record_batch(&batch(columns)).unwrap()[0].timestamp, Some(nanos));
+        }
+    }
+
+    #[test]
+    fn reads_null_and_empty_levels_as_unknown() {
+        for level in [None, Some("")] {
+            let record = &from_record_batch(&batch(minimal(level, Some("Hi"), Some("app")))).unwrap()[0];
+            assert_eq!(record.level, RecordLevel::Unknown(String::new()));
+        }
+        let record = &from_record_batch(&batch(minimal(Some("notice"), Some("Hi"), Some("app")))).unwrap()[0];
+        assert_eq!(record.level, RecordLevel::Unknown("notice".into()));
+    }
+
+    #[test]
+    fn errors() {
+        let missing = batch(vec![
+            ("level", string_array(&[Some("INFO")])),
+            ("args", string_array(&[Some("Hi")])),
+        ]);
+        assert!(matches!(
+            from_record_batch(&missing),
+            Err(RecordBatchError::MissingColumn("target"))
+        ));
+
+        for (args, target, name) in [(None, Some("app"), "args"), (Some("Hi"), None, "target")] {
+            let result = from_record_batch(&batch(minimal(Some("INFO"), args, target)));
+            assert!(
+                matches!(result, Err(RecordBatchError::NullValue(column)) if column == name),
+                "{}",
+                name
+            );
+        }
+
+        let mut columns = minimal(Some("INFO"), Some("Hi"), Some("app"));
+        columns[1].1 = Arc::new(Int32Array::from(vec![1]));
+        assert!(matches!(
+            from_record_batch(&batch(columns)),
+            Err(RecordBatchError::InvalidColumnType("args"))
+        ));
+        let mut columns = minimal(Some("INFO"), Some("Hi"), Some("app"));
+        columns.push(("timestamp", Arc::new(Int32Array::from(vec![1]))));
+        assert!(matches!(
+            from_record_batch(&batch(columns)),
+            Err(RecordBatchError::InvalidColumnType("timestamp"))
+        ));
+        let mut columns = minimal(Some("INFO"), Some("Hi"), Some("app"));
+        columns.push(("line", string_array(&[Some("7")])));
+        assert!(matches!(
+            from_record_batch(&batch(columns)),
+            Err(RecordBatchError::InvalidColumnType("line"))
+        ));
+
+        for timestamp in [
+            Arc::new(TimestampNanosecondArray::from(vec![-1])) as ArrayRef,
+            Arc::new(TimestampSecondArray::from(vec![i64::MAX])),
+        ] {
+            let mut columns = minimal(Some("INFO"), Some("Hi"), Some("app"));
+            columns.push(("timestamp", timestamp));
+            assert!(matches!(
+                from_record_batch(&batch(columns)),
+                Err(RecordBatchError::TimestampOutOfRange)
+            ));
+        }
+        let record =
+            SerializableLogRecord::new(Level::Info, "Hi".into(), "app".into(), None, None, None).with_timestamp(Some(u64::MAX));
+        assert!(matches!(
+            to_record_batch([&record]),
+            Err(RecordBatchError::TimestampOutOfRange)
+        ));
+    }
+}
@@ -0,0 +1,142 @@
+use crate::{capture::CaptureOptions, kv::KeyValues, level::RecordLevel, SerializableLogRecord};
+use alloc::borrow::Cow;
+use log::Record;
+
+/// A borrowed variant of `SerializableLogRecord` for records that are serialized right away.
+///
+/// `target`, `module_path` and `file` borrow from the `log::Record`. `args` is only allocated if the
+/// `fmt::Arguments` are not a plain static string. Both types serialize to exactly the same bytes, so a
+/// `SerializableLogRecordRef` can be deserialized as a `SerializableLogRecord` and vice versa. With `serde`,
+/// deserializing a `SerializableLogRecordRef` borrows the strings from the input where the format allows it.
+///
+/// ```rust
+/// # use log::Level;
+/// use serializable_log_record::{SerializableLogRecord, SerializableLogRecordRef};
+///
+/// let record = log::Record::builder().args(format_args!("Hello")).level(Level::Info).target("my_target").build();
+/// let borrowed = SerializableLogRecordRef::from(&record);
+/// assert!(matches!(borrowed.args, std::borrow::Cow::Borrowed("Hello")));
+///
+/// let owned: SerializableLogRecord = borrowed.clone().into_owned();
+/// assert_eq!(SerializableLogRecordRef::from(&owned), borrowed);
+///
+/// # #[cfg(feature = "serde")]
+/// # {
+/// let json = serde_json::to_string(&borrowed).unwrap();
+/// assert_eq!(json, serde_json::to_string(&owned).unwrap());
+/// let deserialized: SerializableLogRecordRef = serde_json::from_str(&json).unwrap();
+/// assert!(matches!(deserialized.target, std::borrow::Cow::Borrowed("my_target")));
+/// # }
+/// # #[cfg(feature = "bincode2")]
+/// # {
+/// let config = bincode::config::standard();
+/// let bytes = bincode::encode_to_vec(&borrowed, config).unwrap();
+/// assert_eq!(bytes, bincode::encode_to_vec(&owned, config).unwrap());
+/// let (decoded, _): (SerializableLogRecordRef, _) = bincode::borrow_decode_from_slice(&bytes, config).unwrap();
+/// assert_eq!(decoded, borrowed);
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename = "SerializableLogRecord")
+)]
+#[cfg_attr(feature = "bincode2", derive(bincode::Encode, bincode::BorrowDecode))]
+#[non_exhaustive]
+pub struct SerializableLogRecordRef<'a> {
+    pub level: RecordLevel,
+    #[cfg_attr(feature = "serde", serde(borrow))]
+    pub args: Cow<'a, str>,
+    #[cfg_attr(feature = "serde", serde(borrow))]
+    pub target: Cow<'a, str>,
+    #[cfg_attr(feature = "serde", serde(default, borrow, with = "option_cow"))]
+    pub module_path: Option<Cow<'a, str>>,
+    #[cfg_attr(feature = "serde", serde(default, borrow, with = "option_cow"))]
+    pub file: Option<Cow<'a, str>>,
+    pub line: Option<u32>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub key_values: KeyValues,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub module_path_static: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub file_static: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub timestamp: Option<u64>,
+    #[cfg_attr(feature = "serde", serde(default, borrow, with = "option_cow"))]
+    pub thread_name: Option<Cow<'a, str>>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub thread_id: Option<u64>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub process_id: Option<u32>,
+}
+
+impl SerializableLogRecordRef<'_> {
+    /// Convert into an owned `SerializableLogRecord`, allocating only the strings that are still borrowed.
+    #[must_use]
+    pub fn into_owned(self) -> SerializableLogRecord {
+        SerializableLogRecord {
+            level: self.level,
+            args: self.args.into_owned(),
+            target: self.target.into_owned(),
+            module_path: self.module_path.map(Cow::into_owned),
+            file: self.file.map(Cow::into_owned),
+            line: self.line,
+            key_values: self.key_values,
+            module_path_static: self.module_path_static,
+            file_static: self.file_static,
+            timestamp: self.timestamp,
+            thread_name: self.thread_name.map(Cow::into_owned),
+            thread_id: self.thread_id,
+            process_id: self.process_id,
+        }
+    }
+}
+
+impl<'a> From<&Record<'a>> for SerializableLogRecordRef<'a> {
+    /// Convert a `log::Record` to a `SerializableLogRecordRef`, see `SerializableLogRecord::from`.
+    fn from(record: &Record<'a>) -> Self {
+        CaptureOptions::default().capture_ref(record)
+    }
+}
+
+impl<'a> From<&'a SerializableLogRecord> for SerializableLogRecordRef<'a> {
+    /// Borrow all strings of a `SerializableLogRecord`.
+    fn from(record: &'a SerializableLogRecord) -> Self {
+        Self {
+            level: record.level.clone(),
+            args: Cow::Borrowed(&record.args),
+            target: Cow::Borrowed(&record.target),
+            module_path: record.module_path.as_deref().map(Cow::Borrowed),
+            file: record.file.as_deref().map(Cow::Borrowed),
+            line: record.line,
+            key_values: record.key_values.clone(),
+            module_path_static: record.module_path_static,
+            file_static: record.file_static,
+            timestamp: record.timestamp,
+            thread_name: record.thread_name.as_deref().map(Cow::Borrowed),
+            thread_id: record.thread_id,
+            process_id: record.process_id,
+        }
+    }
+}
+
+/// Serde only borrows a `Cow<str>` directly in a field, not inside an `Option`.
+#[cfg(feature = "serde")]
+mod option_cow {
+    use alloc::borrow::Cow;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(transparent)]
+    struct Borrowed<'a>(#[serde(borrow)] Cow<'a, str>);
+
+    #[allow(clippy::ref_option)]
+    pub(super) fn serialize<S: Serializer>(value: &Option<Cow<'_, str>>, serializer: S) -> Result<S::Ok, S::Error> {
+        value.serialize(serializer)
+    }
+
+    pub(super) fn deserialize<'de: 'a, 'a, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Cow<'a, str>>, D::Error> {
+        Ok(Option::<Borrowed<'a>>::deserialize(deserializer)?.map(|borrowed| borrowed.0))
+    }
+}
@@ -0,0 +1,325 @@
+//! JSON lines in the format of bunyan and pino, so that the viewers of the Node.js ecosystem can display them.
+//!
+//! A record maps to the core fields of bunyan as follows:
+//!
+//! | `SerializableLogRecord` | bunyan / pino                                                             |
+//! |-------------------------|---------------------------------------------------------------------------|
+//! | `level`                 | `level` as 10 (trace), 20 (debug), 30 (info), 40 (warn) or 50 (error)      |
+//! | `args`                  | `msg`                                                                     |
+//! | `timestamp`             | `time` as an RFC 3339 string with milliseconds, or milliseconds since the Unix epoch for pino |
+//! | `process_id`            | `pid`, the pid configured on the codec (the current process by default) if the record has none |
+//! | `module_path`, `file`, `line` | `src.func`, `src.file`, `src.line`                                  |
+//! | `target`, `thread_name`, `thread_id` | `target`, `thread_name`, `thread_id`                         |
+//! | `key_values`            | top-level fields, nested key-values as nested objects                     |
+//!
+//! `v` is always 0, `name` and `hostname` are configured on the `BunyanCodec`. `Unknown` levels are written as 30
+//! with their text in `level_name`. Keys that collide with one of the fields above get a `kv_` prefix, and so do keys
+//! that are already `kv_` followed by such a key, so that `kv_msg` is written as `kv_kv_msg`.
+//!
+//! Parsing accepts the output of bunyan and pino: `level` may also be a label such as `"warn"`, levels between the
+//! standard ones round down and 60 (fatal) becomes `Error`, and `time` may be a string or a number. `name` becomes
+//! the target if there is no `target` field and `hostname` is dropped. Every other field becomes a key-value, without
+//! the `kv_` prefix of colliding keys.
+//!
+//! ```rust
+//! use log::Level;
+//! use serializable_log_record::bunyan::{BunyanCodec, TimeFormat};
+//! use serializable_log_record::{codec::Codec, SerializableLogRecord, SerializableLogRecordRef};
+//!
+//! let mut record = SerializableLogRecord::new(Level::Warn, "Disk full".into(), "app::disk".into(), None, Some("src/disk.rs".into()), Some(7))
+//!     .with_timestamp(Some(1_700_000_000_123_456_789));
+//! record.key_values.push("free", 0_u64);
+//!
+//! let codec = BunyanCodec::new("app", "web-1").with_pid(42);
+//! let mut line = Vec::new();
+//! codec.encode(&SerializableLogRecordRef::from(&record), &mut line).unwrap();
+//! assert_eq!(
+//!     String::from_utf8(line).unwrap(),
+//!     concat!(
+//!         r#"{"free":0,"hostname":"web-1","level":40,"msg":"Disk full","name":"app","pid":42,"#,
+//!         r#""src":{"file":"src/disk.rs","line":7},"target":"app::disk","time":"2023-11-14T22:13:20.123Z","v":0}"#
+//!     )
+//! );
+//!
+//! let pino = BunyanCodec::new("app", "web-1").with_time_format(TimeFormat::EpochMillis);
+//! let mut line = Vec::new();
+//! pino.encode(&SerializableLogRecordRef::from(&record), &mut line).unwrap();
+//! assert!(String::from_utf8(line).unwrap().contains(r#""time":1700000000123,"#));
+//!
+//! let parsed = codec
+//!     .decode(br#"{"level":50,"time":1700000000500,"pid":7,"hostname":"db-2","name":"api","msg":"Timeout","req":{"id":3}}"#)
+//!     .unwrap();
+//! assert_eq!(parsed.level, Level::Error.into());
+//! assert_eq!(parsed.target, "api");
+//! assert_eq!(parsed.process_id, Some(7));
+//! assert_eq!(parsed.timestamp, Some(1_700_000_000_500_000_000));
+//! assert_eq!(parsed.key_values.get("req").unwrap().to_string(), "{id: 3}");
+//! ```
+
+use crate::{
+    codec::Codec,
+    json,
+    level::{LevelFallback, RecordLevel},
+    rfc3339::{self, Rfc3339},
+    SerializableLogRecord, SerializableLogRecordRef,
+};
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::convert::TryFrom;
+use log::Level;
+use serde_json::{Map, Value};
+
+/// The fields written for a record, which key-values must not overwrite.
+const RECORD_FIELDS: [&str; 12] = [
+    "v",
+    "level",
+    "name",
+    "hostname",
+    "pid",
+    "time",
+    "msg",
+    "src",
+    "target",
+    "thread_name",
+    "thread_id",
+    "level_name",
+];
+
+/// How `time` is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TimeFormat {
+    /// An RFC 3339 string with milliseconds, as written by bunyan.
+    #[default]
+    Rfc3339,
+    /// Milliseconds since the Unix epoch, as written by pino.
+    EpochMillis,
+}
+
+/// Encodes records as bunyan or pino JSON lines without a trailing newline and decodes them back, see the module
+/// documentation. Use it with `framing::Framing::Newline`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BunyanCodec {
+    name: String,
+    hostname: String,
+    pid: u32,
+    time_format: TimeFormat,
+}
+
+impl BunyanCodec {
+    /// A codec that writes the given logger `name` and `hostname` into every line.
+    pub fn new(name: impl Into<String>, hostname: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            hostname: hostname.into(),
+            #[cfg(feature = "std")]
+            pid: std::process::id(),
+            #[cfg(not(feature = "std"))]
+            pid: 0,
+            time_format: TimeFormat::default(),
+        }
+    }
+
+    /// The `pid` of records without a `process_id`, by default the current process with `std` and 0 without.
+    #[must_use]
+    pub fn with_pid(mut self, pid: u32) -> Self {
+        self.pid = pid;
+        self
+    }
+
+    #[must_use]
+    pub fn with_time_format(mut self, time_format: TimeFormat) -> Self {
+        self.time_format = time_format;
+        self
+    }
+
+    /// The line of a record as a JSON object.
+    #[must_use]
+    pub fn to_json(&self, record: &SerializableLogRecordRef<'_>) -> Map<String, Value> {
+        let mut line = Map::new();
+        line.insert("v".into(), 0.into());
+        line.insert("level".into(), level_number(&record.level).into());
+        if let RecordLevel::Unknown(text) = &record.level {
+            line.insert("level_name".into(), text.as_str().into());
+        }
+        line.insert("name".into(), self.name.as_str().into());
+        line.insert("hostname".into(), self.hostname.as_str().into());
+        line.insert("pid".into(), record.process_id.unwrap_or(self.pid).into());
+        if let Some(nanos) = record.timestamp {
+            let time = match self.time_format {
+                TimeFormat::Rfc3339 => Rfc3339 { nanos, digits: 3 }.to_string().into(),
+                TimeFormat::EpochMillis => (nanos / 1_000_000).into(),
+            };
+            line.insert("time".into(), time);
+        }
+        line.insert("msg".into(), record.args.as_ref().into());
+
+        let mut src = Map::new();
+        if let Some(file) = &record.file {
+            src.insert("file".into(), file.as_ref().into());
+        }
+        if let Some(line) = record.line {
+            src.insert("line".into(), line.into());
+        }
+        if let Some(module_path) = &record.module_path {
+            src.insert("func".into(), module_path.as_ref().into());
+        }
+        if !src.is_empty() {
+            line.insert("src".into(), Value::Object(src));
+        }
+        line.insert("target".into(), record.target.as_ref().into());
+        if let Some(thread_name) = &record.thread_name {
+            line.insert("thread_name".into(), thread_name.as_ref().into());
+        }
+        if let Some(thread_id) = record.thread_id {
+            line.insert("thread_id".into(), thread_id.into());
+        }
+
+        for (key, value) in record.key_values.iter() {
+            line.insert(json::escape_key(key, &RECORD_FIELDS), json::kv_to_value(value));
+        }
+        line
+    }
+
+    /// Parse a bunyan or pino line from a JSON object.
+    ///
+    /// # Errors
+    /// Returns an error if `time` is a string but not an RFC 3339 timestamp.
+    pub fn from_json(&self, line: Map<String, Value>) -> Result<SerializableLogRecord, BunyanError> {
+        let mut record = SerializableLogRecord::new(Level::Info, String::new(), String::new(), None, None, None);
+        let mut name = None;
+        let mut level_name = None;
+        for (key, value) in line {
+            let text = || value.as_str().map_or_else(|| value.to_string(), String::from);
+            let number = || value.as_u64().or_else(|| value.as_str()?.parse().ok());
+            match key.as_str() {
+                "v" | "hostname" => {}
+                "level" => {
+                    record.level = match value.as_u64() {
+                        Some(number) => level_from_number(number),
+                        None => RecordLevel::parse(&text(), LevelFallback::Keep).unwrap_or(record.level),
+                    }
+                }
+                "level_name" => level_name = Some(text()),
+                "name" => name = Some(text()),
+                "pid" => record.process_id = number().and_then(|pid| u32::try_from(pid).ok()),
+                "time" => {
+                    record.timestamp = match &value {
+                        Value::String(time) => {
+                            Some(rfc3339::parse(time).ok_or_else(|| BunyanError::InvalidTimestamp(time.clone()))?)
+                        }
+                        value => value.as_u64().and_then(|millis| millis.checked_mul(1_000_000)),
+                    }
+                }
+                "msg" => record.args = text(),
+                "src" => {
+                    if let Value::Object(src) = &value {
+                        let src_text = |key| {
+                            src.get(key)
+                                .map(|value: &Value| value.as_str().map_or_else(|| value.to_string(), String::from))
+                        };
+                        record.file = src_text("file");
+                        record.line = src
+                            .get("line")
+                            .and_then(Value::as_u64)
+                            .and_then(|line| u32::try_from(line).ok());
+                        record.module_path = src_text("func");
+                    }
+                }
+                "target" => record.target = text(),
+                "thread_name" => record.thread_name = Some(text()),
+                "thread_id" => record.thread_id = number(),
+                key => record
+                    .key_values
+                    .push(json::unescape_key(key, &RECORD_FIELDS), json::value_to_kv(value)),
+            }
+        }
+        if record.target.is_empty() {
+            record.target = name.unwrap_or_default();
+        }
+        if let (Some(level_name), 30) = (level_name, level_number(&record.level)) {
+            record.level = RecordLevel::parse(&level_name, LevelFallback::Keep).unwrap_or(record.level);
+        }
+        Ok(record)
+    }
+}
+
+impl Codec for BunyanCodec {
+    type Error = BunyanError;
+
+    fn encode(&self, record: &SerializableLogRecordRef<'_>, buf: &mut Vec<u8>) -> Result<(), Self::Error> {
+        buf.extend_from_slice(&serde_json::to_vec(&self.to_json(record)).map_err(BunyanError::Json)?);
+        Ok(())
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<SerializableLogRecord, Self::Error> {
+        self.from_json(json::parse_object(bytes)?)
+    }
+}
+
+/// The numeric bunyan level of a record level. `Unknown` levels are info.
+#[must_use]
+pub fn level_number(level: &RecordLevel) -> u8 {
+    match level {
+        RecordLevel::Trace => 10,
+        RecordLevel::Debug => 20,
+        RecordLevel::Info | RecordLevel::Unknown(_) => 30,
+        RecordLevel::Warn => 40,
+        RecordLevel::Error => 50,
+    }
+}
+
+fn level_from_number(number: u64) -> RecordLevel {
+    match number {
+        0..=19 => RecordLevel::Trace,
+        20..=29 => RecordLevel::Debug,
+        30..=39 => RecordLevel::Info,
+        40..=49 => RecordLevel::Warn,
+        _ => RecordLevel::Error,
+    }
+}
+
+/// The error returned when a bunyan or pino line cannot be encoded or parsed.
+pub use crate::json::JsonError as BunyanError;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colliding_keys_round_trip() {
+        let mut record = SerializableLogRecord::new(Level::Info, "Hi".into(), "app".into(), None, None, None);
+        record.key_values.push("msg", "spoofed");
+        record.key_values.push("pid", 1_u64);
+        record.key_values.push("kv_other", 2_u64);
+        record.key_values.push("kv_msg", "literal");
+        let codec = BunyanCodec::new("app", "web-1");
+        let line = codec.to_json(&SerializableLogRecordRef::from(&record));
+        assert_eq!(line["msg"], "Hi");
+        assert_eq!(line["kv_msg"], "spoofed");
+        assert_eq!(line["kv_pid"], 1);
+        assert_eq!(line["kv_kv_msg"], "literal");
+
+        let parsed = codec.from_json(line).unwrap();
+        assert_eq!(parsed.key_values.get("msg").unwrap().to_string(), "spoofed");
+        assert_eq!(parsed.key_values.get("pid").unwrap().to_string(), "1");
+        assert_eq!(parsed.key_values.get("kv_other").unwrap().to_string(), "2");
+        assert_eq!(parsed.key_values.get("kv_msg").unwrap().to_string(), "literal");
+        assert_eq!(parsed.args, "Hi");
+    }
+
+    #[test]
+    fn default_pid() {
+        let mut record = SerializableLogRecord::new(Level::Info, "Hi".into(), "app".into(), None, None, None);
+        let line = BunyanCodec::new("app", "web-1").to_json(&SerializableLogRecordRef::from(&record));
+        #[cfg(feature = "std")]
+        assert_eq!(line["pid"], std::process::id());
+        #[cfg(not(feature = "std"))]
+        assert_eq!(line["pid"], 0);
+        let codec = BunyanCodec::new("app", "web-1").with_pid(42);
+        assert_eq!(codec.to_json(&SerializableLogRecordRef::from(&record))["pid"], 42);
+        record.process_id = Some(7);
+        assert_eq!(codec.to_json(&SerializableLogRecordRef::from(&record))["pid"], 7);
+    }
+}
@@ -0,0 +1,167 @@
+//! Options for the fields that are captured from the environment when converting a `log::Record`.
+//!
+//! The timestamp, the thread name, the thread id and the process id are only captured with the `std` feature.
+//! Each of them can be turned off individually:
+//!
+//! ```rust
+//! use serializable_log_record::capture::CaptureOptions;
+//!
+//! let record = log::Record::builder().args(format_args!("Hello")).build();
+//! let serializable_record = CaptureOptions::default().with_thread_name(false).with_process_id(false).capture(&record);
+//! assert_eq!(serializable_record.thread_name(), None);
+//! assert_eq!(serializable_record.process_id(), None);
+//! # #[cfg(feature = "std")]
+//! assert!(serializable_record.thread_id().is_some());
+//! ```
+
+use crate::{kv, SerializableLogRecord, SerializableLogRecordRef};
+use alloc::{borrow::Cow, string::ToString};
+use log::Record;
+
+/// Which environment fields to capture when converting a `log::Record`. All of them are captured by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct CaptureOptions {
+    timestamp: bool,
+    thread_name: bool,
+    thread_id: bool,
+    process_id: bool,
+}
+
+impl Default for CaptureOptions {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+impl CaptureOptions {
+    /// Capture every field.
+    #[must_use]
+    pub const fn all() -> Self {
+        Self {
+            timestamp: true,
+            thread_name: true,
+            thread_id: true,
+            process_id: true,
+        }
+    }
+
+    /// Capture none of the fields.
+    #[must_use]
+    pub const fn none() -> Self {
+        Self {
+            timestamp: false,
+            thread_name: false,
+            thread_id: false,
+            process_id: false,
+        }
+    }
+
+    #[must_use]
+    pub const fn with_timestamp(mut self, capture: bool) -> Self {
+        self.timestamp = capture;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_thread_name(mut self, capture: bool) -> Self {
+        self.thread_name = capture;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_thread_id(mut self, capture: bool) -> Self {
+        self.thread_id = capture;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_process_id(mut self, capture: bool) -> Self {
+        self.process_id = capture;
+        self
+    }
+
+    /// Convert a `log::Record` to a `SerializableLogRecord`, capturing the enabled fields.
+    #[must_use]
+    pub fn capture(&self, record: &Record<'_>) -> SerializableLogRecord {
+        self.capture_ref(record).into_owned()
+    }
+
+    /// Convert a `log::Record` to a `SerializableLogRecordRef`, capturing the enabled fields.
+    #[must_use]
+    pub fn capture_ref<'a>(&self, record: &Record<'a>) -> SerializableLogRecordRef<'a> {
+        SerializableLogRecordRef {
+            level: record.level().into(),
+            args: record
+                .args()
+                .as_str()
+                .map_or_else(|| Cow::Owned(record.args().to_string()), Cow::Borrowed),
+            target: Cow::Borrowed(record.target()),
+            module_path: record.module_path().map(Cow::Borrowed),
+            file: record.file().map(Cow::Borrowed),
+            line: record.line(),
+            key_values: kv::capture(record),
+            module_path_static: record.module_path_static().is_some(),
+            file_static: record.file_static().is_some(),
+            timestamp: self.timestamp.then(env::timestamp).flatten(),
+            thread_name: self.thread_name.then(env::thread_name).flatten().map(Cow::Owned),
+            thread_id: self.thread_id.then(env::thread_id).flatten(),
+            process_id: self.process_id.then(env::process_id).flatten(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+pub(crate) mod env {
+    use alloc::string::String;
+    use core::{
+        convert::TryFrom,
+        sync::atomic::{AtomicU64, Ordering},
+    };
+
+    /// The current wall-clock time in nanoseconds since the Unix epoch.
+    pub(crate) fn timestamp() -> Option<u64> {
+        let since_epoch = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).ok()?;
+        u64::try_from(since_epoch.as_nanos()).ok()
+    }
+
+    pub(super) fn thread_name() -> Option<String> {
+        std::thread::current().name().map(Into::into)
+    }
+
+    /// A process-unique number for the current thread, assigned in the order in which threads first capture a record.
+    /// `std::thread::ThreadId` has no stable numeric representation, so it cannot be used here.
+    pub(super) fn thread_id() -> Option<u64> {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        std::thread_local! {
+            static ID: u64 = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        }
+        ID.try_with(|id| *id).ok()
+    }
+
+    #[allow(clippy::unnecessary_wraps)]
+    pub(super) fn process_id() -> Option<u32> {
+        Some(std::process::id())
+    }
+}
+
+#[cfg(not(feature = "std"))]
+mod env {
+    use alloc::string::String;
+
+    pub(crate) fn timestamp() -> Option<u64> {
+        None
+    }
+
+    pub(super) fn thread_name() -> Option<String> {
+        None
+    }
+
+    pub(super) fn thread_id() -> Option<u64> {
+        None
+    }
+
+    pub(super) fn process_id() -> Option<u32> {
+        None
+    }
+}
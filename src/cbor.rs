@@ -0,0 +1,527 @@
+//! CBOR encoding of records with `minicbor`, which works without `std`.
+//!
+//! A record is a map with fixed integer keys, described by the following CDDL schema. The schema is also published as
+//! `schema/log_record.cddl` in the repository.
+//!
+#![doc = concat!("```cddl\n", include_str!("../schema/log_record.cddl"), "```")]
+//!
+//! ```rust
+//! use log::Level;
+//! use serializable_log_record::{cbor, SerializableLogRecord};
+//!
+//! let record = SerializableLogRecord::new(Level::Warn, "Hi".into(), "app".into(), None, None, Some(7));
+//!
+//! // {0: 2, 1: "Hi", 2: "app", 5: 7}
+//! let bytes = [0xA4, 0x00, 0x02, 0x01, 0x62, b'H', b'i', 0x02, 0x63, b'a', b'p', b'p', 0x05, 0x07];
+//! assert_eq!(cbor::to_vec(&record), bytes);
+//! assert_eq!(cbor::from_slice(&bytes).unwrap(), record);
+//! ```
+//!
+//! Decoding is tolerant of what other encoders produce:
+//!
+//! ```rust
+//! # use log::Level;
+//! # use serializable_log_record::{cbor, kv::Value, SerializableLogRecord};
+//! // {_ 0: "WARN", 1: "Hi", 2: "app", 3: null, 5: 7, 6: [["k", -1], ["n", [["f", 1.5]]]], 99: true}
+//! let bytes = [
+//!     0xBF,
+//!     0x00, 0x64, b'W', b'A', b'R', b'N',
+//!     0x01, 0x62, b'H', b'i',
+//!     0x02, 0x63, b'a', b'p', b'p',
+//!     0x03, 0xF6,
+//!     0x05, 0x07,
+//!     0x06, 0x82,
+//!         0x82, 0x61, b'k', 0x20,
+//!         0x82, 0x61, b'n', 0x81, 0x82, 0x61, b'f', 0xFA, 0x3F, 0xC0, 0x00, 0x00,
+//!     0x18, 0x63, 0xF5,
+//!     0xFF,
+//! ];
+//! let record = cbor::from_slice(&bytes).unwrap();
+//!
+//! let mut expected = SerializableLogRecord::new(Level::Warn, "Hi".into(), "app".into(), None, None, Some(7));
+//! expected.key_values.push("k", -1i64);
+//! expected.key_values.push("n", Value::Nested(vec![("f".into(), Value::F64(1.5))].into()));
+//! assert_eq!(record, expected);
+//!
+//! // Unknown levels are kept as text, the flags are only written when set.
+//! // {0: "NOTICE", 1: "", 2: "", 4: "main.rs", 8: true, 12: 1000}
+//! let bytes = [
+//!     0xA6,
+//!     0x00, 0x66, b'N', b'O', b'T', b'I', b'C', b'E',
+//!     0x01, 0x60,
+//!     0x02, 0x60,
+//!     0x04, 0x67, b'm', b'a', b'i', b'n', b'.', b'r', b's',
+//!     0x08, 0xF5,
+//!     0x0C, 0x19, 0x03, 0xE8,
+//! ];
+//! let record = cbor::from_slice(&bytes).unwrap();
+//! assert_eq!(record.level.as_str(), "NOTICE");
+//! assert_eq!(record.file.as_deref(), Some("main.rs"));
+//! assert!(record.file_static);
+//! assert_eq!(record.process_id(), Some(1000));
+//! assert_eq!(cbor::to_vec(&record), bytes);
+//!
+//! // The target (key 2) is missing.
+//! assert!(cbor::from_slice(&[0xA2, 0x00, 0x02, 0x01, 0x60]).is_err());
+//! ```
+
+use crate::{
+    codec::Codec,
+    kv::{KeyValues, Value},
+    level::{LevelFallback, RecordLevel},
+    SerializableLogRecord, SerializableLogRecordRef,
+};
+use alloc::{borrow::Cow, string::String, vec::Vec};
+use core::{convert::TryFrom, fmt};
+use minicbor::{
+    data::Type,
+    decode::{self, Decoder},
+    encode::{self, Encoder, Write},
+    Decode, Encode,
+};
+
+/// Encode a record as CBOR into any `minicbor` writer, e.g. a `&mut [u8]` without allocating.
+///
+/// # Errors
+/// Returns an error if the writer fails.
+pub fn encode<W: Write>(record: &SerializableLogRecordRef<'_>, writer: W) -> Result<(), encode::Error<W::Error>> {
+    minicbor::encode(record, writer)
+}
+
+/// Encode a record as CBOR.
+#[must_use]
+pub fn to_vec(record: &SerializableLogRecord) -> Vec<u8> {
+    let mut buf = Vec::new();
+    // Writing into a `Vec` cannot fail.
+    let _ = encode(&SerializableLogRecordRef::from(record), &mut buf);
+    buf
+}
+
+/// Decode a record from exactly the given bytes.
+///
+/// # Errors
+/// Returns an error if the bytes are not a valid record.
+pub fn from_slice(bytes: &[u8]) -> Result<SerializableLogRecord, CborError> {
+    let mut decoder = Decoder::new(bytes);
+    let record: SerializableLogRecordRef<'_> = decoder.decode().map_err(CborError::Decode)?;
+    match bytes.len() - decoder.position() {
+        0 => Ok(record.into_owned()),
+        trailing => Err(CborError::TrailingBytes(trailing)),
+    }
+}
+
+/// Encodes records as CBOR maps with integer keys.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CborCodec;
+
+impl Codec for CborCodec {
+    type Error = CborError;
+
+    fn encode(&self, record: &SerializableLogRecordRef<'_>, buf: &mut Vec<u8>) -> Result<(), Self::Error> {
+        // Writing into a `Vec` cannot fail.
+        let _ = encode(record, buf);
+        Ok(())
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<SerializableLogRecord, Self::Error> {
+        from_slice(bytes)
+    }
+}
+
+/// The error returned when a record cannot be decoded from CBOR.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CborError {
+    Decode(decode::Error),
+    /// The bytes contained more than one record.
+    TrailingBytes(usize),
+}
+
+impl fmt::Display for CborError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Decode(error) => write!(f, "CBOR decoding failed: {error}"),
+            Self::TrailingBytes(count) => write!(f, "{count} trailing bytes after the record"),
+        }
+    }
+}
+
+impl core::error::Error for CborError {}
+
+const LEVEL: u64 = 0;
+const ARGS: u64 = 1;
+const TARGET: u64 = 2;
+const MODULE_PATH: u64 = 3;
+const FILE: u64 = 4;
+const LINE: u64 = 5;
+const KEY_VALUES: u64 = 6;
+const MODULE_PATH_STATIC: u64 = 7;
+const FILE_STATIC: u64 = 8;
+const TIMESTAMP: u64 = 9;
+const THREAD_NAME: u64 = 10;
+const THREAD_ID: u64 = 11;
+const PROCESS_ID: u64 = 12;
+
+impl<C> Encode<C> for SerializableLogRecordRef<'_> {
+    fn encode<W: Write>(&self, e: &mut Encoder<W>, ctx: &mut C) -> Result<(), encode::Error<W::Error>> {
+        let optional = [
+            self.module_path.is_some(),
+            self.file.is_some(),
+            self.line.is_some(),
+            !self.key_values.is_empty(),
+            self.module_path_static,
+            self.file_static,
+            self.timestamp.is_some(),
+            self.thread_name.is_some(),
+            self.thread_id.is_some(),
+            self.process_id.is_some(),
+        ];
+        e.map(3 + optional.iter().filter(|present| **present).count() as u64)?;
+
+        e.u64(LEVEL)?.encode_with(&self.level, ctx)?;
+        e.u64(ARGS)?.str(&self.args)?;
+        e.u64(TARGET)?.str(&self.target)?;
+        if let Some(module_path) = &self.module_path {
+            e.u64(MODULE_PATH)?.str(module_path)?;
+        }
+        if let Some(file) = &self.file {
+            e.u64(FILE)?.str(file)?;
+        }
+        if let Some(line) = self.line {
+            e.u64(LINE)?.u32(line)?;
+        }
+        if !self.key_values.is_empty() {
+            e.u64(KEY_VALUES)?.encode_with(&self.key_values, ctx)?;
+        }
+        if self.module_path_static {
+            e.u64(MODULE_PATH_STATIC)?.bool(true)?;
+        }
+        if self.file_static {
+            e.u64(FILE_STATIC)?.bool(true)?;
+        }
+        if let Some(timestamp) = self.timestamp {
+            e.u64(TIMESTAMP)?.u64(timestamp)?;
+        }
+        if let Some(thread_name) = &self.thread_name {
+            e.u64(THREAD_NAME)?.str(thread_name)?;
+        }
+        if let Some(thread_id) = self.thread_id {
+            e.u64(THREAD_ID)?.u64(thread_id)?;
+        }
+        if let Some(process_id) = self.process_id {
+            e.u64(PROCESS_ID)?.u32(process_id)?;
+        }
+        Ok(())
+    }
+}
+
+impl<C> Encode<C> for SerializableLogRecord {
+    fn encode<W: Write>(&self, e: &mut Encoder<W>, ctx: &mut C) -> Result<(), encode::Error<W::Error>> {
+        SerializableLogRecordRef::from(self).encode(e, ctx)
+    }
+}
+
+impl<'b, C> Decode<'b, C> for SerializableLogRecordRef<'b> {
+    fn decode(d: &mut Decoder<'b>, ctx: &mut C) -> Result<Self, decode::Error> {
+        let start = d.position();
+        let mut level = None;
+        let mut args = None;
+        let mut target = None;
+        let mut record = SerializableLogRecordRef {
+            level: RecordLevel::Error,
+            args: Cow::Borrowed(""),
+            target: Cow::Borrowed(""),
+            module_path: None,
+            file: None,
+            line: None,
+            key_values: KeyValues::new(),
+            module_path_static: false,
+            file_static: false,
+            timestamp: None,
+            thread_name: None,
+            thread_id: None,
+            process_id: None,
+        };
+
+        let len = d.map()?;
+        for_each_item(d, len, |d| {
+            let key = match d.datatype()? {
+                Type::U8 | Type::U16 | Type::U32 | Type::U64 => d.u64()?,
+                _ => {
+                    d.skip()?;
+                    return d.skip();
+                }
+            };
+            match key {
+                LEVEL => level = Some(d.decode_with(ctx)?),
+                ARGS => args = Some(d.str()?),
+                TARGET => target = Some(d.str()?),
+                MODULE_PATH => record.module_path = nullable(d, Decoder::str)?.map(Cow::Borrowed),
+                FILE => record.file = nullable(d, Decoder::str)?.map(Cow::Borrowed),
+                LINE => record.line = nullable(d, Decoder::u32)?,
+                KEY_VALUES => record.key_values = d.decode_with(ctx)?,
+                MODULE_PATH_STATIC => record.module_path_static = d.bool()?,
+                FILE_STATIC => record.file_static = d.bool()?,
+                TIMESTAMP => record.timestamp = nullable(d, Decoder::u64)?,
+                THREAD_NAME => record.thread_name = nullable(d, Decoder::str)?.map(Cow::Borrowed),
+                THREAD_ID => record.thread_id = nullable(d, Decoder::u64)?,
+                PROCESS_ID => record.process_id = nullable(d, Decoder::u32)?,
+                _ => d.skip()?,
+            }
+            Ok(())
+        })?;
+
+        record.level = level.ok_or_else(|| decode::Error::message("missing level (key 0)").at(start))?;
+        record.args = Cow::Borrowed(args.ok_or_else(|| decode::Error::message("missing args (key 1)").at(start))?);
+        record.target = Cow::Borrowed(target.ok_or_else(|| decode::Error::message("missing target (key 2)").at(start))?);
+        Ok(record)
+    }
+}
+
+impl<'b, C> Decode<'b, C> for SerializableLogRecord {
+    fn decode(d: &mut Decoder<'b>, ctx: &mut C) -> Result<Self, decode::Error> {
+        SerializableLogRecordRef::decode(d, ctx).map(SerializableLogRecordRef::into_owned)
+    }
+}
+
+impl<C> Encode<C> for RecordLevel {
+    fn encode<W: Write>(&self, e: &mut Encoder<W>, _ctx: &mut C) -> Result<(), encode::Error<W::Error>> {
+        match self.to_level() {
+            Some(level) => e.u8(level as u8)?,
+            None => e.str(self.as_str())?,
+        };
+        Ok(())
+    }
+}
+
+impl<'b, C> Decode<'b, C> for RecordLevel {
+    fn decode(d: &mut Decoder<'b>, _ctx: &mut C) -> Result<Self, decode::Error> {
+        let position = d.position();
+        match d.datatype()? {
+            Type::String => {
+                let text = d.str()?;
+                Ok(Self::parse(text, LevelFallback::Keep).unwrap_or_else(|_| Self::Unknown(text.into())))
+            }
+            _ => match d.u8()? {
+                1 => Ok(Self::Error),
+                2 => Ok(Self::Warn),
+                3 => Ok(Self::Info),
+                4 => Ok(Self::Debug),
+                5 => Ok(Self::Trace),
+                _ => Err(decode::Error::message("level must be 1 to 5 or a text").at(position)),
+            },
+        }
+    }
+}
+
+impl<C> Encode<C> for KeyValues {
+    fn encode<W: Write>(&self, e: &mut Encoder<W>, ctx: &mut C) -> Result<(), encode::Error<W::Error>> {
+        e.array(self.len() as u64)?;
+        for (key, value) in self.iter() {
+            e.array(2)?.str(key)?.encode_with(value, ctx)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'b, C> Decode<'b, C> for KeyValues {
+    fn decode(d: &mut Decoder<'b>, ctx: &mut C) -> Result<Self, decode::Error> {
+        let mut key_values = Self::new();
+        let len = d.array()?;
+        for_each_item(d, len, |d| {
+            let position = d.position();
+            if d.array()? != Some(2) {
+                return Err(decode::Error::message("expected a [key, value] pair").at(position));
+            }
+            let key = String::from(d.str()?);
+            key_values.push(key, d.decode_with::<C, Value>(ctx)?);
+            Ok(())
+        })?;
+        Ok(key_values)
+    }
+}
+
+impl<C> Encode<C> for Value {
+    fn encode<W: Write>(&self, e: &mut Encoder<W>, ctx: &mut C) -> Result<(), encode::Error<W::Error>> {
+        match self {
+            Self::Str(value) => e.str(value)?,
+            Self::I64(value) => e.i64(*value)?,
+            Self::U64(value) => e.u64(*value)?,
+            Self::F64(value) => e.f64(*value)?,
+            Self::Bool(value) => e.bool(*value)?,
+            Self::Nested(value) => e.encode_with(value, ctx)?,
+        };
+        Ok(())
+    }
+}
+
+impl<'b, C> Decode<'b, C> for Value {
+    fn decode(d: &mut Decoder<'b>, ctx: &mut C) -> Result<Self, decode::Error> {
+        let position = d.position();
+        match d.datatype()? {
+            Type::String => Ok(Self::Str(d.str()?.into())),
+            Type::U8 | Type::U16 | Type::U32 | Type::U64 => Ok(Self::U64(d.u64()?)),
+            Type::I8 | Type::I16 | Type::I32 | Type::I64 | Type::Int => {
+                let int = d.int()?;
+                i64::try_from(int)
+                    .map(Self::I64)
+                    .map_err(|_| decode::Error::message("integer out of range").at(position))
+            }
+            Type::F16 | Type::F32 | Type::F64 => Ok(Self::F64(d.f64()?)),
+            Type::Bool => Ok(Self::Bool(d.bool()?)),
+            Type::Array | Type::ArrayIndef => Ok(Self::Nested(d.decode_with(ctx)?)),
+            other => Err(decode::Error::type_mismatch(other)
+                .at(position)
+                .with_message("expected a key-value value")),
+        }
+    }
+}
+
+/// Call `f` for each item of a definite or indefinite length array or map.
+fn for_each_item<'b>(
+    d: &mut Decoder<'b>,
+    len: Option<u64>,
+    mut f: impl FnMut(&mut Decoder<'b>) -> Result<(), decode::Error>,
+) -> Result<(), decode::Error> {
+    match len {
+        Some(len) => (0..len).try_for_each(|_| f(d)),
+        None => loop {
+            if d.datatype()? == Type::Break {
+                d.set_position(d.position() + 1);
+                return Ok(());
+            }
+            f(d)?;
+        },
+    }
+}
+
+/// Decode a value that may also be `null`.
+fn nullable<'b, T>(
+    d: &mut Decoder<'b>,
+    f: impl FnOnce(&mut Decoder<'b>) -> Result<T, decode::Error>,
+) -> Result<Option<T>, decode::Error> {
+    if d.datatype()? == Type::Null {
+        d.null()?;
+        Ok(None)
+    } else {
+        f(d).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Level;
+
+    fn full_record() -> SerializableLogRecord {
+        let mut record = SerializableLogRecord::new(
+            Level::Error,
+            "Boom".into(),
+            "db".into(),
+            Some("m".into()),
+            Some("f".into()),
+            Some(300),
+        )
+        .with_timestamp(Some(1_700_000_000_000_000_000));
+        record.module_path_static = true;
+        record.thread_name = Some("t".into());
+        record.thread_id = Some(1);
+        record.process_id = Some(42);
+        record.key_values.push("i", -1_i64);
+        record.key_values.push("f", 0.5_f64);
+        record
+    }
+
+    #[rustfmt::skip]
+    const FULL: &[u8] = &[
+        0xAC,
+        0x00, 0x01,
+        0x01, 0x64, b'B', b'o', b'o', b'm',
+        0x02, 0x62, b'd', b'b',
+        0x03, 0x61, b'm',
+        0x04, 0x61, b'f',
+        0x05, 0x19, 0x01, 0x2C,
+        0x06, 0x82,
+            0x82, 0x61, b'i', 0x20,
+            0x82, 0x61, b'f', 0xFB, 0x3F, 0xE0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x07, 0xF5,
+        0x09, 0x1B, 0x17, 0x97, 0x9C, 0xFE, 0x36, 0x2A, 0x00, 0x00,
+        0x0A, 0x61, b't',
+        0x0B, 0x01,
+        0x0C, 0x18, 0x2A,
+    ];
+
+    #[test]
+    fn full_record_matches_vector() {
+        let record = full_record();
+        assert_eq!(to_vec(&record), FULL);
+        assert_eq!(from_slice(FULL).unwrap(), record);
+        assert_eq!(CborCodec.decode(FULL).unwrap(), record);
+    }
+
+    #[test]
+    fn encodes_into_slice_without_allocating() {
+        let mut buf = [0; 64];
+        let mut writer = &mut buf[..];
+        encode(&SerializableLogRecordRef::from(&full_record()), &mut writer).unwrap();
+        let remaining = writer.len();
+        assert_eq!(&buf[..64 - remaining], FULL);
+
+        let mut small = [0; 16];
+        assert!(encode(&SerializableLogRecordRef::from(&full_record()), &mut small[..]).is_err());
+    }
+
+    #[test]
+    fn skips_unknown_keys() {
+        // {"x": 1, 0: 3, 1: "", 2: "", 13: [1]}
+        let bytes = [0xA5, 0x61, b'x', 0x01, 0x00, 0x03, 0x01, 0x60, 0x02, 0x60, 0x0D, 0x81, 0x01];
+        assert_eq!(from_slice(&bytes).unwrap().level, RecordLevel::Info);
+    }
+
+    fn decode_error(bytes: &[u8]) -> String {
+        decode_error_of(from_slice(bytes))
+    }
+
+    fn decode_error_of(result: Result<SerializableLogRecord, CborError>) -> String {
+        match result {
+            Err(CborError::Decode(error)) => alloc::format!("{error}"),
+            other => panic!("expected a decode error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_records() {
+        assert_eq!(decode_error(&[]), "end of input bytes");
+        assert!(decode_error(&FULL[..FULL.len() - 1]).contains("end of input"));
+        // {0: 2, 1: "", 2: ""} followed by another byte.
+        assert!(matches!(
+            from_slice(&[0xA3, 0x00, 0x02, 0x01, 0x60, 0x02, 0x60, 0x00]),
+            Err(CborError::TrailingBytes(1))
+        ));
+        // Levels 0 and 6 and a level that is neither a number nor a text.
+        assert!(decode_error(&[0xA3, 0x00, 0x00, 0x01, 0x60, 0x02, 0x60]).contains("level must be 1 to 5"));
+        assert!(decode_error(&[0xA3, 0x00, 0x06, 0x01, 0x60, 0x02, 0x60]).contains("level must be 1 to 5"));
+        assert!(from_slice(&[0xA3, 0x00, 0x80, 0x01, 0x60, 0x02, 0x60]).is_err());
+        // The args (key 1) are missing.
+        assert!(decode_error(&[0xA2, 0x00, 0x02, 0x02, 0x60]).contains("missing args"));
+        // The args are a number.
+        assert!(from_slice(&[0xA3, 0x00, 0x02, 0x01, 0x01, 0x02, 0x60]).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_key_values() {
+        let with_key_values = |key_values: &[u8]| {
+            let mut bytes = alloc::vec![0xA4, 0x00, 0x02, 0x01, 0x60, 0x02, 0x60, 0x06];
+            bytes.extend_from_slice(key_values);
+            from_slice(&bytes)
+        };
+        assert_eq!(with_key_values(&[0x80]).unwrap().key_values.len(), 0);
+        // A pair with three elements.
+        assert!(decode_error_of(with_key_values(&[0x81, 0x83, 0x61, b'k', 0x01, 0x01])).contains("[key, value] pair"));
+        // A value that is a map.
+        assert!(decode_error_of(with_key_values(&[0x81, 0x82, 0x61, b'k', 0xA0])).contains("expected a key-value value"));
+        // A negative integer below `i64::MIN`.
+        let below_min = [0x81, 0x82, 0x61, b'k', 0x3B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+        assert!(decode_error_of(with_key_values(&below_min)).contains("integer out of range"));
+    }
+}
@@ -0,0 +1,107 @@
+//! Encoding and decoding of single records.
+//!
+//! A `Codec` turns one record into bytes and back. Delimiting several records in a byte stream is the job of
+//! the framing, see the `framing` module. The crate provides `Bincode2Codec` with the `bincode2` feature and
+//! `JsonCodec` with the `json` feature.
+
+use crate::{SerializableLogRecord, SerializableLogRecordRef};
+use alloc::vec::Vec;
+
+/// Encodes a record into bytes and decodes it back.
+///
+/// `encode` takes a `SerializableLogRecordRef` so that records can be encoded without first allocating an owned
+/// copy. Use `SerializableLogRecordRef::from(&record)` to encode a `SerializableLogRecord`.
+pub trait Codec {
+    type Error: core::error::Error + Send + Sync + 'static;
+
+    /// Append the encoded record to `buf`.
+    ///
+    /// # Errors
+    /// Returns an error if the record cannot be represented in this encoding.
+    fn encode(&self, record: &SerializableLogRecordRef<'_>, buf: &mut Vec<u8>) -> Result<(), Self::Error>;
+
+    /// Decode a record from exactly the given bytes.
+    ///
+    /// # Errors
+    /// Returns an error if the bytes are not a valid encoded record.
+    fn decode(&self, bytes: &[u8]) -> Result<SerializableLogRecord, Self::Error>;
+}
+
+/// Encodes records with bincode 2 using its standard configuration.
+#[cfg(feature = "bincode2")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Bincode2Codec;
+
+#[cfg(feature = "bincode2")]
+impl Codec for Bincode2Codec {
+    type Error = Bincode2Error;
+
+    fn encode(&self, record: &SerializableLogRecordRef<'_>, buf: &mut Vec<u8>) -> Result<(), Self::Error> {
+        bincode::encode_into_writer(record, VecWriter(buf), bincode::config::standard()).map_err(Bincode2Error::Encode)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<SerializableLogRecord, Self::Error> {
+        let (record, read) = bincode::decode_from_slice(bytes, bincode::config::standard()).map_err(Bincode2Error::Decode)?;
+        if read == bytes.len() {
+            Ok(record)
+        } else {
+            Err(Bincode2Error::TrailingBytes(bytes.len() - read))
+        }
+    }
+}
+
+/// Appends to a `Vec` without the `std::io::Write` implementation, which needs the `std` feature of bincode.
+#[cfg(feature = "bincode2")]
+struct VecWriter<'a>(&'a mut Vec<u8>);
+
+#[cfg(feature = "bincode2")]
+impl bincode::enc::write::Writer for VecWriter<'_> {
+    fn write(&mut self, bytes: &[u8]) -> Result<(), bincode::error::EncodeError> {
+        self.0.extend_from_slice(bytes);
+        Ok(())
+    }
+}
+
+/// The error of `Bincode2Codec`.
+#[cfg(feature = "bincode2")]
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Bincode2Error {
+    Encode(bincode::error::EncodeError),
+    Decode(bincode::error::DecodeError),
+    /// The bytes contained more than one record.
+    TrailingBytes(usize),
+}
+
+#[cfg(feature = "bincode2")]
+impl core::fmt::Display for Bincode2Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Encode(error) => write!(f, "bincode encoding failed: {error}"),
+            Self::Decode(error) => write!(f, "bincode decoding failed: {error}"),
+            Self::TrailingBytes(count) => write!(f, "{count} trailing bytes after the record"),
+        }
+    }
+}
+
+#[cfg(feature = "bincode2")]
+impl core::error::Error for Bincode2Error {}
+
+/// Encodes records as JSON objects with `serde_json`.
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonCodec;
+
+#[cfg(feature = "json")]
+impl Codec for JsonCodec {
+    type Error = serde_json::Error;
+
+    fn encode(&self, record: &SerializableLogRecordRef<'_>, buf: &mut Vec<u8>) -> Result<(), Self::Error> {
+        buf.extend_from_slice(&serde_json::to_vec(record)?);
+        Ok(())
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<SerializableLogRecord, Self::Error> {
+        serde_json::from_slice(bytes)
+    }
+}
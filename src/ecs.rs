@@ -0,0 +1,253 @@
+//! JSON documents in the Elastic Common Schema (ECS), for indexing records in Elasticsearch.
+//!
+//! A record maps to ECS fields as follows, following the layout of the ECS logging libraries: `@timestamp`,
+//! `log.level`, `message` and `ecs.version` are written with dotted keys, all other fields as nested objects.
+//!
+//! | `SerializableLogRecord` | ECS                                                        |
+//! |-------------------------|------------------------------------------------------------|
+//! | `timestamp`             | `@timestamp` with nanosecond precision                     |
+//! | `level`                 | `log.level` in lower case, `Unknown` levels as their text  |
+//! | `args`                  | `message`                                                  |
+//! | `target`                | `log.logger`                                               |
+//! | `module_path`           | `log.origin.function`                                      |
+//! | `file`                  | `log.origin.file.name`                                     |
+//! | `line`                  | `log.origin.file.line`                                     |
+//! | `thread_name`           | `process.thread.name`                                      |
+//! | `thread_id`             | `process.thread.id`                                        |
+//! | `process_id`            | `process.pid`                                              |
+//! | `key_values`            | `labels` as strings, keys of nested key-values joined with `_` |
+//!
+//! Values are written as their text, since ECS defines `labels` as keyword fields and Elasticsearch rejects
+//! documents whose label has a different type than the one it first saw for that key.
+//!
+//! Reading a document accepts every field with either dotted keys or nested objects. Fields that are not listed
+//! above are ignored, except that every entry of `labels` becomes a key-value.
+//!
+//! ```rust
+//! use log::Level;
+//! use serializable_log_record::ecs::EcsCodec;
+//! use serializable_log_record::{codec::Codec, SerializableLogRecord, SerializableLogRecordRef};
+//!
+//! let mut record = SerializableLogRecord::new(Level::Warn, "Disk full".into(), "app".into(), Some("app::disk".into()), Some("src/disk.rs".into()), Some(7))
+//!     .with_timestamp(Some(1_700_000_000_123_000_000));
+//! record.key_values.push("free", 0_u64);
+//!
+//! let codec = EcsCodec::default().with_ecs_version("8.11.0");
+//! let mut json = Vec::new();
+//! codec.encode(&SerializableLogRecordRef::from(&record), &mut json).unwrap();
+//! assert_eq!(
+//!     String::from_utf8(json).unwrap(),
+//!     concat!(
+//!         r#"{"@timestamp":"2023-11-14T22:13:20.123000000Z","ecs.version":"8.11.0","labels":{"free":"0"},"#,
+//!         r#""log":{"logger":"app","origin":{"file":{"line":7,"name":"src/disk.rs"},"function":"app::disk"}},"#,
+//!         r#""log.level":"warn","message":"Disk full"}"#
+//!     )
+//! );
+//!
+//! let document = br#"{"@timestamp":"2023-11-14T22:13:20.5Z","log":{"level":"error","logger":"db"},"log.origin.file.line":12,"message":"Timeout","process.pid":42,"labels":{"query":"SELECT 1"}}"#;
+//! let parsed = codec.decode(document).unwrap();
+//! assert_eq!(parsed.level, Level::Error.into());
+//! assert_eq!(parsed.target, "db");
+//! assert_eq!(parsed.line, Some(12));
+//! assert_eq!(parsed.process_id, Some(42));
+//! assert_eq!(parsed.timestamp, Some(1_700_000_000_500_000_000));
+//! assert_eq!(parsed.key_values.get("query"), Some(&"SELECT 1".into()));
+//! ```
+
+use crate::{
+    codec::Codec,
+    json,
+    kv::{self, KeyValues},
+    level::{LevelFallback, RecordLevel},
+    rfc3339::{self, Rfc3339},
+    SerializableLogRecord, SerializableLogRecordRef,
+};
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::convert::TryFrom;
+use log::Level;
+use serde_json::{Map, Value};
+
+/// The `ecs.version` written by default.
+pub const DEFAULT_ECS_VERSION: &str = "8.11.0";
+
+/// Renders records as ECS JSON documents and reads them back, see the module documentation.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EcsCodec {
+    ecs_version: String,
+}
+
+impl Default for EcsCodec {
+    fn default() -> Self {
+        Self {
+            ecs_version: DEFAULT_ECS_VERSION.into(),
+        }
+    }
+}
+
+impl EcsCodec {
+    /// The `ecs.version` of every document, `DEFAULT_ECS_VERSION` by default.
+    #[must_use]
+    pub fn with_ecs_version(mut self, ecs_version: impl Into<String>) -> Self {
+        self.ecs_version = ecs_version.into();
+        self
+    }
+
+    /// The ECS document of a record.
+    #[must_use]
+    pub fn to_json(&self, record: &SerializableLogRecordRef<'_>) -> Map<String, Value> {
+        let mut document = Map::new();
+        if let Some(nanos) = record.timestamp {
+            document.insert("@timestamp".into(), Rfc3339 { nanos, digits: 9 }.to_string().into());
+        }
+        let level = match &record.level {
+            RecordLevel::Unknown(text) => text.clone(),
+            level => level.as_str().to_ascii_lowercase(),
+        };
+        document.insert("log.level".into(), level.into());
+        document.insert("message".into(), record.args.as_ref().into());
+        document.insert("ecs.version".into(), self.ecs_version.as_str().into());
+
+        let mut log = Map::new();
+        log.insert("logger".into(), record.target.as_ref().into());
+        let mut origin = Map::new();
+        if let Some(module_path) = &record.module_path {
+            origin.insert("function".into(), module_path.as_ref().into());
+        }
+        let mut file = Map::new();
+        if let Some(name) = &record.file {
+            file.insert("name".into(), name.as_ref().into());
+        }
+        if let Some(line) = record.line {
+            file.insert("line".into(), line.into());
+        }
+        insert_object(&mut origin, "file", file);
+        insert_object(&mut log, "origin", origin);
+        insert_object(&mut document, "log", log);
+
+        let mut process = Map::new();
+        if let Some(process_id) = record.process_id {
+            process.insert("pid".into(), process_id.into());
+        }
+        let mut thread = Map::new();
+        if let Some(thread_id) = record.thread_id {
+            thread.insert("id".into(), thread_id.into());
+        }
+        if let Some(thread_name) = &record.thread_name {
+            thread.insert("name".into(), thread_name.as_ref().into());
+        }
+        insert_object(&mut process, "thread", thread);
+        insert_object(&mut document, "process", process);
+
+        let mut labels = Map::new();
+        insert_labels(&mut labels, "", &record.key_values);
+        insert_object(&mut document, "labels", labels);
+        document
+    }
+
+    /// Read a record from an ECS document.
+    ///
+    /// # Errors
+    /// Returns an error if `@timestamp` is not an RFC 3339 timestamp.
+    pub fn from_json(&self, document: &Map<String, Value>) -> Result<SerializableLogRecord, EcsError> {
+        let text = |path| lookup(document, path).map(|value| value.as_str().map_or_else(|| value.to_string(), String::from));
+        let number = |path| {
+            let value = lookup(document, path)?;
+            value.as_u64().or_else(|| value.as_str()?.parse().ok())
+        };
+
+        let mut record = SerializableLogRecord::new(Level::Info, String::new(), String::new(), None, None, None);
+        if let Some(level) = text("log.level") {
+            record.level = RecordLevel::parse(&level, LevelFallback::Keep).unwrap_or(record.level);
+        }
+        record.args = text("message").unwrap_or_default();
+        record.target = text("log.logger").unwrap_or_default();
+        record.module_path = text("log.origin.function");
+        record.file = text("log.origin.file.name");
+        record.line = number("log.origin.file.line").and_then(|line| u32::try_from(line).ok());
+        record.thread_name = text("process.thread.name");
+        record.thread_id = number("process.thread.id");
+        record.process_id = number("process.pid").and_then(|process_id| u32::try_from(process_id).ok());
+        record.timestamp = match text("@timestamp") {
+            Some(timestamp) => Some(rfc3339::parse(&timestamp).ok_or(EcsError::InvalidTimestamp(timestamp))?),
+            None => None,
+        };
+        if let Some(Value::Object(labels)) = lookup(document, "labels") {
+            for (key, value) in labels {
+                record.key_values.push(key.as_str(), json::value_to_kv(value.clone()));
+            }
+        }
+        Ok(record)
+    }
+}
+
+impl Codec for EcsCodec {
+    type Error = EcsError;
+
+    fn encode(&self, record: &SerializableLogRecordRef<'_>, buf: &mut Vec<u8>) -> Result<(), Self::Error> {
+        buf.extend_from_slice(&serde_json::to_vec(&self.to_json(record)).map_err(EcsError::Json)?);
+        Ok(())
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<SerializableLogRecord, Self::Error> {
+        self.from_json(&json::parse_object(bytes)?)
+    }
+}
+
+fn insert_object(parent: &mut Map<String, Value>, key: &str, object: Map<String, Value>) {
+    if !object.is_empty() {
+        parent.insert(key.into(), Value::Object(object));
+    }
+}
+
+fn insert_labels(labels: &mut Map<String, Value>, prefix: &str, key_values: &KeyValues) {
+    for (key, value) in key_values.iter() {
+        // Elasticsearch would read dots as nested objects, which `labels` does not allow.
+        let key = key.replace('.', "_");
+        let key = if prefix.is_empty() { key } else { format!("{prefix}_{key}") };
+        match value {
+            kv::Value::Nested(value) => insert_labels(labels, &key, value),
+            value => {
+                labels.insert(key, value.to_string().into());
+            }
+        }
+    }
+}
+
+/// Find a field by its dotted path, where every part of the path may be a dotted key or a nested object.
+fn lookup<'a>(object: &'a Map<String, Value>, path: &str) -> Option<&'a Value> {
+    if let Some(value) = object.get(path) {
+        return Some(value);
+    }
+    path.match_indices('.').find_map(|(i, _)| match object.get(&path[..i]) {
+        Some(Value::Object(nested)) => lookup(nested, &path[i + 1..]),
+        _ => None,
+    })
+}
+
+/// The error returned when an ECS document cannot be encoded or read.
+pub use crate::json::JsonError as EcsError;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_are_strings() {
+        let mut nested = KeyValues::new();
+        nested.push("status", 200_u64);
+        let mut record = SerializableLogRecord::new(Level::Info, "Hi".into(), "app".into(), None, None, None);
+        record.key_values.push("free", 0_u64);
+        record.key_values.push("ok", true);
+        record.key_values.push("ratio", 0.5);
+        record.key_values.push("http", kv::Value::Nested(nested));
+        let document = EcsCodec::default().to_json(&SerializableLogRecordRef::from(&record));
+        assert_eq!(
+            document["labels"],
+            serde_json::json!({"free": "0", "ok": "true", "ratio": "0.5", "http_status": "200"})
+        );
+    }
+}
@@ -0,0 +1,222 @@
+//! A record type with bounded strings for targets without a heap.
+//!
+//! `FixedLogRecord<N>` stores every string in a `heapless::String<N>`, so it can capture a `log::Record` without
+//! allocating. Strings that do not fit are truncated:
+//!
+//! * The string is cut at the last character boundary that leaves room for `TRUNCATION_MARKER`, which is appended,
+//!   so a truncated message stays recognizable after it has been converted or logged. If `N` is smaller than the
+//!   marker, the string is only cut.
+//! * The corresponding flag in `FixedLogRecord::truncated` is set.
+//!
+//! Key-value pairs and the environment fields of `SerializableLogRecord` are not captured.
+//!
+//! ```rust
+//! use log::Level;
+//! use serializable_log_record::FixedLogRecord;
+//!
+//! let record = log::Record::builder()
+//!     .args(format_args!("Hello, {}! This is long", "world"))
+//!     .level(Level::Info)
+//!     .target("app")
+//!     .build();
+//! let fixed = FixedLogRecord::<16>::from(&record);
+//! assert_eq!(fixed.args, "Hello, world!…");
+//! assert!(fixed.truncated.args);
+//! assert_eq!(fixed.target, "app");
+//! assert!(!fixed.truncated.target);
+//!
+//! fixed.replay_into(log::logger());
+//! # #[cfg(feature = "alloc")]
+//! # {
+//! use serializable_log_record::SerializableLogRecord;
+//!
+//! let owned = SerializableLogRecord::from(&fixed);
+//! assert_eq!(owned.args, "Hello, world!…");
+//! assert_eq!(FixedLogRecord::<16>::from(&owned).args, fixed.args);
+//! # }
+//! ```
+
+use crate::{replay, statics::StaticStrings};
+use core::fmt::{self, Write};
+use heapless::String;
+use log::{Level, Log, Record, RecordBuilder};
+
+/// Appended to a string that was truncated to fit into its capacity.
+pub const TRUNCATION_MARKER: &str = "…";
+
+/// A `log::Record` with its strings stored inline, each with a capacity of `N` bytes.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct FixedLogRecord<const N: usize> {
+    pub level: Level,
+    pub args: String<N>,
+    pub target: String<N>,
+    pub module_path: Option<String<N>>,
+    pub file: Option<String<N>>,
+    pub line: Option<u32>,
+    /// Whether `module_path` was a `'static` string in the original `log::Record`, see the `statics` module.
+    pub module_path_static: bool,
+    /// Whether `file` was a `'static` string in the original `log::Record`, see the `statics` module.
+    pub file_static: bool,
+    /// Which strings were truncated.
+    pub truncated: Truncated,
+}
+
+/// The strings of a `FixedLogRecord` that were truncated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Truncated {
+    pub args: bool,
+    pub target: bool,
+    pub module_path: bool,
+    pub file: bool,
+}
+
+impl Truncated {
+    /// Whether any string was truncated.
+    #[must_use]
+    pub fn any(self) -> bool {
+        self.args || self.target || self.module_path || self.file
+    }
+}
+
+impl<const N: usize> FixedLogRecord<N> {
+    /// Convert this record into a `log::Record` and pass it to the given closure.
+    pub fn with_log_record<R>(&self, f: impl FnOnce(&Record<'_>) -> R) -> R {
+        let mut builder = Record::builder();
+        f(&crate::into_log_record!(builder, self))
+    }
+
+    /// Convert this record into a `log::Record` and pass it to the `log` method of the given logger.
+    pub fn replay_into(&self, logger: &dyn Log) {
+        self.with_log_record(|record| logger.log(record));
+    }
+
+    /// Set every field of the builder except `args`, see `SerializableLogRecord::prepare_builder`.
+    /// Internal macro use only.
+    #[doc(hidden)]
+    pub fn prepare_builder<'b, 'c>(
+        &'b self,
+        builder: &'c mut RecordBuilder<'b>,
+        statics: Option<&dyn StaticStrings>,
+    ) -> &'c mut RecordBuilder<'b> {
+        let fields = replay::Fields {
+            level: self.level,
+            target: &self.target,
+            module_path: self.module_path.as_deref(),
+            module_path_static: self.module_path_static,
+            file: self.file.as_deref(),
+            file_static: self.file_static,
+            line: self.line,
+        };
+        replay::prepare_builder(builder, fields, statics)
+    }
+}
+
+impl<'a, const N: usize> From<&Record<'a>> for FixedLogRecord<N> {
+    /// Capture a `log::Record`, truncating strings that do not fit.
+    fn from(record: &Record<'a>) -> Self {
+        let mut truncated = Truncated::default();
+        let (args, args_truncated) = bounded(*record.args());
+        truncated.args = args_truncated;
+        Self {
+            level: record.level(),
+            args,
+            target: bounded_str(record.target(), &mut truncated.target),
+            module_path: record
+                .module_path()
+                .map(|value| bounded_str(value, &mut truncated.module_path)),
+            file: record.file().map(|value| bounded_str(value, &mut truncated.file)),
+            line: record.line(),
+            module_path_static: record.module_path_static().is_some(),
+            file_static: record.file_static().is_some(),
+            truncated,
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<const N: usize> From<&crate::SerializableLogRecord> for FixedLogRecord<N> {
+    /// Convert with the same truncation policy. `Unknown` levels become `DEFAULT_REPLAY_LEVEL`, like when replaying
+    /// the record. Key-value pairs and the environment fields are dropped.
+    fn from(record: &crate::SerializableLogRecord) -> Self {
+        let mut truncated = Truncated::default();
+        Self {
+            level: record.level.to_level().unwrap_or(crate::level::DEFAULT_REPLAY_LEVEL),
+            args: bounded_str(&record.args, &mut truncated.args),
+            target: bounded_str(&record.target, &mut truncated.target),
+            module_path: record
+                .module_path
+                .as_deref()
+                .map(|value| bounded_str(value, &mut truncated.module_path)),
+            file: record.file.as_deref().map(|value| bounded_str(value, &mut truncated.file)),
+            line: record.line,
+            module_path_static: record.module_path_static,
+            file_static: record.file_static,
+            truncated,
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<const N: usize> From<&FixedLogRecord<N>> for crate::SerializableLogRecord {
+    /// Convert into an owned record. Truncated strings keep their `TRUNCATION_MARKER`, the `truncated` flags are dropped.
+    fn from(record: &FixedLogRecord<N>) -> Self {
+        let mut converted = Self::new(
+            record.level,
+            record.args.as_str().into(),
+            record.target.as_str().into(),
+            record.module_path.as_deref().map(Into::into),
+            record.file.as_deref().map(Into::into),
+            record.line,
+        );
+        converted.module_path_static = record.module_path_static;
+        converted.file_static = record.file_static;
+        converted
+    }
+}
+
+fn bounded_str<const N: usize>(value: &str, truncated: &mut bool) -> String<N> {
+    let (value, was_truncated) = bounded(format_args!("{value}"));
+    *truncated = was_truncated;
+    value
+}
+
+/// Format `args` into a string of capacity `N`, applying the truncation policy. Returns whether it was truncated.
+fn bounded<const N: usize>(args: fmt::Arguments<'_>) -> (String<N>, bool) {
+    let mut writer = Truncating {
+        buf: String::new(),
+        truncated: false,
+    };
+    // The writer fails once it is full, which stops the formatting early.
+    let _ = writer.write_fmt(args);
+    if writer.truncated && N >= TRUNCATION_MARKER.len() {
+        while writer.buf.len() + TRUNCATION_MARKER.len() > N {
+            writer.buf.pop();
+        }
+        let _ = writer.buf.push_str(TRUNCATION_MARKER);
+    }
+    (writer.buf, writer.truncated)
+}
+
+struct Truncating<const N: usize> {
+    buf: String<N>,
+    truncated: bool,
+}
+
+impl<const N: usize> Write for Truncating<N> {
+    fn write_str(&mut self, value: &str) -> fmt::Result {
+        let remaining = N - self.buf.len();
+        if value.len() <= remaining {
+            let _ = self.buf.push_str(value);
+            return Ok(());
+        }
+        let mut end = remaining;
+        while !value.is_char_boundary(end) {
+            end -= 1;
+        }
+        let _ = self.buf.push_str(&value[..end]);
+        self.truncated = true;
+        Err(fmt::Error)
+    }
+}
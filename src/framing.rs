@@ -0,0 +1,320 @@
+//! Delimiting encoded records in a byte stream.
+//!
+//! A `Codec` encodes a single record, a `Framing` makes it possible to find the record boundaries again in a stream
+//! of several records. `FrameDecoder` is an incremental decoder: bytes can be pushed in chunks of any size, e.g. as
+//! they arrive from a socket or a UART, and complete frames are taken out as soon as they are available. Every
+//! decoder enforces a maximum frame size so that a corrupted length header cannot make it buffer unbounded amounts
+//! of data.
+//!
+//! ```rust
+//! use serializable_log_record::framing::{FrameDecoder, Framing};
+//!
+//! let mut stream = Vec::new();
+//! Framing::Cobs.encode(b"a\0b", &mut stream).unwrap();
+//! Framing::Cobs.encode(b"", &mut stream).unwrap();
+//! assert_eq!(stream, [0x02, b'a', 0x02, b'b', 0x00, 0x01, 0x00]);
+//!
+//! let mut decoder = FrameDecoder::new(Framing::Cobs);
+//! decoder.push(&stream[..3]);
+//! assert_eq!(decoder.next_frame(), Ok(None));
+//! decoder.push(&stream[3..]);
+//! assert_eq!(decoder.next_frame(), Ok(Some(b"a\0b".to_vec())));
+//! assert_eq!(decoder.next_frame(), Ok(Some(Vec::new())));
+//! assert_eq!(decoder.next_frame(), Ok(None));
+//! assert!(decoder.is_empty());
+//! ```
+//!
+//! ```rust
+//! use serializable_log_record::framing::{FrameDecoder, FrameError, Framing};
+//!
+//! let mut stream = Vec::new();
+//! Framing::VarintLengthPrefix.encode(&[7; 300], &mut stream).unwrap();
+//! assert_eq!(stream[..2], [0xAC, 0x02]);
+//!
+//! let mut decoder = FrameDecoder::new(Framing::VarintLengthPrefix).with_max_frame_size(100);
+//! decoder.push(&stream);
+//! assert_eq!(decoder.next_frame(), Err(FrameError::TooLarge(300)));
+//! ```
+
+use alloc::vec::Vec;
+use core::{convert::TryFrom, fmt};
+
+/// The default maximum size of a single frame, 16 MiB.
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// How records are delimited in the byte stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum Framing {
+    /// Each frame is preceded by its length as an unsigned LEB128 varint.
+    VarintLengthPrefix,
+    /// Each frame is preceded by its length as a little endian `u32`.
+    #[default]
+    U32LengthPrefix,
+    /// Each frame is followed by a newline. Only suitable for text codecs that never emit a newline,
+    /// e.g. `JsonCodec` to produce newline-delimited JSON.
+    Newline,
+    /// Each frame is encoded with Consistent Overhead Byte Stuffing and followed by a zero byte.
+    /// Decoding can resynchronize at the next zero byte after a corrupted frame.
+    Cobs,
+}
+
+/// The error returned when a frame cannot be encoded or decoded.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum FrameError {
+    /// The frame (of at least the given size) is larger than the maximum frame size.
+    TooLarge(usize),
+    /// The length prefix is not a valid varint.
+    InvalidVarint,
+    /// The frame is not valid COBS.
+    InvalidCobs,
+    /// The payload contains a newline and cannot be framed with `Framing::Newline`.
+    NewlineInPayload,
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooLarge(len) => write!(f, "frame of {len} bytes exceeds the maximum frame size"),
+            Self::InvalidVarint => f.write_str("invalid varint length prefix"),
+            Self::InvalidCobs => f.write_str("invalid COBS frame"),
+            Self::NewlineInPayload => f.write_str("payload contains a newline"),
+        }
+    }
+}
+
+impl core::error::Error for FrameError {}
+
+impl Framing {
+    /// Append the framed `payload` to `buf`.
+    ///
+    /// # Errors
+    /// Returns an error if the payload cannot be represented in this framing.
+    #[allow(clippy::cast_possible_truncation)] // The varint is encoded seven bits at a time.
+    pub fn encode(self, payload: &[u8], buf: &mut Vec<u8>) -> Result<(), FrameError> {
+        match self {
+            Self::VarintLengthPrefix => {
+                let mut len = payload.len() as u64;
+                while len >= 0x80 {
+                    buf.push((len as u8) | 0x80);
+                    len >>= 7;
+                }
+                buf.push(len as u8);
+                buf.extend_from_slice(payload);
+            }
+            Self::U32LengthPrefix => {
+                let len = u32::try_from(payload.len()).map_err(|_| FrameError::TooLarge(payload.len()))?;
+                buf.extend_from_slice(&len.to_le_bytes());
+                buf.extend_from_slice(payload);
+            }
+            Self::Newline => {
+                if payload.contains(&b'\n') {
+                    return Err(FrameError::NewlineInPayload);
+                }
+                buf.extend_from_slice(payload);
+                buf.push(b'\n');
+            }
+            Self::Cobs => cobs_encode(payload, buf),
+        }
+        Ok(())
+    }
+}
+
+/// An incremental decoder for a `Framing`.
+///
+/// After a `FrameError` of a length prefixed framing, the position in the stream is lost and the decoder should be
+/// discarded. The delimited framings skip to the next delimiter instead, so decoding can continue.
+#[derive(Debug, Clone)]
+pub struct FrameDecoder {
+    framing: Framing,
+    max_frame_size: usize,
+    buf: Vec<u8>,
+    /// Bytes at the start of `buf` that are known to contain no delimiter.
+    scanned: usize,
+    /// Discard bytes up to and including the next delimiter.
+    skipping: bool,
+}
+
+impl FrameDecoder {
+    /// Create a decoder with `DEFAULT_MAX_FRAME_SIZE`.
+    #[must_use]
+    pub fn new(framing: Framing) -> Self {
+        Self {
+            framing,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            buf: Vec::new(),
+            scanned: 0,
+            skipping: false,
+        }
+    }
+
+    /// The maximum size of a decoded frame.
+    #[must_use]
+    pub fn with_max_frame_size(mut self, max_frame_size: usize) -> Self {
+        self.max_frame_size = max_frame_size;
+        self
+    }
+
+    /// Buffer more bytes of the stream.
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Whether there are no buffered bytes left. If the stream ends while this is `false`, it ended inside a frame.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Take the next complete frame out of the buffer, or `None` if more bytes are needed.
+    ///
+    /// # Errors
+    /// Returns an error if the buffered bytes are not a valid frame.
+    pub fn next_frame(&mut self) -> Result<Option<Vec<u8>>, FrameError> {
+        match self.framing {
+            Framing::VarintLengthPrefix => {
+                let mut len: u64 = 0;
+                for (i, byte) in self.buf.iter().enumerate() {
+                    if i == 10 || (i == 9 && *byte > 1) {
+                        return Err(FrameError::InvalidVarint);
+                    }
+                    len |= u64::from(byte & 0x7F) << (7 * i);
+                    if byte & 0x80 == 0 {
+                        let len = usize::try_from(len).map_err(|_| FrameError::TooLarge(usize::MAX))?;
+                        return self.take_prefixed(i + 1, len);
+                    }
+                }
+                Ok(None)
+            }
+            Framing::U32LengthPrefix => match self.buf.get(..4) {
+                Some(&[a, b, c, d]) => {
+                    let len = usize::try_from(u32::from_le_bytes([a, b, c, d])).map_err(|_| FrameError::TooLarge(usize::MAX))?;
+                    self.take_prefixed(4, len)
+                }
+                _ => Ok(None),
+            },
+            Framing::Newline => {
+                let max_encoded = self.max_frame_size;
+                self.take_delimited(b'\n', max_encoded)
+            }
+            Framing::Cobs => loop {
+                // COBS adds one byte per 254 payload bytes plus the leading code byte.
+                let max_encoded = self.max_frame_size.saturating_add(self.max_frame_size / 254 + 1);
+                let Some(encoded) = self.take_delimited(0, max_encoded)? else {
+                    return Ok(None);
+                };
+                // Empty frames between two delimiters carry no data and are used to resynchronize.
+                if encoded.is_empty() {
+                    continue;
+                }
+                let decoded = cobs_decode(&encoded).ok_or(FrameError::InvalidCobs)?;
+                if decoded.len() > self.max_frame_size {
+                    return Err(FrameError::TooLarge(decoded.len()));
+                }
+                return Ok(Some(decoded));
+            },
+        }
+    }
+
+    /// Take the last frame at the end of the stream if it is missing its delimiter. Only `Framing::Newline` has
+    /// such frames, since newline-delimited JSON often ends without a final newline. Returns `None` for the other
+    /// framings, which leave a truncated frame in the buffer.
+    pub fn finish(&mut self) -> Option<Vec<u8>> {
+        if self.framing != Framing::Newline || self.buf.is_empty() {
+            return None;
+        }
+        let skipping = core::mem::take(&mut self.skipping);
+        self.scanned = 0;
+        let frame = core::mem::take(&mut self.buf);
+        (!skipping).then_some(frame)
+    }
+
+    fn take_prefixed(&mut self, header: usize, len: usize) -> Result<Option<Vec<u8>>, FrameError> {
+        if len > self.max_frame_size {
+            return Err(FrameError::TooLarge(len));
+        }
+        if self.buf.len() - header < len {
+            return Ok(None);
+        }
+        let frame = self.buf[header..header + len].to_vec();
+        self.buf.drain(..header + len);
+        Ok(Some(frame))
+    }
+
+    /// Remove the next delimited frame from the buffer and return it without its delimiter.
+    fn take_delimited(&mut self, delimiter: u8, max_encoded: usize) -> Result<Option<Vec<u8>>, FrameError> {
+        loop {
+            let Some(pos) = self.buf[self.scanned..].iter().position(|byte| *byte == delimiter) else {
+                self.scanned = self.buf.len();
+                if self.skipping {
+                    self.buf.clear();
+                    self.scanned = 0;
+                } else if self.buf.len() > max_encoded {
+                    self.skipping = true;
+                    return Err(FrameError::TooLarge(self.buf.len()));
+                }
+                return Ok(None);
+            };
+            let pos = pos + self.scanned;
+            self.scanned = 0;
+            let mut frame: Vec<u8> = self.buf.drain(..=pos).collect();
+            frame.pop();
+            if self.skipping {
+                self.skipping = false;
+                continue;
+            }
+            if frame.len() > max_encoded {
+                return Err(FrameError::TooLarge(frame.len()));
+            }
+            return Ok(Some(frame));
+        }
+    }
+}
+
+fn cobs_encode(payload: &[u8], buf: &mut Vec<u8>) {
+    let mut code_index = buf.len();
+    buf.push(0);
+    let mut code: u8 = 1;
+    for byte in payload {
+        if *byte == 0 {
+            buf[code_index] = code;
+            code_index = buf.len();
+            buf.push(0);
+            code = 1;
+        } else {
+            buf.push(*byte);
+            code += 1;
+            if code == 0xFF {
+                buf[code_index] = code;
+                code_index = buf.len();
+                buf.push(0);
+                code = 1;
+            }
+        }
+    }
+    buf[code_index] = code;
+    buf.push(0);
+}
+
+fn cobs_decode(encoded: &[u8]) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        let code = usize::from(encoded[i]);
+        let end = i + code;
+        if code == 0 || end > encoded.len() {
+            return None;
+        }
+        let block = &encoded[i + 1..end];
+        if block.contains(&0) {
+            return None;
+        }
+        decoded.extend_from_slice(block);
+        i = end;
+        if code < 0xFF && i < encoded.len() {
+            decoded.push(0);
+        }
+    }
+    Some(decoded)
+}
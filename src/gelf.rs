@@ -0,0 +1,886 @@
+//! GELF 1.1, the Graylog Extended Log Format, with compression and chunking for UDP.
+//!
+//! A record maps to a GELF message as follows:
+//!
+//! | `SerializableLogRecord`   | GELF                                                                  |
+//! |---------------------------|-----------------------------------------------------------------------|
+//! | `level`                   | `level` as the syslog severity, see `syslog::severity`. `Trace` and `Unknown` levels are also written to `_level` |
+//! | `args`                    | `short_message`                                                       |
+//! | `timestamp`               | `timestamp` in seconds with microsecond precision                    |
+//! | `target`, `module_path`, `file`, `line`, `thread_name`, `thread_id`, `process_id` | `_target`, `_module_path`, `_file`, `_line`, `_thread_name`, `_thread_id`, `_process_id` |
+//! | `key_values`              | `_<key>`, nested key-values as `_<key>_<nested key>`                  |
+//!
+//! The `host` is configured on the `GelfCodec`. Characters of keys that are not allowed in GELF field names are
+//! replaced with `_`, and keys that collide with one of the fields above or with the reserved `_id` get a `kv_`
+//! prefix, as do keys that are already `kv_` followed by such a key. Boolean values and non-finite numbers are
+//! written as strings, since GELF only allows strings and numbers.
+//!
+//! Decoding accepts any GELF 1.1 message. Additional fields that are not one of the fields above become key-values,
+//! with the `kv_` prefix of colliding keys removed, and a `full_message` is kept as the key-value `full_message`.
+//! The `host` is dropped. Compressed payloads are only decompressed up to `DEFAULT_MAX_DECOMPRESSED_SIZE` bytes, see
+//! `GelfCodec::with_max_decompressed_size`.
+//!
+//! ```rust
+//! use log::Level;
+//! use serializable_log_record::gelf::GelfCodec;
+//! use serializable_log_record::{codec::Codec, SerializableLogRecord, SerializableLogRecordRef};
+//!
+//! let mut record = SerializableLogRecord::new(Level::Warn, "Disk full".into(), "app".into(), None, Some("src/main.rs".into()), Some(7))
+//!     .with_timestamp(Some(1_700_000_000_123_456_789));
+//! record.key_values.push("free", 0_u64);
+//!
+//! let codec = GelfCodec::new("web-1");
+//! let mut payload = Vec::new();
+//! codec.encode(&SerializableLogRecordRef::from(&record), &mut payload).unwrap();
+//! assert_eq!(
+//!     String::from_utf8(payload).unwrap(),
+//!     r#"{"_file":"src/main.rs","_free":0,"_line":7,"_target":"app","host":"web-1","level":4,"short_message":"Disk full","timestamp":1700000000.123456,"version":"1.1"}"#
+//! );
+//!
+//! let decoded = codec
+//!     .decode(br#"{"version":"1.1","host":"db-2","short_message":"Slow query","level":6,"timestamp":1700000000.5,"_target":"db","_query_ms":1250}"#)
+//!     .unwrap();
+//! assert_eq!(decoded.level, Level::Info.into());
+//! assert_eq!(decoded.args, "Slow query");
+//! assert_eq!(decoded.target, "db");
+//! assert_eq!(decoded.timestamp, Some(1_700_000_000_500_000_000));
+//! assert_eq!(decoded.key_values.get("query_ms"), Some(&1250_u64.into()));
+//! ```
+//!
+//! Over UDP, messages are usually compressed and split into chunks if they do not fit into one datagram.
+//! `GelfUdpSender` does both, `chunk` and `Dechunker` are the building blocks:
+//!
+//! ```rust
+//! use serializable_log_record::gelf::{self, Compression, Dechunker, GelfCodec};
+//!
+//! let chunks = gelf::chunk(&[7; 100], 0x0102_0304_0506_0708, 64).unwrap();
+//! assert_eq!(chunks.len(), 2);
+//! assert_eq!(chunks[0][..12], [0x1e, 0x0f, 1, 2, 3, 4, 5, 6, 7, 8, 0, 2]);
+//! assert_eq!(chunks[1][..12], [0x1e, 0x0f, 1, 2, 3, 4, 5, 6, 7, 8, 1, 2]);
+//!
+//! let mut dechunker = Dechunker::default();
+//! assert_eq!(dechunker.push(&chunks[1]).unwrap(), None);
+//! assert_eq!(dechunker.push(&chunks[0]).unwrap(), Some(vec![7; 100]));
+//!
+//! // Compressed payloads are detected when decoding.
+//! let codec = GelfCodec::new("web-1").with_compression(Compression::Gzip);
+//! let record = log::Record::builder().args(format_args!("Hi")).build();
+//! let mut payload = Vec::new();
+//! serializable_log_record::codec::Codec::encode(&codec, &(&record).into(), &mut payload).unwrap();
+//! assert_eq!(payload[..2], [0x1f, 0x8b]);
+//! assert_eq!(GelfCodec::new("other").decode_payload(&payload).unwrap().args, "Hi");
+//! ```
+
+use crate::{
+    codec::Codec,
+    json,
+    kv::{self, KeyValues},
+    level::{LevelFallback, RecordLevel},
+    syslog, SerializableLogRecord, SerializableLogRecordRef,
+};
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{
+    convert::{TryFrom, TryInto},
+    fmt,
+};
+use flate2::{
+    read::{GzDecoder, ZlibDecoder},
+    write::{GzEncoder, ZlibEncoder},
+};
+use log::Level;
+use serde_json::{Map, Value};
+use std::{
+    io::{self, Read, Write},
+    net::{ToSocketAddrs, UdpSocket},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant, SystemTime},
+};
+
+/// The magic bytes at the start of every chunk.
+pub const CHUNK_MAGIC: [u8; 2] = [0x1e, 0x0f];
+/// The most chunks a message may be split into.
+pub const MAX_CHUNKS: usize = 128;
+/// The size of a chunk header: the magic bytes, the message id, the sequence number and the sequence count.
+pub const CHUNK_HEADER_LEN: usize = 12;
+/// The datagram size that Graylog recommends for networks with an unknown MTU.
+pub const DEFAULT_CHUNK_SIZE: usize = 1420;
+/// How long a `Dechunker` waits for the missing chunks of a message, as required by the GELF specification.
+pub const DEFAULT_CHUNK_TIMEOUT: Duration = Duration::from_secs(5);
+/// The most incomplete messages a `Dechunker` keeps by default.
+pub const DEFAULT_MAX_PENDING: usize = 1024;
+/// The most bytes of incomplete messages a `Dechunker` keeps by default.
+pub const DEFAULT_MAX_PENDING_BYTES: usize = 32 * 1024 * 1024;
+/// The largest decompressed payload a `GelfCodec` decodes by default.
+pub const DEFAULT_MAX_DECOMPRESSED_SIZE: usize = 8 * 1024 * 1024;
+
+/// The fields written from the record itself, without the leading `_`.
+const RECORD_FIELDS: [&str; 9] = [
+    "id",
+    "target",
+    "module_path",
+    "file",
+    "line",
+    "thread_name",
+    "thread_id",
+    "process_id",
+    "level",
+];
+
+/// The compression of a GELF payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Compression {
+    #[default]
+    None,
+    Zlib,
+    Gzip,
+}
+
+/// Encodes records as GELF 1.1 payloads and decodes them back, see the module documentation.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GelfCodec {
+    host: String,
+    compression: Compression,
+    max_decompressed_size: usize,
+}
+
+impl GelfCodec {
+    /// A codec that writes the given `host` into every message, without compression.
+    pub fn new(host: impl Into<String>) -> Self {
+        Self {
+            host: host.into(),
+            compression: Compression::None,
+            max_decompressed_size: DEFAULT_MAX_DECOMPRESSED_SIZE,
+        }
+    }
+
+    #[must_use]
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// The largest payload to decompress, `DEFAULT_MAX_DECOMPRESSED_SIZE` by default. Decoding stops with
+    /// `GelfError::TooLarge` once a compressed payload expands beyond it.
+    #[must_use]
+    pub fn with_max_decompressed_size(mut self, max_decompressed_size: usize) -> Self {
+        self.max_decompressed_size = max_decompressed_size;
+        self
+    }
+
+    /// The GELF message of a record as a JSON object.
+    #[must_use]
+    pub fn to_json(&self, record: &SerializableLogRecordRef<'_>) -> Map<String, Value> {
+        let mut message = Map::new();
+        message.insert("version".into(), "1.1".into());
+        message.insert("host".into(), self.host.as_str().into());
+        message.insert("short_message".into(), record.args.as_ref().into());
+        message.insert("level".into(), syslog::severity(&record.level).into());
+        if let Some(timestamp) = record.timestamp {
+            // An f64 holds about 16 significant digits, which is microseconds for current timestamps.
+            #[allow(clippy::cast_precision_loss)]
+            let seconds = (timestamp / 1000) as f64 / 1e6;
+            message.insert("timestamp".into(), seconds.into());
+        }
+
+        message.insert("_target".into(), record.target.as_ref().into());
+        let mut insert = |name: &str, value: Option<Value>| {
+            if let Some(value) = value {
+                message.insert(format!("_{name}"), value);
+            }
+        };
+        insert("module_path", record.module_path.as_deref().map(Into::into));
+        insert("file", record.file.as_deref().map(Into::into));
+        insert("line", record.line.map(Into::into));
+        insert("thread_name", record.thread_name.as_deref().map(Into::into));
+        insert("thread_id", record.thread_id.map(Into::into));
+        insert("process_id", record.process_id.map(Into::into));
+        if matches!(record.level, RecordLevel::Trace | RecordLevel::Unknown(_)) {
+            insert("level", Some(record.level.as_str().into()));
+        }
+        insert_key_values(&mut message, "", &record.key_values);
+        message
+    }
+
+    /// Parse a GELF message from a JSON object.
+    ///
+    /// # Errors
+    /// Returns an error if `short_message` is missing.
+    pub fn from_json(&self, mut message: Map<String, Value>) -> Result<SerializableLogRecord, GelfError> {
+        let Some(Value::String(args)) = message.remove("short_message") else {
+            return Err(GelfError::MissingShortMessage);
+        };
+        // GELF defaults to 1 (alert).
+        let severity = message.get("level").and_then(Value::as_u64).unwrap_or(1);
+        let mut level = syslog::level_from_severity(u8::try_from(severity).unwrap_or(u8::MAX));
+        let mut record = SerializableLogRecord::new(Level::Info, args, String::new(), None, None, None);
+        record.timestamp = message
+            .get("timestamp")
+            .and_then(Value::as_f64)
+            .and_then(timestamp_from_seconds);
+        if let Some(full_message) = message.remove("full_message") {
+            record.key_values.push("full_message", json::value_to_kv(full_message));
+        }
+
+        for (name, value) in message {
+            let Some(name) = name.strip_prefix('_') else {
+                continue;
+            };
+            let text = || value.as_str().map_or_else(|| value.to_string(), String::from);
+            let number = || value.as_u64().or_else(|| value.as_str()?.parse().ok());
+            match name {
+                "target" => record.target = text(),
+                "module_path" => record.module_path = Some(text()),
+                "file" => record.file = Some(text()),
+                "line" => record.line = number().and_then(|line| u32::try_from(line).ok()),
+                "thread_name" => record.thread_name = Some(text()),
+                "thread_id" => record.thread_id = number(),
+                "process_id" => record.process_id = number().and_then(|process_id| u32::try_from(process_id).ok()),
+                "level" => level = RecordLevel::parse(&text(), LevelFallback::Keep).unwrap_or(level),
+                _ => record
+                    .key_values
+                    .push(json::unescape_key(name, &RECORD_FIELDS), json::value_to_kv(value)),
+            }
+        }
+        record.level = level;
+        Ok(record)
+    }
+
+    /// Decode a payload, which may be compressed with either zlib or gzip regardless of the configured compression.
+    ///
+    /// # Errors
+    /// Returns an error if the payload cannot be decompressed, is larger than the configured limit once decompressed
+    /// or is not a GELF message.
+    pub fn decode_payload(&self, payload: &[u8]) -> Result<SerializableLogRecord, GelfError> {
+        let mut decompressed = Vec::new();
+        let json = match payload {
+            [0x1f, 0x8b, ..] => {
+                self.decompress(GzDecoder::new(payload), &mut decompressed)?;
+                &decompressed
+            }
+            // A zlib header declares the deflate method in the low nibble of its first byte and makes both bytes,
+            // read as a big-endian number, a multiple of 31.
+            [cmf, flg, ..] if cmf & 0x0f == 8 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0 => {
+                self.decompress(ZlibDecoder::new(payload), &mut decompressed)?;
+                &decompressed
+            }
+            _ => payload,
+        };
+        match serde_json::from_slice(json).map_err(GelfError::Json)? {
+            Value::Object(message) => self.from_json(message),
+            _ => Err(GelfError::NotAnObject),
+        }
+    }
+
+    fn decompress(&self, decoder: impl Read, decompressed: &mut Vec<u8>) -> Result<(), GelfError> {
+        // One byte more than the limit tells a payload of exactly the limit from a larger one.
+        let limit = u64::try_from(self.max_decompressed_size)
+            .unwrap_or(u64::MAX)
+            .saturating_add(1);
+        decoder.take(limit).read_to_end(decompressed).map_err(GelfError::Io)?;
+        if decompressed.len() > self.max_decompressed_size {
+            return Err(GelfError::TooLarge);
+        }
+        Ok(())
+    }
+}
+
+impl Codec for GelfCodec {
+    type Error = GelfError;
+
+    fn encode(&self, record: &SerializableLogRecordRef<'_>, buf: &mut Vec<u8>) -> Result<(), Self::Error> {
+        let json = serde_json::to_vec(&self.to_json(record)).map_err(GelfError::Json)?;
+        match self.compression {
+            Compression::None => buf.extend_from_slice(&json),
+            Compression::Zlib => {
+                let mut encoder = ZlibEncoder::new(buf, flate2::Compression::default());
+                encoder.write_all(&json).map_err(GelfError::Io)?;
+                encoder.finish().map_err(GelfError::Io)?;
+            }
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(buf, flate2::Compression::default());
+                encoder.write_all(&json).map_err(GelfError::Io)?;
+                encoder.finish().map_err(GelfError::Io)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<SerializableLogRecord, Self::Error> {
+        self.decode_payload(bytes)
+    }
+}
+
+fn insert_key_values(message: &mut Map<String, Value>, prefix: &str, key_values: &KeyValues) {
+    for (key, value) in key_values.iter() {
+        let name: String = key
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-') {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let name = if prefix.is_empty() {
+            format!("_{}", json::escape_key(&name, &RECORD_FIELDS))
+        } else {
+            format!("{prefix}_{name}")
+        };
+        let value = match value {
+            // GELF fields are strings or numbers.
+            kv::Value::Bool(value) => value.to_string().into(),
+            kv::Value::Nested(value) => {
+                insert_key_values(message, &name, value);
+                continue;
+            }
+            value => json::kv_to_value(value),
+        };
+        message.insert(name, value);
+    }
+}
+
+fn timestamp_from_seconds(seconds: f64) -> Option<u64> {
+    if !(0.0..1.8e10).contains(&seconds) {
+        return None;
+    }
+    // The range check above keeps the microseconds within u64, which holds them exactly.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let micros = (seconds * 1e6).round() as u64;
+    Some(micros * 1000)
+}
+
+/// Split a payload into GELF chunks of at most `chunk_size` bytes each, including the header. A payload that fits into
+/// `chunk_size` is returned as a single unchunked datagram.
+///
+/// # Errors
+/// Returns an error if the payload needs more than `MAX_CHUNKS` chunks or `chunk_size` has no room for a payload.
+pub fn chunk(payload: &[u8], message_id: u64, chunk_size: usize) -> Result<Vec<Vec<u8>>, GelfError> {
+    if payload.len() <= chunk_size {
+        return Ok(vec![payload.to_vec()]);
+    }
+    let data_size = chunk_size
+        .checked_sub(CHUNK_HEADER_LEN)
+        .filter(|size| *size > 0)
+        .ok_or(GelfError::TooManyChunks)?;
+    let count = payload.len().div_ceil(data_size);
+    let count = u8::try_from(count)
+        .ok()
+        .filter(|count| usize::from(*count) <= MAX_CHUNKS)
+        .ok_or(GelfError::TooManyChunks)?;
+    Ok(payload
+        .chunks(data_size)
+        .zip(0..)
+        .map(|(data, sequence)| {
+            let mut datagram = Vec::with_capacity(CHUNK_HEADER_LEN + data.len());
+            datagram.extend_from_slice(&CHUNK_MAGIC);
+            datagram.extend_from_slice(&message_id.to_be_bytes());
+            datagram.extend_from_slice(&[sequence, count]);
+            datagram.extend_from_slice(data);
+            datagram
+        })
+        .collect())
+}
+
+/// Reassembles chunked GELF messages from datagrams that may arrive in any order.
+///
+/// Messages whose chunks do not all arrive within `DEFAULT_CHUNK_TIMEOUT` are dropped. At most `DEFAULT_MAX_PENDING`
+/// incomplete messages with at most `DEFAULT_MAX_PENDING_BYTES` of chunks are kept, the oldest ones are dropped to make
+/// room for new chunks. A chunk that arrives twice is ignored.
+#[derive(Debug)]
+pub struct Dechunker {
+    pending: BTreeMap<u64, PendingMessage>,
+    pending_bytes: usize,
+    timeout: Duration,
+    max_pending: usize,
+    max_pending_bytes: usize,
+}
+
+#[derive(Debug)]
+struct PendingMessage {
+    first_seen: Instant,
+    chunks: Vec<Option<Vec<u8>>>,
+    bytes: usize,
+}
+
+impl Default for Dechunker {
+    fn default() -> Self {
+        Self {
+            pending: BTreeMap::new(),
+            pending_bytes: 0,
+            timeout: DEFAULT_CHUNK_TIMEOUT,
+            max_pending: DEFAULT_MAX_PENDING,
+            max_pending_bytes: DEFAULT_MAX_PENDING_BYTES,
+        }
+    }
+}
+
+impl Dechunker {
+    /// How long to wait for the missing chunks of a message after its first chunk arrived.
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The most incomplete messages to keep.
+    #[must_use]
+    pub fn with_max_pending(mut self, max_pending: usize) -> Self {
+        self.max_pending = max_pending;
+        self
+    }
+
+    /// The most bytes of chunks to keep for incomplete messages.
+    #[must_use]
+    pub fn with_max_pending_bytes(mut self, max_pending_bytes: usize) -> Self {
+        self.max_pending_bytes = max_pending_bytes;
+        self
+    }
+
+    /// Add a datagram. Returns the payload once all of its chunks have arrived, or right away if it is not chunked.
+    ///
+    /// # Errors
+    /// Returns an error if the datagram has an invalid chunk header, or if the chunks of its message alone exceed the
+    /// byte limit, in which case the message is dropped.
+    pub fn push(&mut self, datagram: &[u8]) -> Result<Option<Vec<u8>>, GelfError> {
+        let Some(rest) = datagram.strip_prefix(&CHUNK_MAGIC) else {
+            return Ok(Some(datagram.to_vec()));
+        };
+        let (Some(message_id), Some(&[sequence, count])) = (rest.get(..8), rest.get(8..10)) else {
+            return Err(GelfError::InvalidChunk);
+        };
+        let message_id = u64::from_be_bytes(message_id.try_into().map_err(|_| GelfError::InvalidChunk)?);
+        if count == 0 || usize::from(count) > MAX_CHUNKS || sequence >= count {
+            return Err(GelfError::InvalidChunk);
+        }
+        let data = &rest[10..];
+        let now = Instant::now();
+        self.expire(now);
+        match self.pending.get(&message_id) {
+            Some(message) if message.chunks.len() != usize::from(count) => return Err(GelfError::InvalidChunk),
+            Some(message) if message.chunks[usize::from(sequence)].is_some() => return Ok(None),
+            Some(_) => {}
+            None => while self.pending.len() >= self.max_pending.max(1) && self.drop_oldest(message_id) {},
+        }
+        while self.pending_bytes + data.len() > self.max_pending_bytes {
+            if !self.drop_oldest(message_id) {
+                self.remove(message_id);
+                return Err(GelfError::TooLarge);
+            }
+        }
+        let message = self.pending.entry(message_id).or_insert_with(|| PendingMessage {
+            first_seen: now,
+            chunks: vec![None; count.into()],
+            bytes: 0,
+        });
+        message.chunks[usize::from(sequence)] = Some(data.to_vec());
+        message.bytes += data.len();
+        self.pending_bytes += data.len();
+        if message.chunks.iter().any(Option::is_none) {
+            return Ok(None);
+        }
+        let chunks = self.remove(message_id).map(|message| message.chunks).unwrap_or_default();
+        Ok(Some(chunks.into_iter().flatten().flatten().collect()))
+    }
+
+    /// The number of messages that are still missing chunks.
+    #[must_use]
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// The number of bytes of chunks kept for the messages that are still missing chunks.
+    #[must_use]
+    pub fn pending_bytes(&self) -> usize {
+        self.pending_bytes
+    }
+
+    /// Drop all incomplete messages.
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.pending_bytes = 0;
+    }
+
+    /// Drop the incomplete messages whose timeout has passed. `push` does this as well.
+    pub fn remove_expired(&mut self) {
+        self.expire(Instant::now());
+    }
+
+    fn expire(&mut self, now: Instant) {
+        let (timeout, mut expired_bytes) = (self.timeout, 0);
+        self.pending.retain(|_, message| {
+            let keep = now.saturating_duration_since(message.first_seen) < timeout;
+            if !keep {
+                expired_bytes += message.bytes;
+            }
+            keep
+        });
+        self.pending_bytes -= expired_bytes;
+    }
+
+    /// Drop the oldest message other than `keep`. Returns false if there is none.
+    fn drop_oldest(&mut self, keep: u64) -> bool {
+        let oldest = self
+            .pending
+            .iter()
+            .filter(|(id, _)| **id != keep)
+            .min_by_key(|(_, message)| message.first_seen)
+            .map(|(id, _)| *id);
+        oldest.and_then(|oldest| self.remove(oldest)).is_some()
+    }
+
+    fn remove(&mut self, message_id: u64) -> Option<PendingMessage> {
+        let message = self.pending.remove(&message_id)?;
+        self.pending_bytes -= message.bytes;
+        Some(message)
+    }
+}
+
+/// Sends records as GELF messages over UDP, chunked if they do not fit into one datagram.
+///
+/// ```rust,no_run
+/// use serializable_log_record::gelf::{Compression, GelfCodec, GelfUdpSender};
+///
+/// let sender = GelfUdpSender::connect("graylog:12201", GelfCodec::new("web-1").with_compression(Compression::Gzip)).unwrap();
+/// let record = log::Record::builder().args(format_args!("Hello")).build();
+/// sender.send(&(&record).into()).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct GelfUdpSender {
+    socket: UdpSocket,
+    codec: GelfCodec,
+    chunk_size: usize,
+    next_message_id: AtomicU64,
+}
+
+impl GelfUdpSender {
+    /// Bind a local socket and connect it to the Graylog input at `address`.
+    ///
+    /// # Errors
+    /// Returns an error if the socket cannot be bound or connected.
+    pub fn connect(address: impl ToSocketAddrs, codec: GelfCodec) -> io::Result<Self> {
+        let address = address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no address to send GELF messages to"))?;
+        let local: std::net::SocketAddr = if address.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0; 16], 0).into()
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(address)?;
+        Ok(Self::new(socket, codec))
+    }
+
+    /// Send over an already connected socket.
+    #[must_use]
+    pub fn new(socket: UdpSocket, codec: GelfCodec) -> Self {
+        // Message ids only need to be unique among the messages in flight, so a clock-based start is enough.
+        let seed = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs() ^ u64::from(duration.subsec_nanos()) << 32);
+        Self {
+            socket,
+            codec,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            next_message_id: AtomicU64::new(seed ^ u64::from(std::process::id())),
+        }
+    }
+
+    /// The largest datagram to send, `DEFAULT_CHUNK_SIZE` by default. Use 8154 on networks with jumbo frames.
+    #[must_use]
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Encode a record and send it.
+    ///
+    /// # Errors
+    /// Returns an error if the record cannot be encoded, is too large for `MAX_CHUNKS` chunks or cannot be sent.
+    pub fn send(&self, record: &SerializableLogRecordRef<'_>) -> Result<(), GelfError> {
+        let mut payload = Vec::new();
+        self.codec.encode(record, &mut payload)?;
+        let message_id = self.next_message_id.fetch_add(1, Ordering::Relaxed);
+        for datagram in chunk(&payload, message_id, self.chunk_size)? {
+            self.socket.send(&datagram).map_err(GelfError::Io)?;
+        }
+        Ok(())
+    }
+}
+
+/// The error returned when a GELF message cannot be encoded, decoded or sent.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum GelfError {
+    Json(serde_json::Error),
+    /// Compression, decompression or sending failed.
+    Io(io::Error),
+    /// The payload is not a JSON object.
+    NotAnObject,
+    /// The message has no `short_message` string.
+    MissingShortMessage,
+    /// The payload does not fit into `MAX_CHUNKS` chunks.
+    TooManyChunks,
+    /// A datagram starts with the chunk magic bytes but has an invalid header.
+    InvalidChunk,
+    /// A decompressed payload or the chunks of a message exceed the configured limit.
+    TooLarge,
+}
+
+impl fmt::Display for GelfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Json(error) => write!(f, "GELF JSON error: {error}"),
+            Self::Io(error) => write!(f, "GELF I/O error: {error}"),
+            Self::NotAnObject => f.write_str("the GELF payload is not a JSON object"),
+            Self::MissingShortMessage => f.write_str("the GELF message has no short_message"),
+            Self::TooManyChunks => write!(f, "the GELF message does not fit into {MAX_CHUNKS} chunks"),
+            Self::InvalidChunk => f.write_str("invalid GELF chunk header"),
+            Self::TooLarge => f.write_str("the GELF message exceeds the configured size limit"),
+        }
+    }
+}
+
+impl core::error::Error for GelfError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Json(error) => Some(error),
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(payload: &str) -> Result<SerializableLogRecord, GelfError> {
+        GelfCodec::new("test").decode_payload(payload.as_bytes())
+    }
+
+    fn chunk_header(message_id: u64, sequence: u8, count: u8) -> Vec<u8> {
+        let mut datagram = CHUNK_MAGIC.to_vec();
+        datagram.extend_from_slice(&message_id.to_be_bytes());
+        datagram.extend_from_slice(&[sequence, count]);
+        datagram
+    }
+
+    #[test]
+    fn decodes_minimal_message() {
+        let record = decode(r#"{"version":"1.1","host":"h","short_message":"Hi"}"#).unwrap();
+        // GELF defaults to level 1 (alert), which is an error.
+        assert_eq!(record.level, RecordLevel::Error);
+        assert_eq!(record.args, "Hi");
+        assert_eq!(record.target, "");
+        assert_eq!(record.timestamp, None);
+        assert!(record.key_values.is_empty());
+    }
+
+    #[test]
+    fn decodes_all_fields() {
+        let record = decode(
+            r#"{"version":"1.1","host":"h","short_message":"Hi","full_message":"Hi\nthere","level":7,"_level":"TRACE",
+                "timestamp":1700000000.25,"_target":"db","_module_path":"db::pool","_file":"src/pool.rs","_line":"12",
+                "_thread_name":"main","_thread_id":3,"_process_id":42,"_flag":true,"ignored":1}"#,
+        )
+        .unwrap();
+        assert_eq!(record.level, RecordLevel::Trace);
+        assert_eq!(record.timestamp, Some(1_700_000_000_250_000_000));
+        assert_eq!(record.target, "db");
+        assert_eq!(record.module_path.as_deref(), Some("db::pool"));
+        assert_eq!(record.file.as_deref(), Some("src/pool.rs"));
+        assert_eq!(record.line, Some(12));
+        assert_eq!(record.thread_name.as_deref(), Some("main"));
+        assert_eq!(record.thread_id, Some(3));
+        assert_eq!(record.process_id, Some(42));
+        assert_eq!(record.key_values.get("full_message"), Some(&"Hi\nthere".into()));
+        assert_eq!(record.key_values.get("flag"), Some(&true.into()));
+        assert_eq!(record.key_values.len(), 2);
+    }
+
+    #[test]
+    fn round_trips_colliding_key_values() {
+        let mut record = SerializableLogRecord::new(Level::Info, "Hi".into(), "app".into(), None, None, None);
+        record.level = RecordLevel::Unknown("NOTICE".into());
+        // In the order of the JSON object, which sorts its keys.
+        record.key_values.push("id", 1_u64);
+        record.key_values.push("kv_target", "literal");
+        record.key_values.push("kv_other", 2_u64);
+        record.key_values.push("target", "spoofed");
+        let codec = GelfCodec::new("test");
+        let message = codec.to_json(&SerializableLogRecordRef::from(&record));
+        assert_eq!(message["_target"], "app");
+        assert_eq!(message["_kv_target"], "spoofed");
+        assert_eq!(message["_kv_id"], 1);
+        assert_eq!(message["_kv_kv_target"], "literal");
+        assert_eq!(message["_level"], "NOTICE");
+        assert_eq!(codec.from_json(message).unwrap(), record);
+    }
+
+    #[test]
+    fn ignores_out_of_range_timestamps() {
+        assert_eq!(decode(r#"{"short_message":"Hi","timestamp":-1}"#).unwrap().timestamp, None);
+        assert_eq!(decode(r#"{"short_message":"Hi","timestamp":1e300}"#).unwrap().timestamp, None);
+        assert_eq!(decode(r#"{"short_message":"Hi","timestamp":"1"}"#).unwrap().timestamp, None);
+    }
+
+    #[test]
+    fn rejects_invalid_payloads() {
+        assert!(matches!(
+            decode(r#"{"version":"1.1","host":"h"}"#),
+            Err(GelfError::MissingShortMessage)
+        ));
+        assert!(matches!(
+            decode(r#"{"short_message":1}"#),
+            Err(GelfError::MissingShortMessage)
+        ));
+        assert!(matches!(decode("[]"), Err(GelfError::NotAnObject)));
+        assert!(matches!(decode("{"), Err(GelfError::Json(_))));
+        assert!(matches!(decode(""), Err(GelfError::Json(_))));
+        let codec = GelfCodec::new("test");
+        assert!(matches!(
+            codec.decode_payload(&[0x1f, 0x8b, 0x08, 0x00]),
+            Err(GelfError::Io(_))
+        ));
+        assert!(matches!(codec.decode_payload(&[0x78, 0x9c, 0xff]), Err(GelfError::Io(_))));
+    }
+
+    #[test]
+    fn round_trips_compressed_payloads() {
+        let record = SerializableLogRecord::new(Level::Warn, "Hi".into(), "app".into(), None, None, Some(7));
+        for compression in [Compression::None, Compression::Zlib, Compression::Gzip] {
+            let codec = GelfCodec::new("test").with_compression(compression);
+            let mut payload = Vec::new();
+            codec.encode(&SerializableLogRecordRef::from(&record), &mut payload).unwrap();
+            assert_eq!(codec.decode(&payload).unwrap(), record);
+        }
+    }
+
+    #[test]
+    fn limits_the_decompressed_size() {
+        let record = SerializableLogRecord::new(Level::Warn, "Hi".into(), "app".into(), None, None, Some(7));
+        let json = serde_json::to_vec(&GelfCodec::new("test").to_json(&SerializableLogRecordRef::from(&record))).unwrap();
+        for compression in [Compression::Zlib, Compression::Gzip] {
+            let codec = GelfCodec::new("test").with_compression(compression);
+            let mut payload = Vec::new();
+            codec.encode(&SerializableLogRecordRef::from(&record), &mut payload).unwrap();
+            let exact = codec.clone().with_max_decompressed_size(json.len());
+            assert_eq!(exact.decode(&payload).unwrap(), record);
+            let smaller = codec.with_max_decompressed_size(json.len() - 1);
+            assert!(matches!(smaller.decode(&payload), Err(GelfError::TooLarge)));
+        }
+
+        // A megabyte of zeros compresses to about a kilobyte.
+        let mut bomb = ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+        bomb.write_all(&vec![0; 1 << 20]).unwrap();
+        let bomb = bomb.finish().unwrap();
+        let codec = GelfCodec::new("test").with_max_decompressed_size(1 << 16);
+        assert!(matches!(codec.decode(&bomb), Err(GelfError::TooLarge)));
+    }
+
+    #[test]
+    fn detects_zlib_by_its_header() {
+        // The low nibble of the first byte is 8, but the two bytes are no multiple of 31, so this is not zlib.
+        assert!(matches!(decode("\x08{}"), Err(GelfError::Json(_))));
+        assert!(matches!(
+            GelfCodec::new("test").decode_payload(&[0x78, 0x9d, 0xff]),
+            Err(GelfError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_unchunkable_payloads() {
+        assert!(matches!(chunk(&[0; 100], 1, CHUNK_HEADER_LEN), Err(GelfError::TooManyChunks)));
+        assert!(matches!(
+            chunk(&[0; 129], 1, CHUNK_HEADER_LEN + 1),
+            Err(GelfError::TooManyChunks)
+        ));
+        assert_eq!(chunk(&[0; 128], 1, CHUNK_HEADER_LEN + 1).unwrap().len(), 128);
+        assert_eq!(chunk(&[0; 10], 1, 10).unwrap(), [vec![0; 10]]);
+    }
+
+    #[test]
+    fn rejects_invalid_chunk_headers() {
+        let mut dechunker = Dechunker::default();
+        assert!(matches!(dechunker.push(&CHUNK_MAGIC), Err(GelfError::InvalidChunk)));
+        assert!(matches!(
+            dechunker.push(&chunk_header(1, 0, 1)[..11]),
+            Err(GelfError::InvalidChunk)
+        ));
+        assert!(matches!(dechunker.push(&chunk_header(1, 0, 0)), Err(GelfError::InvalidChunk)));
+        assert!(matches!(dechunker.push(&chunk_header(1, 2, 2)), Err(GelfError::InvalidChunk)));
+        assert!(matches!(
+            dechunker.push(&chunk_header(1, 0, 129)),
+            Err(GelfError::InvalidChunk)
+        ));
+        assert_eq!(dechunker.push(&chunk_header(1, 0, 2)).unwrap(), None);
+        // The same message id with a different chunk count.
+        assert!(matches!(dechunker.push(&chunk_header(1, 0, 3)), Err(GelfError::InvalidChunk)));
+        assert_eq!(dechunker.pending(), 1);
+    }
+
+    #[test]
+    fn keeps_a_bounded_number_of_pending_messages() {
+        let mut dechunker = Dechunker::default().with_max_pending(2);
+        for message_id in 1..=3 {
+            assert_eq!(dechunker.push(&chunk_header(message_id, 0, 2)).unwrap(), None);
+        }
+        assert_eq!(dechunker.pending(), 2);
+        // Message 1 was dropped to make room for message 3.
+        assert_eq!(dechunker.push(&chunk_header(1, 1, 2)).unwrap(), None);
+        let mut last = chunk_header(3, 1, 2);
+        last.push(b'x');
+        assert_eq!(dechunker.push(&last).unwrap(), Some(vec![b'x']));
+    }
+
+    #[test]
+    fn keeps_a_bounded_number_of_pending_bytes() {
+        let chunk_with = |message_id, sequence, len| {
+            let mut datagram = chunk_header(message_id, sequence, 2);
+            datagram.resize(datagram.len() + len, b'x');
+            datagram
+        };
+        let mut dechunker = Dechunker::default().with_max_pending_bytes(10);
+        assert_eq!(dechunker.push(&chunk_with(1, 0, 4)).unwrap(), None);
+        assert_eq!(dechunker.push(&chunk_with(2, 0, 4)).unwrap(), None);
+        // A duplicate chunk is ignored.
+        assert_eq!(dechunker.push(&chunk_with(2, 0, 4)).unwrap(), None);
+        assert_eq!(dechunker.pending_bytes(), 8);
+        // Message 1 is dropped to make room for message 3.
+        assert_eq!(dechunker.push(&chunk_with(3, 0, 4)).unwrap(), None);
+        assert_eq!((dechunker.pending(), dechunker.pending_bytes()), (2, 8));
+        assert_eq!(dechunker.push(&chunk_with(2, 1, 2)).unwrap(), Some(vec![b'x'; 6]));
+        assert_eq!((dechunker.pending(), dechunker.pending_bytes()), (1, 4));
+
+        // A message that does not fit on its own is dropped.
+        assert!(matches!(dechunker.push(&chunk_with(3, 1, 7)), Err(GelfError::TooLarge)));
+        assert_eq!((dechunker.pending(), dechunker.pending_bytes()), (0, 0));
+        assert!(matches!(dechunker.push(&chunk_with(4, 0, 11)), Err(GelfError::TooLarge)));
+        assert_eq!((dechunker.pending(), dechunker.pending_bytes()), (0, 0));
+    }
+
+    #[test]
+    fn drops_expired_messages() {
+        let mut dechunker = Dechunker::default().with_timeout(Duration::ZERO);
+        assert_eq!(dechunker.push(&chunk_header(1, 0, 2)).unwrap(), None);
+        // The first chunk expired, so the second one starts the message again.
+        assert_eq!(dechunker.push(&chunk_header(1, 1, 2)).unwrap(), None);
+        assert_eq!(dechunker.pending(), 1);
+        dechunker.remove_expired();
+        assert_eq!((dechunker.pending(), dechunker.pending_bytes()), (0, 0));
+
+        let mut dechunker = Dechunker::default();
+        assert_eq!(dechunker.push(&chunk_header(1, 0, 2)).unwrap(), None);
+        dechunker.remove_expired();
+        assert_eq!(dechunker.pending(), 1);
+    }
+}
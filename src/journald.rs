@@ -0,0 +1,467 @@
+//! The native protocol of the systemd journal, which stores every field of a record as a separate journal field.
+//!
+//! A record maps to journal fields as follows:
+//!
+//! | `SerializableLogRecord` | journal field                                                              |
+//! |-------------------------|----------------------------------------------------------------------------|
+//! | `args`                  | `MESSAGE`                                                                  |
+//! | `level`                 | `PRIORITY` as the syslog severity, see `syslog::severity`. `Trace` and `Unknown` levels are also written to `LEVEL` |
+//! | `file`, `line`          | `CODE_FILE`, `CODE_LINE`                                                   |
+//! | `module_path`           | `CODE_FUNC`                                                                |
+//! | `target`                | `TARGET`                                                                   |
+//! | `thread_name`, `thread_id` | `THREAD_NAME`, `TID`                                                    |
+//! | `process_id`            | `SYSLOG_PID`                                                               |
+//! | `timestamp`             | `RECORD_TIMESTAMP` in nanoseconds since the Unix epoch                     |
+//! | `key_values`            | the key in upper case, nested key-values as `<KEY>_<NESTED KEY>`           |
+//!
+//! A `SYSLOG_IDENTIFIER` can be configured on the `JournaldCodec`. Journal field names only consist of upper case
+//! letters, digits and `_`, must not start with `_` or a digit and are at most 64 bytes long, so other characters
+//! of keys are replaced with `_` and keys that are not valid otherwise or collide with one of the fields above get a
+//! `KV_` prefix, as do keys that are already `KV_` followed by such a key, e.g. `kv_level` becomes `KV_KV_LEVEL`.
+//! Names are then cut to `MAX_FIELD_NAME_LEN` bytes. Since the case is lost and characters are replaced, different
+//! keys such as `user-id`, `user.id` and `USER_ID`, or long keys with the same first 64 bytes, map to the same field
+//! name. They are not made unique: journald keeps every value of a field, and decoding returns them as key-values
+//! with the same key. Values that contain a newline use the binary-safe form with a little-endian 64-bit length
+//! prefix.
+//!
+//! Decoding reads both forms. Fields that are not one of the fields above become string key-values with lower case
+//! keys, without the `KV_` prefix of colliding keys, and the fields that journald adds itself, which start with `_`,
+//! are dropped.
+//!
+//! ```rust
+//! use log::Level;
+//! use serializable_log_record::journald::JournaldCodec;
+//! use serializable_log_record::{codec::Codec, SerializableLogRecord, SerializableLogRecordRef};
+//!
+//! let mut record = SerializableLogRecord::new(Level::Warn, "Disk full\non /var".into(), "app".into(), None, Some("src/disk.rs".into()), Some(7));
+//! record.key_values.push("free-bytes", 0_u64);
+//!
+//! let codec = JournaldCodec::default().with_syslog_identifier("app");
+//! let mut payload = Vec::new();
+//! codec.encode(&SerializableLogRecordRef::from(&record), &mut payload).unwrap();
+//! assert_eq!(
+//!     payload,
+//!     [
+//!         &b"MESSAGE\n"[..],
+//!         &17_u64.to_le_bytes(),
+//!         b"Disk full\non /var\n",
+//!         b"PRIORITY=4\nSYSLOG_IDENTIFIER=app\nTARGET=app\nCODE_FILE=src/disk.rs\nCODE_LINE=7\nFREE_BYTES=0\n",
+//!     ]
+//!     .concat()
+//! );
+//!
+//! let decoded = codec.decode(&payload).unwrap();
+//! assert_eq!(decoded.args, "Disk full\non /var");
+//! assert_eq!(decoded.level, Level::Warn.into());
+//! assert_eq!(decoded.line, Some(7));
+//! assert_eq!(decoded.key_values.get("free_bytes"), Some(&"0".into()));
+//! ```
+
+use crate::{
+    codec::Codec,
+    kv::{self, KeyValues},
+    level::{LevelFallback, RecordLevel},
+    syslog, SerializableLogRecord, SerializableLogRecordRef,
+};
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{
+    convert::{TryFrom, TryInto},
+    fmt,
+};
+use log::Level;
+#[cfg(unix)]
+use std::{io, os::unix::net::UnixDatagram, path::Path};
+
+/// The socket that journald reads native protocol datagrams from.
+pub const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+
+/// The longest field name journald accepts.
+pub const MAX_FIELD_NAME_LEN: usize = 64;
+
+/// The fields written for a record, which key-values must not overwrite.
+const RECORD_FIELDS: [&str; 12] = [
+    "MESSAGE",
+    "PRIORITY",
+    "LEVEL",
+    "SYSLOG_IDENTIFIER",
+    "TARGET",
+    "CODE_FILE",
+    "CODE_LINE",
+    "CODE_FUNC",
+    "THREAD_NAME",
+    "TID",
+    "SYSLOG_PID",
+    "RECORD_TIMESTAMP",
+];
+
+/// Encodes records as journal native protocol payloads and decodes them back, see the module documentation.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct JournaldCodec {
+    syslog_identifier: Option<String>,
+}
+
+impl JournaldCodec {
+    /// The `SYSLOG_IDENTIFIER` of every record, which `journalctl -t` filters by. None by default.
+    #[must_use]
+    pub fn with_syslog_identifier(mut self, syslog_identifier: impl Into<String>) -> Self {
+        self.syslog_identifier = Some(syslog_identifier.into());
+        self
+    }
+
+    /// Parse a native protocol payload.
+    ///
+    /// # Errors
+    /// Returns an error if the payload is not in the native protocol format or a `NAME=value` field is not valid
+    /// UTF-8. Length-prefixed values are decoded lossily.
+    pub fn parse(&self, mut payload: &[u8]) -> Result<SerializableLogRecord, JournaldError> {
+        let mut record = SerializableLogRecord::new(Level::Info, String::new(), String::new(), None, None, None);
+        let mut priority = None;
+        let mut level = None;
+        while !payload.is_empty() {
+            let name_end = payload
+                .iter()
+                .position(|byte| matches!(byte, b'=' | b'\n'))
+                .ok_or(JournaldError::InvalidPayload)?;
+            let name = core::str::from_utf8(&payload[..name_end]).map_err(|_| JournaldError::InvalidPayload)?;
+            let rest = &payload[name_end + 1..];
+            let value = if payload[name_end] == b'=' {
+                let value_end = rest.iter().position(|byte| *byte == b'\n').unwrap_or(rest.len());
+                payload = rest.get(value_end + 1..).unwrap_or_default();
+                String::from_utf8(rest[..value_end].to_vec()).map_err(|_| JournaldError::InvalidUtf8)?
+            } else {
+                let value_end = rest
+                    .get(..8)
+                    .and_then(|len| Some(u64::from_le_bytes(len.try_into().ok()?)))
+                    .and_then(|len| usize::try_from(len).ok())
+                    .and_then(|len| 8usize.checked_add(len))
+                    .ok_or(JournaldError::InvalidPayload)?;
+                let value = rest.get(8..value_end).ok_or(JournaldError::InvalidPayload)?;
+                if !matches!(rest.get(value_end), Some(b'\n') | None) {
+                    return Err(JournaldError::InvalidPayload);
+                }
+                payload = rest.get(value_end + 1..).unwrap_or_default();
+                // The binary-safe form may carry arbitrary bytes, so they are kept as far as they are text.
+                String::from_utf8_lossy(value).into_owned()
+            };
+
+            match name {
+                "MESSAGE" => record.args = value,
+                "PRIORITY" => priority = value.parse().ok(),
+                "LEVEL" => level = RecordLevel::parse(&value, LevelFallback::Keep).ok(),
+                "TARGET" => record.target = value,
+                "CODE_FILE" => record.file = Some(value),
+                "CODE_LINE" => record.line = value.parse().ok(),
+                "CODE_FUNC" => record.module_path = Some(value),
+                "THREAD_NAME" => record.thread_name = Some(value),
+                "TID" => record.thread_id = value.parse().ok(),
+                "SYSLOG_PID" => record.process_id = value.parse().ok(),
+                "RECORD_TIMESTAMP" => record.timestamp = value.parse().ok(),
+                "SYSLOG_IDENTIFIER" | "" => {}
+                name if name.starts_with('_') => {}
+                name => {
+                    let name = name.strip_prefix("KV_").filter(|name| needs_prefix(name)).unwrap_or(name);
+                    record.key_values.push(name.to_ascii_lowercase(), value);
+                }
+            }
+        }
+        record.level = match (level, priority) {
+            (Some(level), _) => level,
+            (None, Some(priority)) => syslog::level_from_severity(priority),
+            (None, None) => record.level,
+        };
+        Ok(record)
+    }
+}
+
+impl Codec for JournaldCodec {
+    type Error = JournaldError;
+
+    fn encode(&self, record: &SerializableLogRecordRef<'_>, buf: &mut Vec<u8>) -> Result<(), Self::Error> {
+        let mut field = |name: &str, value: &str| {
+            buf.extend_from_slice(name.as_bytes());
+            if value.contains('\n') {
+                buf.push(b'\n');
+                buf.extend_from_slice(&(value.len() as u64).to_le_bytes());
+            } else {
+                buf.push(b'=');
+            }
+            buf.extend_from_slice(value.as_bytes());
+            buf.push(b'\n');
+        };
+        field("MESSAGE", &record.args);
+        field("PRIORITY", &syslog::severity(&record.level).to_string());
+        if matches!(record.level, RecordLevel::Trace | RecordLevel::Unknown(_)) {
+            field("LEVEL", record.level.as_str());
+        }
+        if let Some(syslog_identifier) = &self.syslog_identifier {
+            field("SYSLOG_IDENTIFIER", syslog_identifier);
+        }
+        field("TARGET", &record.target);
+        if let Some(file) = &record.file {
+            field("CODE_FILE", file);
+        }
+        if let Some(line) = record.line {
+            field("CODE_LINE", &line.to_string());
+        }
+        if let Some(module_path) = &record.module_path {
+            field("CODE_FUNC", module_path);
+        }
+        if let Some(thread_name) = &record.thread_name {
+            field("THREAD_NAME", thread_name);
+        }
+        if let Some(thread_id) = record.thread_id {
+            field("TID", &thread_id.to_string());
+        }
+        if let Some(process_id) = record.process_id {
+            field("SYSLOG_PID", &process_id.to_string());
+        }
+        if let Some(timestamp) = record.timestamp {
+            field("RECORD_TIMESTAMP", &timestamp.to_string());
+        }
+        key_value_fields(&mut field, "", &record.key_values);
+        Ok(())
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<SerializableLogRecord, Self::Error> {
+        self.parse(bytes)
+    }
+}
+
+/// Whether a key-value field name needs a `KV_` prefix: if it is not a valid name or one of `RECORD_FIELDS` after
+/// removing any number of `KV_` prefixes.
+fn needs_prefix(mut name: &str) -> bool {
+    while let Some(rest) = name.strip_prefix("KV_") {
+        name = rest;
+    }
+    name.is_empty() || name.starts_with(|c: char| c == '_' || c.is_ascii_digit()) || RECORD_FIELDS.contains(&name)
+}
+
+fn key_value_fields(field: &mut impl FnMut(&str, &str), prefix: &str, key_values: &KeyValues) {
+    for (key, value) in key_values.iter() {
+        let mut name = String::from(prefix);
+        if !prefix.is_empty() {
+            name.push('_');
+        }
+        name.extend(key.chars().map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        }));
+        if needs_prefix(&name) {
+            name.insert_str(0, "KV_");
+        }
+        // The name is ASCII, so it can be cut at any byte.
+        name.truncate(MAX_FIELD_NAME_LEN);
+        match value {
+            kv::Value::Nested(nested) => key_value_fields(field, &name, nested),
+            value => field(&name, &value.to_string()),
+        }
+    }
+}
+
+/// Sends records to journald, or to any other Unix datagram socket that reads the native protocol.
+///
+/// Payloads that are too large for a datagram fail with an I/O error, since passing them in a memory file descriptor
+/// is not supported.
+///
+/// ```rust
+/// # #[cfg(unix)]
+/// # {
+/// use serializable_log_record::{codec::Codec, journald::{JournaldCodec, JournaldSender}};
+/// use std::os::unix::net::UnixDatagram;
+///
+/// // In production, `JournaldSender::connect(journald::JOURNAL_SOCKET, codec)` sends to journald itself.
+/// let path = std::env::temp_dir().join(format!("journald-doctest-{}.sock", std::process::id()));
+/// let _ = std::fs::remove_file(&path);
+/// let journal = UnixDatagram::bind(&path).unwrap();
+///
+/// let sender = JournaldSender::connect(&path, JournaldCodec::default()).unwrap();
+/// let record = log::Record::builder().args(format_args!("Hello")).target("app").build();
+/// sender.send(&(&record).into()).unwrap();
+///
+/// let mut datagram = [0; 1024];
+/// let len = journal.recv(&mut datagram).unwrap();
+/// let received = JournaldCodec::default().decode(&datagram[..len]).unwrap();
+/// assert_eq!(received.args, "Hello");
+/// assert_eq!(received.target, "app");
+/// std::fs::remove_file(&path).unwrap();
+/// # }
+/// ```
+#[cfg(unix)]
+#[derive(Debug)]
+pub struct JournaldSender {
+    socket: UnixDatagram,
+    codec: JournaldCodec,
+}
+
+#[cfg(unix)]
+impl JournaldSender {
+    /// Create an unbound socket and connect it to the socket at `path`, usually `JOURNAL_SOCKET`.
+    ///
+    /// # Errors
+    /// Returns an error if the socket cannot be created or connected.
+    pub fn connect(path: impl AsRef<Path>, codec: JournaldCodec) -> io::Result<Self> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(path)?;
+        Ok(Self::new(socket, codec))
+    }
+
+    /// Send over an already connected socket.
+    #[must_use]
+    pub fn new(socket: UnixDatagram, codec: JournaldCodec) -> Self {
+        Self { socket, codec }
+    }
+
+    /// Encode a record and send it as one datagram.
+    ///
+    /// # Errors
+    /// Returns an error if the datagram cannot be sent.
+    pub fn send(&self, record: &SerializableLogRecordRef<'_>) -> Result<(), JournaldError> {
+        let mut payload = Vec::new();
+        self.codec.encode(record, &mut payload)?;
+        self.socket.send(&payload).map_err(JournaldError::Io)?;
+        Ok(())
+    }
+}
+
+/// The error returned when a native protocol payload cannot be decoded or sent.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum JournaldError {
+    /// Sending failed.
+    #[cfg(unix)]
+    Io(io::Error),
+    /// A field is neither `NAME=value\n` nor a valid length-prefixed field.
+    InvalidPayload,
+    /// A `NAME=value` field is not valid UTF-8.
+    InvalidUtf8,
+}
+
+impl fmt::Display for JournaldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(unix)]
+            Self::Io(error) => write!(f, "journald I/O error: {error}"),
+            Self::InvalidPayload => f.write_str("invalid journald native protocol payload"),
+            Self::InvalidUtf8 => f.write_str("a journald field value is not valid UTF-8"),
+        }
+    }
+}
+
+impl core::error::Error for JournaldError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            #[cfg(unix)]
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn binary_field(name: &str, value: &[u8]) -> Vec<u8> {
+        let mut field = name.as_bytes().to_vec();
+        field.push(b'\n');
+        field.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        field.extend_from_slice(value);
+        field.push(b'\n');
+        field
+    }
+
+    #[test]
+    fn round_trip_with_newlines() {
+        let mut record = SerializableLogRecord::new(Level::Warn, "two\nlines".into(), "app".into(), None, None, Some(3));
+        record.key_values.push("user", "ada");
+        let codec = JournaldCodec::default().with_syslog_identifier("app");
+        let mut payload = Vec::new();
+        codec.encode(&SerializableLogRecordRef::from(&record), &mut payload).unwrap();
+        assert_eq!(codec.decode(&payload).unwrap(), record);
+    }
+
+    #[test]
+    fn colliding_keys_round_trip() {
+        let mut record = SerializableLogRecord::new(Level::Info, "Hi".into(), "app".into(), None, None, None);
+        for key in ["level", "kv_level", "kv_kv_message", "kv_user", "kv_", "_private", "1st"] {
+            record.key_values.push(key, "x");
+        }
+        let codec = JournaldCodec::default();
+        let mut payload = Vec::new();
+        codec.encode(&SerializableLogRecordRef::from(&record), &mut payload).unwrap();
+        let payload = String::from_utf8(payload).unwrap();
+        assert!(
+            payload.ends_with("KV_LEVEL=x\nKV_KV_LEVEL=x\nKV_KV_KV_MESSAGE=x\nKV_USER=x\nKV_KV_=x\nKV__PRIVATE=x\nKV_1ST=x\n")
+        );
+        assert_eq!(codec.decode(payload.as_bytes()).unwrap(), record);
+    }
+
+    #[test]
+    fn colliding_names_keep_every_value() {
+        let mut record = SerializableLogRecord::new(Level::Info, "Hi".into(), "app".into(), None, None, None);
+        record.key_values.push("user-id", 1_u64);
+        record.key_values.push("user.id", 2_u64);
+        let codec = JournaldCodec::default();
+        let mut payload = Vec::new();
+        codec.encode(&SerializableLogRecordRef::from(&record), &mut payload).unwrap();
+        let decoded = codec.decode(&payload).unwrap();
+        let values: Vec<_> = decoded
+            .key_values
+            .iter()
+            .map(|(key, value)| (key, value.to_string()))
+            .collect();
+        assert_eq!(values, [("user_id", "1".into()), ("user_id", "2".into())]);
+    }
+
+    #[test]
+    fn binary_values_are_decoded_lossily() {
+        let payload = binary_field("MESSAGE", b"caf\xe9\nbar");
+        let record = JournaldCodec::default().parse(&payload).unwrap();
+        assert_eq!(record.args, "caf\u{fffd}\nbar");
+
+        let payload = b"MESSAGE=caf\xe9\n";
+        assert!(matches!(
+            JournaldCodec::default().parse(payload),
+            Err(JournaldError::InvalidUtf8)
+        ));
+    }
+
+    #[test]
+    fn overflowing_length_is_rejected() {
+        let mut payload = b"MESSAGE\n".to_vec();
+        payload.extend_from_slice(&u64::MAX.to_le_bytes());
+        assert!(matches!(
+            JournaldCodec::default().parse(&payload),
+            Err(JournaldError::InvalidPayload)
+        ));
+    }
+
+    #[test]
+    fn malformed_payloads_are_rejected() {
+        let mut too_long = binary_field("MESSAGE", b"hi");
+        too_long.truncate(too_long.len() - 2);
+        let mut no_separator = binary_field("MESSAGE", b"hi");
+        *no_separator.last_mut().unwrap() = b'x';
+        no_separator.push(b'\n');
+        for payload in [
+            b"MESSAGE".to_vec(),
+            b"MESSAGE\n\x02\0\0".to_vec(),
+            too_long,
+            no_separator,
+            vec![0xff, b'=', b'\n'],
+        ] {
+            assert!(
+                matches!(JournaldCodec::default().parse(&payload), Err(JournaldError::InvalidPayload)),
+                "{:?}",
+                payload
+            );
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! The conversion between key-values and JSON that the JSON based codecs share.
+
+use crate::kv;
+#[cfg(any(feature = "bunyan", feature = "gelf"))]
+use crate::kv::KeyValues;
+use alloc::string::{String, ToString};
+#[cfg(any(feature = "bunyan", feature = "ecs"))]
+use core::fmt;
+#[cfg(any(feature = "bunyan", feature = "gelf"))]
+use serde_json::Number;
+use serde_json::{Map, Value};
+
+/// The JSON value of a key-value, with nested key-values as objects. Floats that JSON cannot hold become strings.
+#[cfg(any(feature = "bunyan", feature = "gelf"))]
+pub(crate) fn kv_to_value(value: &kv::Value) -> Value {
+    match value {
+        kv::Value::Str(value) => value.as_str().into(),
+        kv::Value::I64(value) => (*value).into(),
+        kv::Value::U64(value) => (*value).into(),
+        kv::Value::F64(value) => Number::from_f64(*value).map_or_else(|| value.to_string().into(), Value::Number),
+        kv::Value::Bool(value) => (*value).into(),
+        kv::Value::Nested(value) => Value::Object(key_values_to_object(value)),
+    }
+}
+
+#[cfg(any(feature = "bunyan", feature = "gelf"))]
+fn key_values_to_object(key_values: &KeyValues) -> Map<String, Value> {
+    key_values
+        .iter()
+        .map(|(key, value)| (key.into(), kv_to_value(value)))
+        .collect()
+}
+
+/// The key-value of a JSON value. Objects become nested key-values, arrays and null their JSON text.
+pub(crate) fn value_to_kv(value: Value) -> kv::Value {
+    match value {
+        Value::String(value) => kv::Value::Str(value),
+        Value::Number(number) => match (number.as_u64(), number.as_i64(), number.as_f64()) {
+            (Some(value), _, _) => kv::Value::U64(value),
+            (None, Some(value), _) => kv::Value::I64(value),
+            (None, None, Some(value)) => kv::Value::F64(value),
+            (None, None, None) => kv::Value::Str(number.to_string()),
+        },
+        Value::Bool(value) => kv::Value::Bool(value),
+        Value::Object(object) => kv::Value::Nested(object.into_iter().map(|(key, value)| (key, value_to_kv(value))).collect()),
+        other => kv::Value::Str(other.to_string()),
+    }
+}
+
+/// The key under which a top-level key-value is written: with a `kv_` prefix if it is one of the `fields` of the
+/// record, so that it cannot replace them. Keys that already look escaped, `kv_` prefixes followed by a field, get
+/// one more prefix so that `unescape_key` gives them back unchanged.
+#[cfg(any(feature = "bunyan", feature = "gelf"))]
+pub(crate) fn escape_key(key: &str, fields: &[&str]) -> String {
+    if is_escaped(key, fields) {
+        ["kv_", key].concat()
+    } else {
+        key.into()
+    }
+}
+
+/// The key of a top-level key-value written by `escape_key`. Other keys starting with `kv_` are kept as they are.
+#[cfg(any(feature = "bunyan", feature = "gelf"))]
+pub(crate) fn unescape_key<'k>(key: &'k str, fields: &[&str]) -> &'k str {
+    key.strip_prefix("kv_").filter(|key| is_escaped(key, fields)).unwrap_or(key)
+}
+
+/// Whether `key` is one of the `fields` after removing any number of `kv_` prefixes.
+#[cfg(any(feature = "bunyan", feature = "gelf"))]
+fn is_escaped(mut key: &str, fields: &[&str]) -> bool {
+    while let Some(rest) = key.strip_prefix("kv_") {
+        key = rest;
+    }
+    fields.contains(&key)
+}
+
+/// Parse a JSON object.
+#[cfg(any(feature = "bunyan", feature = "ecs"))]
+pub(crate) fn parse_object(bytes: &[u8]) -> Result<Map<String, Value>, JsonError> {
+    match serde_json::from_slice(bytes).map_err(JsonError::Json)? {
+        Value::Object(object) => Ok(object),
+        _ => Err(JsonError::NotAnObject),
+    }
+}
+
+/// The error returned when a JSON line cannot be encoded or parsed.
+#[cfg(any(feature = "bunyan", feature = "ecs"))]
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum JsonError {
+    Json(serde_json::Error),
+    /// The line is not a JSON object.
+    NotAnObject,
+    /// The timestamp is a string but not an RFC 3339 timestamp.
+    InvalidTimestamp(String),
+}
+
+#[cfg(any(feature = "bunyan", feature = "ecs"))]
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Json(error) => write!(f, "JSON error: {error}"),
+            Self::NotAnObject => f.write_str("the line is not a JSON object"),
+            Self::InvalidTimestamp(timestamp) => write!(f, "invalid timestamp {timestamp:?}"),
+        }
+    }
+}
+
+#[cfg(any(feature = "bunyan", feature = "ecs"))]
+impl core::error::Error for JsonError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Json(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(any(feature = "bunyan", feature = "gelf"))]
+    #[test]
+    fn values_round_trip() {
+        let mut nested = KeyValues::new();
+        nested.push("id", 3_u64);
+        let values = [
+            kv::Value::Str("text".into()),
+            kv::Value::I64(-1),
+            kv::Value::U64(u64::MAX),
+            kv::Value::F64(0.5),
+            kv::Value::Bool(true),
+            kv::Value::Nested(nested),
+        ];
+        for value in values {
+            assert_eq!(value_to_kv(kv_to_value(&value)), value);
+        }
+        assert_eq!(kv_to_value(&kv::Value::F64(f64::NAN)), "NaN");
+        assert_eq!(value_to_kv(serde_json::json!([1, null])), kv::Value::Str("[1,null]".into()));
+    }
+
+    #[cfg(any(feature = "bunyan", feature = "gelf"))]
+    #[test]
+    fn keys() {
+        let fields = ["msg", "level"];
+        assert_eq!(escape_key("msg", &fields), "kv_msg");
+        assert_eq!(escape_key("user", &fields), "user");
+        assert_eq!(unescape_key("kv_msg", &fields), "msg");
+        assert_eq!(unescape_key("kv_user", &fields), "kv_user");
+        assert_eq!(unescape_key("msg", &fields), "msg");
+        assert_eq!(escape_key("kv_msg", &fields), "kv_kv_msg");
+        assert_eq!(escape_key("kv_kv_level", &fields), "kv_kv_kv_level");
+        assert_eq!(escape_key("kv_user", &fields), "kv_user");
+        for key in ["msg", "kv_msg", "kv_kv_level", "kv_user", "kv_", "user"] {
+            assert_eq!(unescape_key(&escape_key(key, &fields), &fields), key);
+        }
+    }
+
+    #[cfg(any(feature = "bunyan", feature = "ecs"))]
+    #[test]
+    fn errors() {
+        assert!(matches!(parse_object(b"[]"), Err(JsonError::NotAnObject)));
+        assert!(matches!(parse_object(b"{"), Err(JsonError::Json(_))));
+        assert!(parse_object(b"{}").unwrap().is_empty());
+    }
+}
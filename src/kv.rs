@@ -0,0 +1,434 @@
+//! Serializable key-value pairs captured from `log::Record::key_values()`.
+//!
+//! The `key_values` field of a `SerializableLogRecord` is always present so that the wire format does not
+//! depend on the enabled features. It is only filled in when the `kv` feature is enabled, which also enables
+//! the `kv` feature of the `log` crate and re-attaches the pairs when converting back into a `log::Record`.
+//!
+//! Integers, floats, booleans and strings are captured with their type, every other value by its `Display`
+//! representation, since `log` does not expose the structure of maps and sequences without its `serde` or `sval`
+//! features. So capturing never produces `Value::Nested`, which only comes from decoding formats with nested
+//! objects such as JSON. When replaying, nested key-values are attached as their text, e.g. `{status: 200}`.
+//!
+//! ```rust
+//! # #[cfg(feature = "kv")]
+//! # {
+//! use serializable_log_record::{kv::Value, SerializableLogRecord};
+//!
+//! let pairs = [("user_id", log::kv::Value::from(42u64)), ("admin", log::kv::Value::from(false))];
+//! let record = log::Record::builder().args(format_args!("login")).key_values(&pairs).build();
+//! let serializable_record = SerializableLogRecord::from(&record);
+//! assert_eq!(serializable_record.key_values.get("user_id"), Some(&Value::U64(42)));
+//! assert_eq!(serializable_record.key_values.get("admin"), Some(&Value::Bool(false)));
+//!
+//! let user_id = serializable_record.with_log_record(|replayed| replayed.key_values().get("user_id".into()).and_then(|v| v.to_u64()));
+//! assert_eq!(user_id, Some(42));
+//! # }
+//! ```
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{
+    fmt,
+    hash::{Hash, Hasher},
+    iter::FromIterator,
+};
+
+/// A typed, serializable representation of a `log::kv::Value`.
+///
+/// Values that are not one of the primitive types are captured by their `Display` representation as `Value::Str`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "bincode2", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(::rkyv::Archive, ::rkyv::Serialize, ::rkyv::Deserialize),
+    rkyv(
+        derive(Debug),
+        serialize_bounds(__S: ::rkyv::ser::Writer + ::rkyv::ser::Allocator, __S::Error: ::rkyv::rancor::Source),
+        deserialize_bounds(__D::Error: ::rkyv::rancor::Source),
+        bytecheck(bounds(__C: ::rkyv::validation::ArchiveContext, __C::Error: ::rkyv::rancor::Source))
+    )
+)]
+#[non_exhaustive]
+pub enum Value {
+    Str(String),
+    I64(i64),
+    U64(u64),
+    F64(f64),
+    Bool(bool),
+    /// Nested key-values, as decoded from JSON objects. Never captured from a `log::Record`, see the module
+    /// documentation.
+    Nested(#[cfg_attr(feature = "rkyv", rkyv(omit_bounds))] KeyValues),
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Str(a), Self::Str(b)) => a == b,
+            (Self::I64(a), Self::I64(b)) => a == b,
+            (Self::U64(a), Self::U64(b)) => a == b,
+            // Compare bitwise so that `Eq` and `Hash` stay consistent, even for NaN.
+            (Self::F64(a), Self::F64(b)) => a.to_bits() == b.to_bits(),
+            (Self::Bool(a), Self::Bool(b)) => a == b,
+            (Self::Nested(a), Self::Nested(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Value {}
+
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        core::mem::discriminant(self).hash(state);
+        match self {
+            Self::Str(value) => value.hash(state),
+            Self::I64(value) => value.hash(state),
+            Self::U64(value) => value.hash(state),
+            Self::F64(value) => value.to_bits().hash(state),
+            Self::Bool(value) => value.hash(state),
+            Self::Nested(value) => value.hash(state),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Str(value) => f.write_str(value),
+            Self::I64(value) => value.fmt(f),
+            Self::U64(value) => value.fmt(f),
+            Self::F64(value) => value.fmt(f),
+            Self::Bool(value) => value.fmt(f),
+            Self::Nested(value) => fmt_nested(f, value.iter()),
+        }
+    }
+}
+
+/// Write nested key-values as `{key: value, ...}`, for `Value` and its archived form.
+pub(crate) fn fmt_nested<'a, V: fmt::Display + 'a>(
+    f: &mut fmt::Formatter<'_>,
+    pairs: impl Iterator<Item = (&'a str, &'a V)>,
+) -> fmt::Result {
+    f.write_str("{")?;
+    for (i, (key, value)) in pairs.enumerate() {
+        if i > 0 {
+            f.write_str(", ")?;
+        }
+        write!(f, "{key}: {value}")?;
+    }
+    f.write_str("}")
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Self::Str(value.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Self::Str(value)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Self::I64(value)
+    }
+}
+
+impl From<u64> for Value {
+    fn from(value: u64) -> Self {
+        Self::U64(value)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Self::F64(value)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<KeyValues> for Value {
+    fn from(value: KeyValues) -> Self {
+        Self::Nested(value)
+    }
+}
+
+/// An ordered list of key-value pairs as captured from a `log::Record`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
+#[cfg_attr(feature = "bincode2", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(::rkyv::Archive, ::rkyv::Serialize, ::rkyv::Deserialize),
+    rkyv(derive(Debug))
+)]
+pub struct KeyValues(Vec<(String, Value)>);
+
+impl KeyValues {
+    /// Create an empty list of key-value pairs.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    /// Append a key-value pair.
+    pub fn push(&mut self, key: impl Into<String>, value: impl Into<Value>) {
+        self.0.push((key.into(), value.into()));
+    }
+
+    /// Get the value of the first pair with the given key.
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.0.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    /// Iterate over the key-value pairs in the order they were captured.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<Vec<(String, Value)>> for KeyValues {
+    fn from(pairs: Vec<(String, Value)>) -> Self {
+        Self(pairs)
+    }
+}
+
+impl<K: Into<String>, V: Into<Value>> FromIterator<(K, V)> for KeyValues {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self(iter.into_iter().map(|(k, v)| (k.into(), v.into())).collect())
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl ArchivedKeyValues {
+    /// Get the value of the first pair with the given key.
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&ArchivedValue> {
+        self.iter().find(|(k, _)| *k == key).map(|(_, v)| v)
+    }
+
+    /// Iterate over the key-value pairs in the order they were captured.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &ArchivedValue)> {
+        self.0.iter().map(|pair| (pair.0.as_str(), &pair.1))
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Capture the key-value pairs of a `log::Record`. Without the `kv` feature there is nothing to capture.
+pub(crate) fn capture(record: &log::Record<'_>) -> KeyValues {
+    #[cfg(feature = "kv")]
+    {
+        let mut key_values = KeyValues::new();
+        // Our visitor never fails, so there is no error to handle.
+        let _ = record.key_values().visit(&mut key_values);
+        key_values
+    }
+    #[cfg(not(feature = "kv"))]
+    {
+        let _ = record;
+        KeyValues::new()
+    }
+}
+
+/// The key under which the timestamp of a record is attached when replaying it with the `kv` feature.
+pub const TIMESTAMP_KEY: &str = "timestamp";
+
+#[cfg(feature = "kv")]
+mod log_kv {
+    use super::{KeyValues, Value, TIMESTAMP_KEY};
+    use crate::SerializableLogRecord;
+    use alloc::string::{String, ToString};
+    use core::convert::TryFrom;
+    use log::kv::{self, Key, Source, ToValue, VisitSource, VisitValue};
+
+    impl<'kvs> VisitSource<'kvs> for KeyValues {
+        fn visit_pair(&mut self, key: Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+            let mut captured = None;
+            value.visit(Capture(&mut captured))?;
+            self.0.push((
+                String::from(key.as_str()),
+                captured.unwrap_or_else(|| Value::Str(value.to_string())),
+            ));
+            Ok(())
+        }
+    }
+
+    /// Visits a `log::kv::Value` and stores the closest typed representation.
+    struct Capture<'c>(&'c mut Option<Value>);
+
+    impl VisitValue<'_> for Capture<'_> {
+        fn visit_any(&mut self, value: kv::Value<'_>) -> Result<(), kv::Error> {
+            *self.0 = Some(Value::Str(value.to_string()));
+            Ok(())
+        }
+
+        fn visit_u64(&mut self, value: u64) -> Result<(), kv::Error> {
+            *self.0 = Some(Value::U64(value));
+            Ok(())
+        }
+
+        fn visit_i64(&mut self, value: i64) -> Result<(), kv::Error> {
+            *self.0 = Some(Value::I64(value));
+            Ok(())
+        }
+
+        fn visit_u128(&mut self, value: u128) -> Result<(), kv::Error> {
+            *self.0 = Some(u64::try_from(value).map_or_else(|_| Value::Str(value.to_string()), Value::U64));
+            Ok(())
+        }
+
+        fn visit_i128(&mut self, value: i128) -> Result<(), kv::Error> {
+            *self.0 = Some(i64::try_from(value).map_or_else(|_| Value::Str(value.to_string()), Value::I64));
+            Ok(())
+        }
+
+        fn visit_f64(&mut self, value: f64) -> Result<(), kv::Error> {
+            *self.0 = Some(Value::F64(value));
+            Ok(())
+        }
+
+        fn visit_bool(&mut self, value: bool) -> Result<(), kv::Error> {
+            *self.0 = Some(Value::Bool(value));
+            Ok(())
+        }
+
+        fn visit_str(&mut self, value: &str) -> Result<(), kv::Error> {
+            *self.0 = Some(Value::Str(String::from(value)));
+            Ok(())
+        }
+    }
+
+    impl ToValue for Value {
+        fn to_value(&self) -> kv::Value<'_> {
+            match self {
+                Self::Str(value) => kv::Value::from(value.as_str()),
+                Self::I64(value) => kv::Value::from(*value),
+                Self::U64(value) => kv::Value::from(*value),
+                Self::F64(value) => kv::Value::from(*value),
+                Self::Bool(value) => kv::Value::from(*value),
+                Self::Nested(_) => kv::Value::from_dyn_display(self),
+            }
+        }
+    }
+
+    impl Source for KeyValues {
+        fn visit<'kvs>(&'kvs self, visitor: &mut dyn VisitSource<'kvs>) -> Result<(), kv::Error> {
+            for (key, value) in &self.0 {
+                visitor.visit_pair(Key::from_str(key), value.to_value())?;
+            }
+            Ok(())
+        }
+
+        fn count(&self) -> usize {
+            self.0.len()
+        }
+    }
+
+    /// The key-value pairs attached when replaying a record: the captured pairs followed by the timestamp,
+    /// unless a captured pair already uses the `timestamp` key.
+    impl Source for SerializableLogRecord {
+        fn visit<'kvs>(&'kvs self, visitor: &mut dyn VisitSource<'kvs>) -> Result<(), kv::Error> {
+            self.key_values.visit(visitor)?;
+            if let Some(timestamp) = replayed_timestamp(self) {
+                visitor.visit_pair(Key::from_str(TIMESTAMP_KEY), kv::Value::from(timestamp))?;
+            }
+            Ok(())
+        }
+
+        fn count(&self) -> usize {
+            self.key_values.count() + usize::from(replayed_timestamp(self).is_some())
+        }
+    }
+
+    fn replayed_timestamp(record: &SerializableLogRecord) -> Option<u64> {
+        record.timestamp.filter(|_| record.key_values.get(TIMESTAMP_KEY).is_none())
+    }
+}
+
+#[cfg(all(test, feature = "kv"))]
+mod tests {
+    use super::*;
+    use crate::SerializableLogRecord;
+
+    #[test]
+    fn captures_typed_values() {
+        let pairs = [
+            ("str", log::kv::Value::from("text")),
+            ("i64", log::kv::Value::from(-1_i64)),
+            ("u64", log::kv::Value::from(u64::MAX)),
+            ("i128", log::kv::Value::from(i128::MIN)),
+            ("f64", log::kv::Value::from(0.5)),
+            ("bool", log::kv::Value::from(true)),
+            ("display", log::kv::Value::from_display(&'c')),
+        ];
+        let record = log::Record::builder().key_values(&pairs).build();
+        let key_values = capture(&record);
+        let expected = [
+            ("str", Value::from("text")),
+            ("i64", Value::I64(-1)),
+            ("u64", Value::U64(u64::MAX)),
+            ("i128", Value::Str(i128::MIN.to_string())),
+            ("f64", Value::F64(0.5)),
+            ("bool", Value::Bool(true)),
+            ("display", Value::from("c")),
+        ];
+        assert!(key_values.iter().eq(expected.iter().map(|(key, value)| (*key, value))));
+    }
+
+    #[test]
+    fn replays_typed_values() {
+        let mut nested = KeyValues::new();
+        nested.push("status", 200_u64);
+        let mut record = SerializableLogRecord::new(log::Level::Info, "Hi".into(), "app".into(), None, None, None);
+        record.key_values.push("str", "text");
+        record.key_values.push("i64", -1_i64);
+        record.key_values.push("u64", 2_u64);
+        record.key_values.push("f64", 0.5);
+        record.key_values.push("bool", true);
+        record.key_values.push("http", Value::Nested(nested));
+        let recaptured = record.with_log_record(|replayed| {
+            let key_values = replayed.key_values();
+            assert_eq!(key_values.get("str".into()).unwrap().to_borrowed_str(), Some("text"));
+            assert_eq!(key_values.get("i64".into()).unwrap().to_i64(), Some(-1));
+            assert_eq!(key_values.get("u64".into()).unwrap().to_u64(), Some(2));
+            assert_eq!(key_values.get("f64".into()).unwrap().to_f64(), Some(0.5));
+            assert_eq!(key_values.get("bool".into()).unwrap().to_bool(), Some(true));
+            capture(replayed)
+        });
+        // Everything but the nested key-values comes back with its type.
+        let (nested, typed) = recaptured.iter().partition::<Vec<_>, _>(|(key, _)| *key == "http");
+        assert!(typed.into_iter().eq(record.key_values.iter().take(5)));
+        assert_eq!(nested, [("http", &Value::from("{status: 200}"))]);
+    }
+}
@@ -0,0 +1,303 @@
+//! The typed level of a `SerializableLogRecord`.
+//!
+//! Parsing a level name is strict by default: unknown names are reported as a `ParseLevelError` instead of being
+//! mapped to some arbitrary level. `RecordLevel::parse` takes a `LevelFallback` to choose a different policy.
+//!
+//! ```rust
+//! use log::Level;
+//! use serializable_log_record::level::{LevelFallback, RecordLevel};
+//!
+//! assert_eq!("info".parse::<RecordLevel>(), Ok(RecordLevel::Info));
+//! assert!("Inf0".parse::<RecordLevel>().is_err());
+//!
+//! assert!(RecordLevel::parse("Inf0", LevelFallback::Error).is_err());
+//! assert_eq!(RecordLevel::parse("Inf0", LevelFallback::Default(Level::Error)), Ok(RecordLevel::Error));
+//! assert_eq!(RecordLevel::parse("Inf0", LevelFallback::Keep), Ok(RecordLevel::Unknown("Inf0".into())));
+//! ```
+//!
+//! A `log::Record` cannot carry an `Unknown` level. Replaying such a record uses `DEFAULT_REPLAY_LEVEL` unless the
+//! caller picks another `LevelFallback`:
+//!
+//! ```rust
+//! # use log::Level;
+//! # use serializable_log_record::{level::{LevelFallback, RecordLevel}, SerializableLogRecord};
+//! let mut record = SerializableLogRecord::new(Level::Info, "Hi".into(), "app".into(), None, None, None);
+//! record.level = RecordLevel::Unknown("NOTICE".into());
+//!
+//! assert_eq!(record.with_log_record(|replayed| replayed.level()), Level::Warn);
+//! assert_eq!(record.try_with_log_record(LevelFallback::Default(Level::Info), |replayed| replayed.level()), Ok(Level::Info));
+//! assert!(record.try_with_log_record(LevelFallback::Error, |replayed| replayed.level()).is_err());
+//! ```
+//!
+//! With the `serde` feature, levels serialize as their upper case name or their original text in human-readable
+//! formats, and as a variant index in compact formats. Human-readable formats parse level names case-insensitively
+//! like `FromStr`, and keep unknown names as `Unknown`, since that is how an `Unknown` level is written.
+//!
+//! ```rust
+//! # #[cfg(feature = "serde")]
+//! # {
+//! use serializable_log_record::level::RecordLevel;
+//!
+//! assert_eq!(serde_json::to_string(&RecordLevel::Warn).unwrap(), r#""WARN""#);
+//! assert_eq!(serde_json::to_string(&RecordLevel::Unknown("Inf0".into())).unwrap(), r#""Inf0""#);
+//! assert_eq!(serde_json::from_str::<RecordLevel>(r#""DEBUG""#).unwrap(), RecordLevel::Debug);
+//! assert_eq!(serde_json::from_str::<RecordLevel>(r#""debug""#).unwrap(), RecordLevel::Debug);
+//! assert_eq!(serde_json::from_str::<RecordLevel>(r#""Inf0""#).unwrap(), RecordLevel::Unknown("Inf0".into()));
+//! # }
+//! ```
+
+use alloc::string::{String, ToString};
+use core::{convert::TryFrom, fmt, str::FromStr};
+use log::Level;
+
+/// The `log::Level` of replayed records whose level is `Unknown`, unless the caller picks another `LevelFallback`.
+pub const DEFAULT_REPLAY_LEVEL: Level = Level::Warn;
+
+/// The level of a `SerializableLogRecord`.
+///
+/// `Unknown` holds a level name that could not be parsed. It is only ever produced when explicitly asked for
+/// with `LevelFallback::Keep` or when deserializing a record that was serialized with an `Unknown` level.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "bincode2", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(::rkyv::Archive, ::rkyv::Serialize, ::rkyv::Deserialize),
+    rkyv(derive(Debug))
+)]
+pub enum RecordLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+    Unknown(String),
+}
+
+/// What to do with a level name that does not name a `log::Level`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum LevelFallback {
+    /// Return a `ParseLevelError`.
+    #[default]
+    Error,
+    /// Use the given level instead.
+    Default(Level),
+    /// Keep the original text as `RecordLevel::Unknown`.
+    Keep,
+}
+
+/// The error returned when a level name does not name a `log::Level`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ParseLevelError {
+    text: String,
+}
+
+impl ParseLevelError {
+    pub(crate) fn new(text: &str) -> Self {
+        Self { text: text.to_string() }
+    }
+
+    /// The text that could not be parsed.
+    #[must_use]
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+impl fmt::Display for ParseLevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown log level {:?}", self.text)
+    }
+}
+
+impl core::error::Error for ParseLevelError {}
+
+impl RecordLevel {
+    /// Parse a level name (case-insensitively) and apply the given fallback policy if it is not a known level.
+    ///
+    /// # Errors
+    /// Returns a `ParseLevelError` if the name is unknown and the policy is `LevelFallback::Error`.
+    pub fn parse(text: &str, fallback: LevelFallback) -> Result<Self, ParseLevelError> {
+        match (Level::from_str(text), fallback) {
+            (Ok(level), _) | (Err(_), LevelFallback::Default(level)) => Ok(level.into()),
+            (Err(_), LevelFallback::Error) => Err(ParseLevelError::new(text)),
+            (Err(_), LevelFallback::Keep) => Ok(Self::Unknown(text.to_string())),
+        }
+    }
+
+    /// The `log::Level`, or `None` if the level is `Unknown`.
+    #[must_use]
+    pub fn to_level(&self) -> Option<Level> {
+        match self {
+            Self::Error => Some(Level::Error),
+            Self::Warn => Some(Level::Warn),
+            Self::Info => Some(Level::Info),
+            Self::Debug => Some(Level::Debug),
+            Self::Trace => Some(Level::Trace),
+            Self::Unknown(_) => None,
+        }
+    }
+
+    /// The `log::Level`, applying the fallback policy if the level is `Unknown`.
+    ///
+    /// # Errors
+    /// Returns a `ParseLevelError` if the level is `Unknown` and the policy is `LevelFallback::Error` or
+    /// `LevelFallback::Keep`, since a `log::Level` cannot keep the original text.
+    pub fn to_level_or(&self, fallback: LevelFallback) -> Result<Level, ParseLevelError> {
+        match (self, fallback) {
+            (Self::Unknown(_), LevelFallback::Default(level)) => Ok(level),
+            (level, _) => Level::try_from(level),
+        }
+    }
+
+    /// The upper case name of the level, or the original text if it is `Unknown`.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Error => "ERROR",
+            Self::Warn => "WARN",
+            Self::Info => "INFO",
+            Self::Debug => "DEBUG",
+            Self::Trace => "TRACE",
+            Self::Unknown(text) => text,
+        }
+    }
+}
+
+impl From<Level> for RecordLevel {
+    fn from(level: Level) -> Self {
+        match level {
+            Level::Error => Self::Error,
+            Level::Warn => Self::Warn,
+            Level::Info => Self::Info,
+            Level::Debug => Self::Debug,
+            Level::Trace => Self::Trace,
+        }
+    }
+}
+
+impl TryFrom<&RecordLevel> for Level {
+    type Error = ParseLevelError;
+
+    fn try_from(level: &RecordLevel) -> Result<Self, ParseLevelError> {
+        level.to_level().ok_or_else(|| ParseLevelError::new(level.as_str()))
+    }
+}
+
+impl TryFrom<RecordLevel> for Level {
+    type Error = ParseLevelError;
+
+    fn try_from(level: RecordLevel) -> Result<Self, ParseLevelError> {
+        Self::try_from(&level)
+    }
+}
+
+impl FromStr for RecordLevel {
+    type Err = ParseLevelError;
+
+    /// Parse a level name strictly, see `RecordLevel::parse` for other fallback policies.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Self::parse(text, LevelFallback::Error)
+    }
+}
+
+impl TryFrom<&str> for RecordLevel {
+    type Error = ParseLevelError;
+
+    fn try_from(text: &str) -> Result<Self, ParseLevelError> {
+        text.parse()
+    }
+}
+
+impl fmt::Display for RecordLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::{LevelFallback, RecordLevel};
+    use alloc::string::String;
+    use core::fmt;
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    /// The layout of compact formats, which write the variant index.
+    #[derive(Serialize)]
+    #[serde(rename = "RecordLevel")]
+    enum CompactLevelRef<'a> {
+        #[serde(rename = "ERROR")]
+        Error,
+        #[serde(rename = "WARN")]
+        Warn,
+        #[serde(rename = "INFO")]
+        Info,
+        #[serde(rename = "DEBUG")]
+        Debug,
+        #[serde(rename = "TRACE")]
+        Trace,
+        Unknown(&'a str),
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename = "RecordLevel")]
+    enum CompactLevel {
+        #[serde(rename = "ERROR")]
+        Error,
+        #[serde(rename = "WARN")]
+        Warn,
+        #[serde(rename = "INFO")]
+        Info,
+        #[serde(rename = "DEBUG")]
+        Debug,
+        #[serde(rename = "TRACE")]
+        Trace,
+        Unknown(String),
+    }
+
+    impl Serialize for RecordLevel {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            if serializer.is_human_readable() {
+                return serializer.serialize_str(self.as_str());
+            }
+            match self {
+                Self::Error => CompactLevelRef::Error,
+                Self::Warn => CompactLevelRef::Warn,
+                Self::Info => CompactLevelRef::Info,
+                Self::Debug => CompactLevelRef::Debug,
+                Self::Trace => CompactLevelRef::Trace,
+                Self::Unknown(text) => CompactLevelRef::Unknown(text),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for RecordLevel {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            if deserializer.is_human_readable() {
+                return deserializer.deserialize_str(LevelVisitor);
+            }
+            Ok(match CompactLevel::deserialize(deserializer)? {
+                CompactLevel::Error => Self::Error,
+                CompactLevel::Warn => Self::Warn,
+                CompactLevel::Info => Self::Info,
+                CompactLevel::Debug => Self::Debug,
+                CompactLevel::Trace => Self::Trace,
+                CompactLevel::Unknown(text) => Self::Unknown(text),
+            })
+        }
+    }
+
+    struct LevelVisitor;
+
+    impl de::Visitor<'_> for LevelVisitor {
+        type Value = RecordLevel;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("a level name")
+        }
+
+        fn visit_str<E: de::Error>(self, text: &str) -> Result<RecordLevel, E> {
+            RecordLevel::parse(text, LevelFallback::Keep).map_err(E::custom)
+        }
+    }
+}
@@ -9,6 +9,8 @@
 //! Convert a `log::Record` to a `SerializableLogRecord` using the `::from` method:
 //!
 //! ```rust
+//! # #[cfg(feature = "alloc")]
+//! # {
 //! # use log::{Record, Level};
 //! use serializable_log_record::SerializableLogRecord;
 //!
@@ -21,15 +23,97 @@
 //! #     .build();
 //! //let record: log::Record = ...;
 //! let serializable_record = SerializableLogRecord::from(&record);
+//! # }
 //! ```
 //! `Serde`'s `Serialize` and `Deserialize` traits are implemented for `SerializableLogRecord` if the `serde` feature is enabled.
 //! The feature `bincode2` is also available which implements `bincode::Encode` and `bincode::Decode` from bincode version 2 for `SerializableLogRecord`.
 //!
-//! To convert a `SerializableLogRecord` back into a `log::Record` use the `into_log_record` macro. The result of this macro has to be passed
-//! directly into a call to the `log` method of any `log::Log` implementation. It cannot be stored in an intermediate variable or alike due to
-//! the extremely restrictive lifetime of the `args` field of `log::Record`.
+//! The level is stored as a typed `level::RecordLevel`. Parsing level names is strict and reports unknown names as an error,
+//! see the `level` module for the available fallback policies.
+//!
+//! If the `std` feature is enabled, the wall-clock time of the conversion is captured into the `timestamp` field as nanoseconds
+//! since the Unix epoch. Without it, use `SerializableLogRecord::from_record_with_timestamp` to provide the timestamp yourself.
+//!
+//! The `std` feature also captures the name and a numeric id of the emitting thread as well as the process id.
+//! Each captured field can be turned off with `capture::CaptureOptions`.
+//!
+//! `SerializableLogRecordRef` is a borrowed variant that avoids allocating for records that are serialized right away.
+//! It serializes to exactly the same bytes as `SerializableLogRecord`.
+//!
+//! With the `std` feature, `logger::SerializingLogger` is a `log::Log` implementation that encodes every record
+//! with a `codec::Codec` and writes it into any `std::io::Write` sink. The `json` feature provides `codec::JsonCodec`.
+//! `reader::RecordReader` decodes such a stream again and `reader::replay_all` replays it into any `log::Log`.
+//! The records are delimited with one of the framings of the `framing` module, which also provides incremental
+//! decoders for other transports.
+//!
+//! The `rmp` feature encodes records as MessagePack for readers in other languages, see the `rmp` module for the layout.
+//! The `cbor` feature encodes them as CBOR maps with integer keys, also without `std`, see the `cbor` module for the schema.
+//! The `postcard` feature encodes them into caller-provided buffers without a heap, e.g. to forward records from a
+//! microcontroller to a host, which decodes them with the `serde` feature.
+//! The `protobuf` feature converts records to and from `prost` messages described by `schema/log_record.proto`, for
+//! collectors that generate their code from the schema, see the `protobuf` module.
+//! The `otel` feature maps records to the OpenTelemetry log data model and serializes OTLP/JSON and OTLP/protobuf export
+//! requests, see the `otel` module for the mapping.
+//!
+//! The `syslog` module formats records as RFC 5424 syslog messages and parses RFC 5424 and RFC 3164 messages back
+//! into records.
+//! The `logfmt` module renders records as logfmt lines and parses logfmt lines back into records.
+//! The `gelf` feature encodes records as GELF 1.1 messages for Graylog, optionally compressed, and sends them over UDP
+//! in chunks, see the `gelf` module.
+//! The `ecs` feature renders records as Elastic Common Schema JSON documents for Elasticsearch and reads them back.
+//! The `bunyan` feature renders records as bunyan or pino JSON lines for the Node.js log viewers and parses them back.
+//! The `journald` feature encodes records as structured journal fields in the native protocol of the systemd journal and
+//! sends them over a Unix datagram socket.
+//! The `arrow` feature converts records into Apache Arrow record batches and back, see the `arrow` module.
+//! The `parquet` feature archives records in rolling Parquet files and reads them back filtered by level and target.
+//!
+//! The `rkyv` feature archives records for zero-copy access, e.g. from memory-mapped files. A validated
+//! `ArchivedSerializableLogRecord` is replayed into a `log::Log` without deserializing it, see the `rkyv` module.
+//!
+//! Everything above except the encode side of `postcard` needs a heap and is behind the `alloc` feature, which is enabled
+//! by default. For targets without a heap, the `heapless` feature provides `FixedLogRecord`, which stores its strings
+//! inline with a bounded capacity and converts to and from `SerializableLogRecord` when `alloc` is enabled, see the
+//! `fixed` module.
+//!
+//! If the `kv` feature is enabled, the key-value pairs of the `log::Record` are captured into the `key_values` field
+//! and re-attached by the `into_log_record` macro. Values other than integers, floats, booleans and strings are
+//! captured as their text, see the `kv` module.
+//!
+//! To convert a `SerializableLogRecord` back into a `log::Record` use `with_log_record`, which builds the `log::Record` and passes it
+//! to a closure, or `replay_into`, which passes it directly to the `log` method of any `log::Log` implementation. The `log::Record`
+//! cannot be returned or stored in an intermediate variable due to the extremely restrictive lifetime of the `args` field of `log::Record`.
+//!
+//! ```rust
+//! # #[cfg(feature = "alloc")]
+//! # {
+//! # use log::Level;
+//! # use serializable_log_record::SerializableLogRecord;
+//! #
+//! # let record = log::Record::builder()
+//! #     .args(format_args!("Hello"))
+//! #     .level(Level::Info)
+//! #     .target("my_target")
+//! #     .build();
+//! #
+//! # let serializable_record = SerializableLogRecord::from(&record);
+//! #
+//! let target_len = serializable_record.with_log_record(|record| record.target().len());
+//! # assert_eq!(target_len, 9);
+//!
+//! let any_logger = log::logger();
+//! serializable_record.replay_into(any_logger);
+//! # }
+//! ```
+//!
+//! The record remembers whether `module_path` and `file` were `'static` strings. `with_log_record_interned` and
+//! `replay_into_interned` restore them through a registry of known strings, see the `statics` module.
+//!
+//! Alternatively, the `into_log_record` macro can be used. The result of this macro has to be passed directly into a call to the `log`
+//! method of any `log::Log` implementation.
 //!
 //! ```rust
+//! # #[cfg(feature = "alloc")]
+//! # {
 //! # use log::Level;
 //! # use serializable_log_record::SerializableLogRecord;
 //! #
@@ -46,41 +130,135 @@
 //! # let any_logger = log::logger();
 //! let mut builder = log::Record::builder();
 //! any_logger.log(&serializable_log_record::into_log_record!(builder, serializable_record));
+//! # }
 //! ```
 //!
 #![no_std]
 
+#[cfg(feature = "alloc")]
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
 
-use alloc::{
-    borrow::ToOwned,
-    string::{String, ToString},
-};
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(feature = "alloc")]
+mod borrowed;
+#[cfg(feature = "bunyan")]
+pub mod bunyan;
+#[cfg(feature = "alloc")]
+pub mod capture;
+#[cfg(feature = "cbor")]
+pub mod cbor;
+#[cfg(feature = "alloc")]
+pub mod codec;
+#[cfg(feature = "ecs")]
+pub mod ecs;
+#[cfg(feature = "heapless")]
+pub mod fixed;
+#[cfg(feature = "alloc")]
+pub mod framing;
+#[cfg(feature = "gelf")]
+pub mod gelf;
+#[cfg(feature = "journald")]
+pub mod journald;
+#[cfg(any(feature = "bunyan", feature = "ecs", feature = "gelf"))]
+mod json;
+#[cfg(feature = "alloc")]
+pub mod kv;
+#[cfg(feature = "alloc")]
+pub mod level;
+#[cfg(feature = "alloc")]
+pub mod logfmt;
+#[cfg(feature = "std")]
+pub mod logger;
+#[cfg(feature = "otel")]
+pub mod otel;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+#[cfg(feature = "postcard")]
+pub mod postcard;
+#[cfg(feature = "protobuf")]
+pub mod protobuf;
+#[cfg(feature = "std")]
+pub mod reader;
+#[cfg(any(feature = "alloc", feature = "heapless"))]
+mod replay;
+#[cfg(feature = "alloc")]
+mod rfc3339;
+#[cfg(feature = "rkyv")]
+pub mod rkyv;
+#[cfg(feature = "rmp")]
+pub mod rmp;
+pub mod statics;
+#[cfg(feature = "alloc")]
+pub mod syslog;
 
-use core::str::FromStr;
-use log::{Level, Record};
+#[cfg(feature = "alloc")]
+use alloc::string::String;
+
+#[cfg(feature = "alloc")]
+pub use borrowed::SerializableLogRecordRef;
+#[cfg(feature = "alloc")]
+use capture::CaptureOptions;
+#[cfg(feature = "heapless")]
+pub use fixed::FixedLogRecord;
+#[cfg(feature = "alloc")]
+use kv::KeyValues;
+#[cfg(feature = "alloc")]
+use level::{LevelFallback, ParseLevelError, RecordLevel, DEFAULT_REPLAY_LEVEL};
+#[cfg(feature = "alloc")]
+use log::{Level, Log, Record, RecordBuilder};
+#[cfg(feature = "alloc")]
+use statics::StaticStrings;
 
 /// A custom representation of the `log::Record` struct which is unfortunately
 /// not directly serializable (due to the use of `fmt::Arguments`).
 ///
 /// Use `::from` to convert a `log::Record` to a `SerializedRecord`.
 ///
-/// The use of `::into` is unfortunately not possible. This is why the
-/// `log_into_record` macro is provided. Use it directly in a function call to
-/// convert a `SerializedRecord` into a `log::Record`.
+/// The use of `::into` is unfortunately not possible. This is why
+/// `with_log_record` and `replay_into` are provided, which build the `log::Record`
+/// internally and hand it to a closure or a `log::Log` implementation.
+/// The `into_log_record` macro can also be used directly in a function call.
+#[cfg(feature = "alloc")]
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "bincode2", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(::rkyv::Archive, ::rkyv::Serialize, ::rkyv::Deserialize),
+    rkyv(derive(Debug))
+)]
 #[non_exhaustive]
 pub struct SerializableLogRecord {
-    pub level: String,
+    pub level: RecordLevel,
     pub args: String,
     pub target: String,
     pub module_path: Option<String>,
     pub file: Option<String>,
     pub line: Option<u32>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub key_values: KeyValues,
+    /// Whether `module_path` was a `'static` string in the original `log::Record`, see the `statics` module.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub module_path_static: bool,
+    /// Whether `file` was a `'static` string in the original `log::Record`, see the `statics` module.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub file_static: bool,
+    /// Wall-clock time of the conversion from a `log::Record` in nanoseconds since the Unix epoch.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub timestamp: Option<u64>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub thread_name: Option<String>,
+    /// A process-unique number of the thread that emitted the record, see `CaptureOptions`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub thread_id: Option<u64>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub process_id: Option<u32>,
 }
 
+#[cfg(feature = "alloc")]
 impl SerializableLogRecord {
     /// Create a new `SerializableLogRecord` from the given arguments.
     /// Use `::from` to directly convert a `log::Record` to a `SerializableLogRecord`.
@@ -94,37 +272,165 @@ impl SerializableLogRecord {
         line: Option<u32>,
     ) -> Self {
         Self {
-            level: level.as_str().to_owned(),
+            level: level.into(),
             args,
             target,
             module_path,
             file,
             line,
+            key_values: KeyValues::new(),
+            module_path_static: false,
+            file_static: false,
+            timestamp: None,
+            thread_name: None,
+            thread_id: None,
+            process_id: None,
         }
     }
 
+    /// Convert a `log::Record` to a `SerializableLogRecord` with the given timestamp in nanoseconds since the Unix epoch.
+    /// Without the `std` feature this is the only way to attach a timestamp during the conversion.
+    ///
+    /// ```rust
+    /// # use serializable_log_record::SerializableLogRecord;
+    /// let record = log::Record::builder().args(format_args!("Hello")).build();
+    /// let serializable_record = SerializableLogRecord::from_record_with_timestamp(&record, 1_700_000_000_000_000_000);
+    /// assert_eq!(serializable_record.timestamp, Some(1_700_000_000_000_000_000));
+    /// ```
+    #[must_use]
+    pub fn from_record_with_timestamp(record: &Record<'_>, timestamp: u64) -> Self {
+        CaptureOptions::default()
+            .with_timestamp(false)
+            .capture(record)
+            .with_timestamp(Some(timestamp))
+    }
+
+    /// Replace the key-value pairs of this record.
+    #[must_use]
+    pub fn with_key_values(mut self, key_values: KeyValues) -> Self {
+        self.key_values = key_values;
+        self
+    }
+
+    /// Replace the timestamp of this record.
+    #[must_use]
+    pub fn with_timestamp(mut self, timestamp: Option<u64>) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    /// Convert this record into a `log::Record` and pass it to the given closure.
+    /// With the `kv` feature, the timestamp is attached as the `timestamp` key-value pair
+    /// unless the record already has a pair with that key. An `Unknown` level is replayed as `DEFAULT_REPLAY_LEVEL`,
+    /// use `try_with_log_record` to choose another fallback.
+    ///
+    /// The `log::Record` only lives for the duration of the closure call because its `args` field
+    /// borrows a temporary `fmt::Arguments`.
+    pub fn with_log_record<R>(&self, f: impl FnOnce(&Record<'_>) -> R) -> R {
+        let mut builder = Record::builder();
+        f(&crate::into_log_record!(builder, self))
+    }
+
+    /// Like `with_log_record`, but applies the given fallback policy to an `Unknown` level.
+    ///
+    /// # Errors
+    /// Returns a `ParseLevelError` without calling the closure if the level is `Unknown` and the policy does not
+    /// provide a level, see `RecordLevel::to_level_or`.
+    pub fn try_with_log_record<R>(
+        &self,
+        fallback: LevelFallback,
+        f: impl FnOnce(&Record<'_>) -> R,
+    ) -> Result<R, ParseLevelError> {
+        let level = self.level.to_level_or(fallback)?;
+        let mut builder = Record::builder();
+        Ok(f(&self
+            .prepare_builder(&mut builder, None)
+            .level(level)
+            .args(format_args!("{}", self.args))
+            .build()))
+    }
+
+    /// Like `with_log_record`, but `module_path` and `file` are passed as `'static` strings if they were static
+    /// in the original `log::Record` and the registry knows them.
+    pub fn with_log_record_interned<R>(&self, statics: &dyn StaticStrings, f: impl FnOnce(&Record<'_>) -> R) -> R {
+        let mut builder = Record::builder();
+        f(&self
+            .prepare_builder(&mut builder, Some(statics))
+            .args(format_args!("{}", self.args))
+            .build())
+    }
+
+    /// Convert this record into a `log::Record` and pass it to the `log` method of the given logger.
+    pub fn replay_into(&self, logger: &dyn Log) {
+        self.with_log_record(|record| logger.log(record));
+    }
+
+    /// Like `replay_into`, but applies the given fallback policy to an `Unknown` level, see `try_with_log_record`.
+    ///
+    /// # Errors
+    /// Returns a `ParseLevelError` without logging if the level is `Unknown` and the policy does not provide a level.
+    pub fn try_replay_into(&self, logger: &dyn Log, fallback: LevelFallback) -> Result<(), ParseLevelError> {
+        self.try_with_log_record(fallback, |record| logger.log(record))
+    }
+
+    /// Like `replay_into`, but restores `'static` strings through the registry, see `with_log_record_interned`.
+    pub fn replay_into_interned(&self, logger: &dyn Log, statics: &dyn StaticStrings) {
+        self.with_log_record_interned(statics, |record| logger.log(record));
+    }
+
+    /// Set every field of the builder except `args`, which has to be set in the same expression that uses the record.
     /// Internal macro use only.
-    #[allow(clippy::must_use_candidate)]
     #[doc(hidden)]
-    pub fn string_to_level(level: &str) -> Level {
-        Level::from_str(level).unwrap_or(Level::Warn)
+    pub fn prepare_builder<'b, 'c>(
+        &'b self,
+        builder: &'c mut RecordBuilder<'b>,
+        statics: Option<&dyn StaticStrings>,
+    ) -> &'c mut RecordBuilder<'b> {
+        let fields = replay::Fields {
+            level: self.level.to_level().unwrap_or(DEFAULT_REPLAY_LEVEL),
+            target: &self.target,
+            module_path: self.module_path.as_deref(),
+            module_path_static: self.module_path_static,
+            file: self.file.as_deref(),
+            file_static: self.file_static,
+            line: self.line,
+        };
+        let builder = replay::prepare_builder(builder, fields, statics);
+        #[cfg(feature = "kv")]
+        builder.key_values(self);
+        builder
+    }
+
+    /// The name of the thread that emitted the record.
+    #[must_use]
+    pub fn thread_name(&self) -> Option<&str> {
+        self.thread_name.as_deref()
+    }
+
+    /// The process-unique number of the thread that emitted the record.
+    #[must_use]
+    pub fn thread_id(&self) -> Option<u64> {
+        self.thread_id
+    }
+
+    /// The id of the process that emitted the record.
+    #[must_use]
+    pub fn process_id(&self) -> Option<u32> {
+        self.process_id
     }
 }
 
+#[cfg(feature = "alloc")]
 impl<'a> From<&Record<'a>> for SerializableLogRecord {
     /// Convert a `log::Record` to a `SerializableLogRecord`.
+    /// With the `std` feature, the current wall-clock time, the thread and the process are captured as well.
+    /// Use `CaptureOptions` to turn these off.
     fn from(record: &Record<'a>) -> Self {
-        Self::new(
-            record.level(),
-            record.args().to_string(),
-            record.target().to_owned(),
-            record.module_path().map(str::to_owned),
-            record.file().map(str::to_owned),
-            record.line(),
-        )
+        CaptureOptions::default().capture(record)
     }
 }
 
+#[cfg(feature = "alloc")]
 impl<'a> From<Record<'a>> for SerializableLogRecord {
     /// Convert a `log::Record` to a `SerializableLogRecord`.
     fn from(value: Record<'a>) -> Self {
@@ -137,13 +443,9 @@ impl<'a> From<Record<'a>> for SerializableLogRecord {
 /// immediately into a call to the `log` method of any `log::Log` implementation.
 macro_rules! into_log_record {
     ($builder:expr, $message:expr) => {
-        $builder
-            .level(SerializableLogRecord::string_to_level(&$message.level))
+        $message
+            .prepare_builder(&mut $builder, None)
             .args(format_args!("{}", $message.args))
-            .target($message.target.as_str())
-            .module_path($message.module_path.as_deref())
-            .file($message.file.as_deref())
-            .line($message.line)
             .build()
     };
 }
@@ -0,0 +1,380 @@
+//! Rendering records as logfmt lines and parsing them back.
+//!
+//! `format` writes the fields that are set in this order, followed by the key-value pairs:
+//!
+//! ```text
+//! time=2023-11-14T22:13:20.123456789Z level=warn target=app module_path=app::disk file=src/disk.rs line=7 thread_name=main thread_id=1 process_id=42 msg="Disk full" free=0
+//! ```
+//!
+//! Values are quoted if they are empty or contain spaces, `=`, `"` or control characters. Inside quotes, `"` and `\` are
+//! escaped with a backslash, newlines, carriage returns and tabs as `\n`, `\r` and `\t`, and other control characters
+//! as `\u{..}`. Characters of keys that are not allowed in logfmt keys are replaced with `_`. Nested key-values are
+//! flattened with dots, e.g. `http.status=200`. Key-values whose key is one of the keys of the record fields, including
+//! the alternatives accepted by `parse`, get a `kv_` prefix, e.g. `kv_level=high`, and so do keys that are already
+//! `kv_` followed by such a key, so that a key-value `kv_level` is written as `kv_kv_level`.
+//!
+//! `parse` is tolerant: it never fails, skips what it cannot make sense of and keeps every key it does not know as a
+//! key-value, without the `kv_` prefix of colliding keys. `lvl`, `ts` and `message` are accepted for `level`, `time`
+//! and `msg`. Unquoted values of unknown keys
+//! that look like integers, floats or booleans are parsed as such, everything else becomes a string. A record without
+//! a level gets `Info`.
+//!
+//! ```rust
+//! use log::Level;
+//! use serializable_log_record::{logfmt, SerializableLogRecord, SerializableLogRecordRef};
+//!
+//! let mut record = SerializableLogRecord::new(Level::Info, "Saved \"a.txt\"".into(), "foo".into(), None, Some("src/x.rs".into()), Some(10));
+//! record.key_values.push("user", "alice smith");
+//! record.key_values.push("bytes", 512_u64);
+//!
+//! let line = logfmt::format(&SerializableLogRecordRef::from(&record));
+//! assert_eq!(line, r#"level=info target=foo file=src/x.rs line=10 msg="Saved \"a.txt\"" user="alice smith" bytes=512"#);
+//! assert_eq!(logfmt::parse(&line), record);
+//!
+//! let parsed = logfmt::parse(r#"ts=2023-11-14T22:13:20Z lvl=ERROR msg="connection lost" retry=true peer=10.0.0.1:80 garbage"#);
+//! assert_eq!(parsed.level, Level::Error.into());
+//! assert_eq!(parsed.timestamp, Some(1_700_000_000_000_000_000));
+//! assert_eq!(parsed.args, "connection lost");
+//! assert_eq!(parsed.key_values.get("retry"), Some(&true.into()));
+//! assert_eq!(parsed.key_values.get("peer"), Some(&"10.0.0.1:80".into()));
+//! assert_eq!(parsed.key_values.get("garbage"), Some(&"".into()));
+//! ```
+
+use crate::{
+    codec::Codec,
+    kv::{KeyValues, Value},
+    level::{LevelFallback, RecordLevel},
+    rfc3339::{self, Rfc3339},
+    SerializableLogRecord, SerializableLogRecordRef,
+};
+use alloc::{string::String, vec::Vec};
+use core::fmt::{self, Display, Write};
+use log::Level;
+
+/// The keys of the record fields, which key-values must not use.
+const RECORD_KEYS: [&str; 13] = [
+    "time",
+    "ts",
+    "level",
+    "lvl",
+    "target",
+    "module_path",
+    "file",
+    "line",
+    "thread_name",
+    "thread_id",
+    "process_id",
+    "msg",
+    "message",
+];
+
+/// Whether `key` is one of `RECORD_KEYS` after removing any number of `kv_` prefixes, and so needs one more.
+fn is_escaped(mut key: &str) -> bool {
+    while let Some(rest) = key.strip_prefix("kv_") {
+        key = rest;
+    }
+    RECORD_KEYS.contains(&key)
+}
+
+/// Render a record as a logfmt line without a trailing newline.
+#[must_use]
+pub fn format(record: &SerializableLogRecordRef<'_>) -> String {
+    let mut line = String::new();
+    // Writing to a String cannot fail.
+    let _ = write(record, &mut line);
+    line
+}
+
+/// Render a record as a logfmt line into `out`.
+///
+/// # Errors
+/// Returns an error if `out` fails.
+pub fn write(record: &SerializableLogRecordRef<'_>, out: &mut impl Write) -> fmt::Result {
+    let mut pairs = Pairs { out, first: true };
+    if let Some(nanos) = record.timestamp {
+        pairs.pair("time", &Rfc3339 { nanos, digits: 9 })?;
+    }
+    match &record.level {
+        RecordLevel::Unknown(text) => pairs.pair("level", text)?,
+        level => pairs.pair("level", &level.as_str().to_ascii_lowercase())?,
+    }
+    pairs.pair("target", &record.target)?;
+    if let Some(module_path) = &record.module_path {
+        pairs.pair("module_path", module_path)?;
+    }
+    if let Some(file) = &record.file {
+        pairs.pair("file", file)?;
+    }
+    if let Some(line) = &record.line {
+        pairs.pair("line", line)?;
+    }
+    if let Some(thread_name) = &record.thread_name {
+        pairs.pair("thread_name", thread_name)?;
+    }
+    if let Some(thread_id) = &record.thread_id {
+        pairs.pair("thread_id", thread_id)?;
+    }
+    if let Some(process_id) = &record.process_id {
+        pairs.pair("process_id", process_id)?;
+    }
+    pairs.pair("msg", &record.args)?;
+    pairs.key_values("", &record.key_values)
+}
+
+struct Pairs<'a, W> {
+    out: &'a mut W,
+    first: bool,
+}
+
+impl<W: Write> Pairs<'_, W> {
+    fn pair(&mut self, key: &str, value: &dyn Display) -> fmt::Result {
+        if !self.first {
+            self.out.write_char(' ')?;
+        }
+        self.first = false;
+        if key.is_empty() {
+            self.out.write_char('_')?;
+        }
+        for c in key.chars() {
+            self.out.write_char(if is_key_char(c) { c } else { '_' })?;
+        }
+        self.out.write_char('=')?;
+        // Render into a buffer first, since whether the value needs quotes depends on all of its characters.
+        let mut rendered = String::new();
+        write!(rendered, "{value}")?;
+        if !rendered.is_empty() && rendered.chars().all(is_key_char) {
+            return self.out.write_str(&rendered);
+        }
+        self.out.write_char('"')?;
+        for c in rendered.chars() {
+            match c {
+                '"' => self.out.write_str("\\\"")?,
+                '\\' => self.out.write_str("\\\\")?,
+                '\n' => self.out.write_str("\\n")?,
+                '\r' => self.out.write_str("\\r")?,
+                '\t' => self.out.write_str("\\t")?,
+                c if c.is_control() => write!(self.out, "\\u{{{:x}}}", u32::from(c))?,
+                c => self.out.write_char(c)?,
+            }
+        }
+        self.out.write_char('"')
+    }
+
+    fn key_values(&mut self, prefix: &str, key_values: &KeyValues) -> fmt::Result {
+        for (key, value) in key_values.iter() {
+            let mut key = String::from(key);
+            if !prefix.is_empty() {
+                key.insert(0, '.');
+                key.insert_str(0, prefix);
+            } else if is_escaped(&key) {
+                key.insert_str(0, "kv_");
+            }
+            match value {
+                Value::Nested(nested) => self.key_values(&key, nested)?,
+                value => self.pair(&key, value)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_key_char(c: char) -> bool {
+    !c.is_whitespace() && !c.is_control() && c != '=' && c != '"'
+}
+
+/// Parse a logfmt line into a record, see the module documentation. A trailing newline is ignored.
+#[must_use]
+pub fn parse(line: &str) -> SerializableLogRecord {
+    let mut record = SerializableLogRecord::new(Level::Info, String::new(), String::new(), None, None, None);
+    let mut rest = line.trim_end_matches(&['\r', '\n'][..]);
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            return record;
+        }
+        let key_end = rest.find(|c: char| c == '=' || c.is_whitespace()).unwrap_or(rest.len());
+        let key = &rest[..key_end];
+        rest = &rest[key_end..];
+        let (value, quoted) = match rest.strip_prefix('=') {
+            Some(value) => {
+                let (value, quoted, after) = parse_value(value);
+                rest = after;
+                (value, quoted)
+            }
+            None => (String::new(), false),
+        };
+        if key.is_empty() {
+            continue;
+        }
+        match key {
+            "level" | "lvl" => record.level = RecordLevel::parse(&value, LevelFallback::Keep).unwrap_or(record.level),
+            "target" => record.target = value,
+            "module_path" => record.module_path = Some(value),
+            "file" => record.file = Some(value),
+            "line" => record.line = value.parse().ok(),
+            "thread_name" => record.thread_name = Some(value),
+            "thread_id" => record.thread_id = value.parse().ok(),
+            "process_id" => record.process_id = value.parse().ok(),
+            "msg" | "message" => record.args = value,
+            "time" | "ts" if rfc3339::parse(&value).is_some() => record.timestamp = rfc3339::parse(&value),
+            key => {
+                let key = key.strip_prefix("kv_").filter(|key| is_escaped(key)).unwrap_or(key);
+                record
+                    .key_values
+                    .push(key, if quoted { Value::Str(value) } else { infer_value(value) });
+            }
+        }
+    }
+}
+
+/// Parse a quoted or unquoted value and return it, whether it was quoted and the rest of the line.
+fn parse_value(text: &str) -> (String, bool, &str) {
+    let Some(mut rest) = text.strip_prefix('"') else {
+        let end = text.find(char::is_whitespace).unwrap_or(text.len());
+        return (text[..end].into(), false, &text[end..]);
+    };
+    let mut value = String::new();
+    loop {
+        let Some(end) = rest.find(&['"', '\\'][..]) else {
+            // An unterminated quote runs until the end of the line.
+            value.push_str(rest);
+            return (value, true, "");
+        };
+        value.push_str(&rest[..end]);
+        if rest[end..].starts_with('"') {
+            return (value, true, &rest[end + 1..]);
+        }
+        let escaped = &rest[end + 1..];
+        rest = match escaped.chars().next() {
+            Some('n') => {
+                value.push('\n');
+                &escaped[1..]
+            }
+            Some('r') => {
+                value.push('\r');
+                &escaped[1..]
+            }
+            Some('t') => {
+                value.push('\t');
+                &escaped[1..]
+            }
+            Some('u') => {
+                if let Some((c, len)) = unicode_escape(&escaped[1..]) {
+                    value.push(c);
+                    &escaped[1 + len..]
+                } else {
+                    value.push_str("\\u");
+                    &escaped[1..]
+                }
+            }
+            Some(c) => {
+                value.push(c);
+                &escaped[c.len_utf8()..]
+            }
+            None => {
+                value.push('\\');
+                ""
+            }
+        };
+    }
+}
+
+/// Parse the `{..}` of `\u{..}`, or four hex digits as written by other logfmt implementations. Returns the character
+/// and the length of the escape.
+fn unicode_escape(text: &str) -> Option<(char, usize)> {
+    let (hex, len) = match text.strip_prefix('{') {
+        Some(braced) => {
+            let end = braced.find('}')?;
+            (&braced[..end], end + 2)
+        }
+        None => (text.get(..4)?, 4),
+    };
+    let c = u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)?;
+    Some((c, len))
+}
+
+/// The key-value of an unquoted value of an unknown key.
+fn infer_value(value: String) -> Value {
+    if let Ok(number) = value.parse::<u64>() {
+        Value::U64(number)
+    } else if let Ok(number) = value.parse::<i64>() {
+        Value::I64(number)
+    } else if let Some(number) = value.parse::<f64>().ok().filter(|number| number.is_finite()) {
+        Value::F64(number)
+    } else if let Ok(boolean) = value.parse::<bool>() {
+        Value::Bool(boolean)
+    } else {
+        Value::Str(value)
+    }
+}
+
+/// Encodes records as logfmt lines without a trailing newline. Use it with `framing::Framing::Newline`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogfmtCodec;
+
+impl Codec for LogfmtCodec {
+    type Error = core::str::Utf8Error;
+
+    fn encode(&self, record: &SerializableLogRecordRef<'_>, buf: &mut Vec<u8>) -> Result<(), Self::Error> {
+        buf.extend_from_slice(format(record).as_bytes());
+        Ok(())
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<SerializableLogRecord, Self::Error> {
+        core::str::from_utf8(bytes).map(parse)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_key_values_that_collide_with_record_fields() {
+        let mut record = SerializableLogRecord::new(Level::Warn, "Hi".into(), "app".into(), None, None, None);
+        for key in RECORD_KEYS {
+            record.key_values.push(key, "x");
+        }
+        record.key_values.push("kv_other", 1_u64);
+        record.key_values.push("kv_msg", "y");
+        record.key_values.push("kv_kv_level", "z");
+        let line = format(&SerializableLogRecordRef::from(&record));
+        assert!(line.starts_with("level=warn target=app msg=Hi kv_time=x kv_ts=x kv_level=x kv_lvl=x"));
+        assert!(line.ends_with(" kv_msg=x kv_message=x kv_other=1 kv_kv_msg=y kv_kv_kv_level=z"));
+        assert_eq!(parse(&line), record);
+    }
+
+    #[test]
+    fn keeps_nested_keys_unprefixed() {
+        let mut nested = KeyValues::new();
+        nested.push("level", 2_u64);
+        let mut record = SerializableLogRecord::new(Level::Info, "Hi".into(), "app".into(), None, None, None);
+        record.key_values.push("http", Value::Nested(nested));
+        assert_eq!(
+            format(&SerializableLogRecordRef::from(&record)),
+            "level=info target=app msg=Hi http.level=2"
+        );
+    }
+
+    #[test]
+    fn parses_malformed_lines_without_panicking() {
+        for line in [
+            "",
+            "=",
+            "==",
+            "\"",
+            "a=\"\\",
+            "a=\"\\u{110000}\"",
+            "a=\"\\u12",
+            "é=€ ü",
+            "line=x thread_id=-1",
+            "a=\"\\u{d800}\"",
+        ] {
+            let _ = parse(line);
+        }
+        let record = parse("line=x level= msg=\"unterminated");
+        assert_eq!(record.line, None);
+        assert_eq!(record.level, RecordLevel::Unknown(String::new()));
+        assert_eq!(record.args, "unterminated");
+    }
+}
@@ -0,0 +1,155 @@
+//! A `log::Log` implementation that serializes records into any `std::io::Write` sink.
+//!
+//! ```rust
+//! # #[cfg(feature = "json")]
+//! # {
+//! use log::{Level, LevelFilter, Log};
+//! use serializable_log_record::codec::JsonCodec;
+//! use serializable_log_record::framing::Framing;
+//! use serializable_log_record::logger::{FlushPolicy, SerializingLogger};
+//!
+//! let logger = SerializingLogger::new(Vec::new(), JsonCodec)
+//!     .with_framing(Framing::Newline)
+//!     .with_flush_policy(FlushPolicy::AtLevel(Level::Warn))
+//!     .with_level(LevelFilter::Info);
+//! logger.log(&log::Record::builder().args(format_args!("Hello")).level(Level::Info).target("app").build());
+//! logger.log(&log::Record::builder().args(format_args!("Hidden")).level(Level::Debug).target("app").build());
+//!
+//! let output = String::from_utf8(logger.into_inner()).unwrap();
+//! assert_eq!(output.lines().count(), 1);
+//! assert!(output.starts_with(r#"{"level":"INFO","args":"Hello","target":"app""#));
+//! # }
+//! ```
+//!
+//! To install it as the global logger:
+//!
+//! ```rust,no_run
+//! # #[cfg(feature = "bincode2")]
+//! # {
+//! # use serializable_log_record::{codec::Bincode2Codec, logger::SerializingLogger};
+//! let logger = SerializingLogger::new(std::io::stdout(), Bincode2Codec);
+//! log::set_max_level(logger.max_level());
+//! log::set_boxed_logger(Box::new(logger)).unwrap();
+//! # }
+//! ```
+
+use crate::{capture::CaptureOptions, codec::Codec, framing::Framing};
+use alloc::vec::Vec;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::{
+    io::Write,
+    sync::{Mutex, PoisonError},
+};
+
+/// When the underlying writer is flushed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum FlushPolicy {
+    /// After every record.
+    #[default]
+    EveryRecord,
+    /// After every record of the given level or more severe, e.g. `Level::Warn` flushes on warnings and errors.
+    AtLevel(Level),
+    /// Only when `Log::flush` is called.
+    Manual,
+}
+
+/// A `log::Log` implementation that converts each record into a `SerializableLogRecordRef`, encodes it with a `Codec`
+/// and writes it into `W`, delimited with a `Framing`.
+///
+/// Encoding and I/O errors are dropped, since `Log::log` has no way of reporting them.
+#[derive(Debug)]
+pub struct SerializingLogger<W: Write, C: Codec> {
+    writer: Mutex<W>,
+    codec: C,
+    framing: Framing,
+    flush_policy: FlushPolicy,
+    level: LevelFilter,
+    capture: CaptureOptions,
+}
+
+impl<W: Write, C: Codec> SerializingLogger<W, C> {
+    /// Create a logger with `Framing::U32LengthPrefix`, `FlushPolicy::EveryRecord`, `LevelFilter::Trace`
+    /// and the default `CaptureOptions`.
+    pub fn new(writer: W, codec: C) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+            codec,
+            framing: Framing::default(),
+            flush_policy: FlushPolicy::default(),
+            level: LevelFilter::Trace,
+            capture: CaptureOptions::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_framing(mut self, framing: Framing) -> Self {
+        self.framing = framing;
+        self
+    }
+
+    #[must_use]
+    pub fn with_flush_policy(mut self, flush_policy: FlushPolicy) -> Self {
+        self.flush_policy = flush_policy;
+        self
+    }
+
+    /// Only records with this level or more severe are written.
+    #[must_use]
+    pub fn with_level(mut self, level: LevelFilter) -> Self {
+        self.level = level;
+        self
+    }
+
+    #[must_use]
+    pub fn with_capture_options(mut self, capture: CaptureOptions) -> Self {
+        self.capture = capture;
+        self
+    }
+
+    /// The level filter of this logger, to be passed to `log::set_max_level`.
+    pub fn max_level(&self) -> LevelFilter {
+        self.level
+    }
+
+    /// Return the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn should_flush(&self, level: Level) -> bool {
+        match self.flush_policy {
+            FlushPolicy::EveryRecord => true,
+            FlushPolicy::AtLevel(at) => level <= at,
+            FlushPolicy::Manual => false,
+        }
+    }
+}
+
+impl<W: Write + Send, C: Codec + Send + Sync> Log for SerializingLogger<W, C> {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let mut payload = Vec::new();
+        if self.codec.encode(&self.capture.capture_ref(record), &mut payload).is_err() {
+            return;
+        }
+        let mut frame = Vec::with_capacity(payload.len() + 4);
+        if self.framing.encode(&payload, &mut frame).is_err() {
+            return;
+        }
+
+        let mut writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        if writer.write_all(&frame).is_ok() && self.should_flush(record.level()) {
+            let _ = writer.flush();
+        }
+    }
+
+    fn flush(&self) {
+        let _ = self.writer.lock().unwrap_or_else(PoisonError::into_inner).flush();
+    }
+}
@@ -0,0 +1,269 @@
+//! Conversion of records into the OpenTelemetry log data model and OTLP export requests.
+//!
+//! A record maps to an OTLP `LogRecord` as follows:
+//!
+//! | `SerializableLogRecord` | OTLP `LogRecord`                                                    |
+//! |-------------------------|---------------------------------------------------------------------|
+//! | `level`                 | `severity_number` `TRACE`, `DEBUG`, `INFO`, `WARN` or `ERROR` and `severity_text` as written by `RecordLevel::as_str`. `Unknown` levels are `UNSPECIFIED` |
+//! | `args`                  | `body` as a string                                                  |
+//! | `timestamp`             | `time_unix_nano`, left at 0 if the record has no timestamp          |
+//! | `target`                | attribute `log.target`                                              |
+//! | `module_path`           | attribute `code.namespace`                                          |
+//! | `file`                  | attribute `code.file.path`                                          |
+//! | `line`                  | attribute `code.line.number`                                        |
+//! | `thread_name`           | attribute `thread.name`                                             |
+//! | `thread_id`             | attribute `thread.id`                                               |
+//! | `process_id`            | attribute `process.pid`                                             |
+//! | `key_values`            | attributes with the same keys, nested key-values as `kvlist_value`  |
+//!
+//! `observed_time_unix_nano` is the time of the conversion. Key-values whose key is one of the attributes above get a
+//! `kv_` prefix, so that they cannot replace them. Unsigned key-values larger than `i64::MAX` become string attributes.
+//! `module_path_static` and `file_static` are dropped.
+//!
+//! `export_request` wraps records into an `ExportLogsServiceRequest`, which `to_json` and `to_protobuf` serialize as
+//! the bodies of an OTLP/HTTP request to `OTLP_HTTP_PATH`:
+//!
+//! ```rust
+//! use log::Level;
+//! use prost::Message;
+//! use serializable_log_record::otel::{self, ExportLogsServiceRequest};
+//! use serializable_log_record::SerializableLogRecord;
+//!
+//! let mut record = SerializableLogRecord::new(Level::Warn, "Hi".into(), "app".into(), None, Some("src/main.rs".into()), Some(7))
+//!     .with_timestamp(Some(5));
+//! record.key_values.push("user", "alice");
+//! let request = otel::export_request(otel::resource("checkout"), vec![&record]);
+//!
+//! let json: serde_json::Value = serde_json::from_str(&otel::to_json(&request).unwrap()).unwrap();
+//! let log_record = &json["resourceLogs"][0]["scopeLogs"][0]["logRecords"][0];
+//! assert_eq!(log_record["timeUnixNano"], "5");
+//! assert_eq!(log_record["severityNumber"], 13);
+//! assert_eq!(log_record["severityText"], "WARN");
+//! assert_eq!(log_record["body"]["stringValue"], "Hi");
+//! assert_eq!(log_record["attributes"][2]["key"], "code.line.number");
+//! assert_eq!(log_record["attributes"][2]["value"]["intValue"], "7");
+//! assert_ne!(log_record["observedTimeUnixNano"], "5");
+//!
+//! // A collector stand-in accepts both encodings of the same request.
+//! let from_json: ExportLogsServiceRequest = serde_json::from_str(&otel::to_json(&request).unwrap()).unwrap();
+//! let from_protobuf = ExportLogsServiceRequest::decode(otel::to_protobuf(&request).as_slice()).unwrap();
+//! assert_eq!(from_json, request);
+//! assert_eq!(from_protobuf, request);
+//! ```
+
+use crate::{kv, level::RecordLevel, SerializableLogRecord, SerializableLogRecordRef};
+use alloc::{
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::convert::TryFrom;
+use opentelemetry_proto::tonic::{
+    common::v1::{any_value, AnyValue, InstrumentationScope, KeyValue, KeyValueList},
+    logs::v1::{ResourceLogs, ScopeLogs},
+};
+use prost::Message;
+
+pub use opentelemetry_proto::tonic::{
+    collector::logs::v1::ExportLogsServiceRequest,
+    logs::v1::{LogRecord, SeverityNumber},
+    resource::v1::Resource,
+};
+
+/// The path that an OTLP/HTTP collector accepts export requests for logs on.
+pub const OTLP_HTTP_PATH: &str = "/v1/logs";
+/// The attribute that holds the target of the record.
+pub const LOG_TARGET: &str = "log.target";
+/// The attribute that holds the module path of the record.
+pub const CODE_NAMESPACE: &str = "code.namespace";
+/// The attribute that holds the file of the record.
+pub const CODE_FILE_PATH: &str = "code.file.path";
+/// The attribute that holds the line of the record.
+pub const CODE_LINE_NUMBER: &str = "code.line.number";
+/// The attribute that holds the thread name of the record.
+pub const THREAD_NAME: &str = "thread.name";
+/// The attribute that holds the thread id of the record.
+pub const THREAD_ID: &str = "thread.id";
+/// The attribute that holds the process id of the record.
+pub const PROCESS_PID: &str = "process.pid";
+
+/// The attributes written for the fields of a record, which key-values must not replace.
+const RECORD_ATTRIBUTES: [&str; 7] = [
+    LOG_TARGET,
+    CODE_NAMESPACE,
+    CODE_FILE_PATH,
+    CODE_LINE_NUMBER,
+    THREAD_NAME,
+    THREAD_ID,
+    PROCESS_PID,
+];
+
+/// The severity number of a level. `Unknown` levels are `Unspecified`.
+#[must_use]
+pub fn severity_number(level: &RecordLevel) -> SeverityNumber {
+    match level {
+        RecordLevel::Error => SeverityNumber::Error,
+        RecordLevel::Warn => SeverityNumber::Warn,
+        RecordLevel::Info => SeverityNumber::Info,
+        RecordLevel::Debug => SeverityNumber::Debug,
+        RecordLevel::Trace => SeverityNumber::Trace,
+        RecordLevel::Unknown(_) => SeverityNumber::Unspecified,
+    }
+}
+
+/// A resource with the `service.name` attribute.
+#[must_use]
+pub fn resource(service_name: &str) -> Resource {
+    Resource {
+        attributes: vec![attribute("service.name", string_value(service_name))],
+        ..Resource::default()
+    }
+}
+
+/// Wrap records into an export request with a single resource. The instrumentation scope is this crate.
+pub fn export_request<'a>(
+    resource: Resource,
+    records: impl IntoIterator<Item = &'a SerializableLogRecord>,
+) -> ExportLogsServiceRequest {
+    ExportLogsServiceRequest {
+        resource_logs: vec![ResourceLogs {
+            resource: Some(resource),
+            scope_logs: vec![ScopeLogs {
+                scope: Some(InstrumentationScope {
+                    name: env!("CARGO_PKG_NAME").into(),
+                    version: env!("CARGO_PKG_VERSION").into(),
+                    ..InstrumentationScope::default()
+                }),
+                log_records: records.into_iter().map(LogRecord::from).collect(),
+                schema_url: String::new(),
+            }],
+            schema_url: String::new(),
+        }],
+    }
+}
+
+/// Serialize an export request as OTLP/JSON, to be sent with the content type `application/json`.
+///
+/// # Errors
+/// Returns an error if serialization fails.
+pub fn to_json(request: &ExportLogsServiceRequest) -> Result<String, serde_json::Error> {
+    serde_json::to_string(request)
+}
+
+/// Serialize an export request as OTLP/protobuf, to be sent with the content type `application/x-protobuf`.
+#[must_use]
+pub fn to_protobuf(request: &ExportLogsServiceRequest) -> Vec<u8> {
+    request.encode_to_vec()
+}
+
+impl From<&SerializableLogRecordRef<'_>> for LogRecord {
+    fn from(record: &SerializableLogRecordRef<'_>) -> Self {
+        let mut attributes = vec![attribute(LOG_TARGET, string_value(&record.target))];
+        let mut push = |key: &str, value: Option<any_value::Value>| attributes.extend(value.map(|value| attribute(key, value)));
+        push(CODE_NAMESPACE, record.module_path.as_deref().map(string_value));
+        push(CODE_FILE_PATH, record.file.as_deref().map(string_value));
+        push(
+            CODE_LINE_NUMBER,
+            record.line.map(|line| any_value::Value::IntValue(line.into())),
+        );
+        push(THREAD_NAME, record.thread_name.as_deref().map(string_value));
+        push(THREAD_ID, record.thread_id.map(unsigned_value));
+        push(
+            PROCESS_PID,
+            record.process_id.map(|pid| any_value::Value::IntValue(pid.into())),
+        );
+        attributes.extend(key_value_attributes(&record.key_values).into_iter().map(|mut attribute| {
+            if RECORD_ATTRIBUTES.contains(&attribute.key.as_str()) {
+                attribute.key.insert_str(0, "kv_");
+            }
+            attribute
+        }));
+        Self {
+            time_unix_nano: record.timestamp.unwrap_or_default(),
+            observed_time_unix_nano: crate::capture::env::timestamp().unwrap_or_default(),
+            severity_number: severity_number(&record.level).into(),
+            severity_text: record.level.as_str().into(),
+            body: Some(AnyValue {
+                value: Some(string_value(&record.args)),
+            }),
+            attributes,
+            ..Self::default()
+        }
+    }
+}
+
+impl From<&SerializableLogRecord> for LogRecord {
+    fn from(record: &SerializableLogRecord) -> Self {
+        Self::from(&SerializableLogRecordRef::from(record))
+    }
+}
+
+fn attribute(key: &str, value: any_value::Value) -> KeyValue {
+    KeyValue {
+        key: key.into(),
+        value: Some(AnyValue { value: Some(value) }),
+        ..KeyValue::default()
+    }
+}
+
+fn string_value(value: &str) -> any_value::Value {
+    any_value::Value::StringValue(value.into())
+}
+
+fn unsigned_value(value: u64) -> any_value::Value {
+    i64::try_from(value).map_or_else(
+        |_| any_value::Value::StringValue(value.to_string()),
+        any_value::Value::IntValue,
+    )
+}
+
+fn key_value_attributes(key_values: &kv::KeyValues) -> Vec<KeyValue> {
+    key_values
+        .iter()
+        .map(|(key, value)| {
+            let value = match value {
+                kv::Value::Str(value) => any_value::Value::StringValue(value.clone()),
+                kv::Value::I64(value) => any_value::Value::IntValue(*value),
+                kv::Value::U64(value) => unsigned_value(*value),
+                kv::Value::F64(value) => any_value::Value::DoubleValue(*value),
+                kv::Value::Bool(value) => any_value::Value::BoolValue(*value),
+                kv::Value::Nested(value) => any_value::Value::KvlistValue(KeyValueList {
+                    values: key_value_attributes(value),
+                }),
+            };
+            attribute(key, value)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Level;
+
+    #[test]
+    fn prefixes_key_values_that_collide_with_record_attributes() {
+        let mut record = SerializableLogRecord::new(Level::Info, "Hi".into(), "app".into(), None, None, None);
+        record.key_values.push(LOG_TARGET, "spoofed");
+        record.key_values.push(CODE_LINE_NUMBER, 1_u64);
+        record.key_values.push("user", "alice");
+        let keys: Vec<String> = LogRecord::from(&record)
+            .attributes
+            .into_iter()
+            .map(|attribute| attribute.key)
+            .collect();
+        assert_eq!(keys, ["log.target", "kv_log.target", "kv_code.line.number", "user"]);
+    }
+
+    #[test]
+    fn observes_the_conversion_time() {
+        let record = SerializableLogRecord::new(Level::Info, "Hi".into(), "app".into(), None, None, None);
+        let log_record = LogRecord::from(&record);
+        assert_eq!(log_record.time_unix_nano, 0);
+        assert!(log_record.observed_time_unix_nano > 1_700_000_000_000_000_000);
+
+        let log_record = LogRecord::from(&record.with_timestamp(Some(5)));
+        assert_eq!(log_record.time_unix_nano, 5);
+        assert!(log_record.observed_time_unix_nano > 1_700_000_000_000_000_000);
+    }
+}
@@ -0,0 +1,703 @@
+//! Archiving records in Parquet files and replaying them, built on the columns of the `arrow` module.
+//!
+//! `RollingParquetWriter` appends records to numbered files `<prefix>-000000.parquet`, `<prefix>-000001.parquet`, …
+//! in a directory. It buffers records until a row group is full, writes it and starts a new file once the current one
+//! holds `max_rows_per_file` records or, if set, `max_file_size` bytes. Existing files are never overwritten: the
+//! first file is numbered after the highest number among the files with the same prefix in the directory. A file only becomes readable when it is finished, which
+//! happens when it is full, on `close` and, ignoring errors, on drop.
+//!
+//! `ParquetRecordReader` streams the records of a file back. A `RecordFilter` on level and target is pushed down into
+//! the Parquet reader: row groups whose statistics rule out a match are skipped, and the other columns are only
+//! decoded for the rows that match.
+//!
+//! ```rust
+//! use log::{Level, LevelFilter};
+//! use serializable_log_record::parquet::{Compression, ParquetRecordReader, RecordFilter, RollingParquetWriter};
+//! use serializable_log_record::SerializableLogRecord;
+//!
+//! let directory = std::env::temp_dir().join(format!("parquet-doctest-{}", std::process::id()));
+//! std::fs::create_dir_all(&directory).unwrap();
+//!
+//! let mut writer = RollingParquetWriter::new(&directory, "app")
+//!     .with_row_group_size(2)
+//!     .with_max_rows_per_file(4)
+//!     .with_compression(Compression::SNAPPY);
+//! for i in 0..6 {
+//!     let (level, target) = if i % 3 == 0 { (Level::Warn, "app::db") } else { (Level::Info, "app::http") };
+//!     writer.append(SerializableLogRecord::new(level, format!("Record {i}"), target.into(), None, None, None)).unwrap();
+//! }
+//! let files = writer.close().unwrap();
+//! assert_eq!(files.len(), 2);
+//! assert!(files[0].ends_with("app-000000.parquet"));
+//!
+//! let reader = ParquetRecordReader::open(&files[0], RecordFilter::default()).unwrap();
+//! assert_eq!(reader.count(), 4);
+//!
+//! let filter = RecordFilter::default().with_max_level(LevelFilter::Warn).with_target_prefix("app::db");
+//! let warnings: Vec<_> = files
+//!     .iter()
+//!     .flat_map(|file| ParquetRecordReader::open(file, filter.clone()).unwrap())
+//!     .map(|record| record.unwrap().args)
+//!     .collect();
+//! assert_eq!(warnings, ["Record 0", "Record 3"]);
+//!
+//! // Or replay them into any logger.
+//! let replayed = ParquetRecordReader::open(&files[0], filter).unwrap().replay_all(log::logger()).unwrap();
+//! assert_eq!(replayed, 2);
+//! # std::fs::remove_dir_all(&directory).unwrap();
+//! ```
+
+use crate::{
+    arrow::{self, RecordBatchError},
+    level::{LevelFallback, RecordLevel},
+    SerializableLogRecord,
+};
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use arrow_array::{BooleanArray, RecordBatch};
+use arrow_schema::ArrowError;
+use core::fmt;
+use log::{LevelFilter, Log};
+use parquet::{
+    arrow::{
+        arrow_reader::{ArrowPredicateFn, ParquetRecordBatchReader, ParquetRecordBatchReaderBuilder, RowFilter},
+        ArrowWriter, ProjectionMask,
+    },
+    errors::ParquetError,
+    file::{metadata::RowGroupMetaData, properties::WriterProperties, reader::ChunkReader},
+    schema::types::SchemaDescriptor,
+};
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+};
+
+pub use parquet::basic::Compression;
+
+/// The number of records per row group unless configured otherwise.
+pub const DEFAULT_ROW_GROUP_SIZE: usize = 64 * 1024;
+
+/// The number of records per file unless configured otherwise.
+pub const DEFAULT_MAX_ROWS_PER_FILE: usize = 1_000_000;
+
+/// Appends records to a rolling set of Parquet files, see the module documentation.
+#[derive(Debug)]
+pub struct RollingParquetWriter {
+    directory: PathBuf,
+    prefix: String,
+    row_group_size: usize,
+    compression: Compression,
+    max_rows_per_file: usize,
+    max_file_size: Option<usize>,
+    buffer: Vec<SerializableLogRecord>,
+    current: Option<ArrowWriter<File>>,
+    rows_in_file: usize,
+    /// The number of the next file, found by scanning the directory when the first file is created. Also `None` once
+    /// `u32::MAX` was used, so that the next scan fails.
+    next_sequence: Option<u32>,
+    files: Vec<PathBuf>,
+}
+
+impl RollingParquetWriter {
+    /// A writer that creates its files in `directory`, which must exist, with names starting with `prefix`. Files are
+    /// compressed with Snappy by default.
+    pub fn new(directory: impl Into<PathBuf>, prefix: impl Into<String>) -> Self {
+        Self {
+            directory: directory.into(),
+            prefix: prefix.into(),
+            row_group_size: DEFAULT_ROW_GROUP_SIZE,
+            compression: Compression::SNAPPY,
+            max_rows_per_file: DEFAULT_MAX_ROWS_PER_FILE,
+            max_file_size: None,
+            buffer: Vec::new(),
+            current: None,
+            rows_in_file: 0,
+            next_sequence: None,
+            files: Vec::new(),
+        }
+    }
+
+    /// The number of records per row group, `DEFAULT_ROW_GROUP_SIZE` by default. This many records are buffered in
+    /// memory before they are written.
+    #[must_use]
+    pub fn with_row_group_size(mut self, row_group_size: usize) -> Self {
+        self.row_group_size = row_group_size.max(1);
+        self
+    }
+
+    /// The compression of the column chunks, Snappy by default.
+    #[must_use]
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Start a new file after this many records, `DEFAULT_MAX_ROWS_PER_FILE` by default.
+    #[must_use]
+    pub fn with_max_rows_per_file(mut self, max_rows_per_file: usize) -> Self {
+        self.max_rows_per_file = max_rows_per_file.max(1);
+        self
+    }
+
+    /// Also start a new file once the current one has reached this many bytes. Checked after each row group, so files
+    /// exceed it by up to one row group.
+    #[must_use]
+    pub fn with_max_file_size(mut self, max_file_size: usize) -> Self {
+        self.max_file_size = Some(max_file_size);
+        self
+    }
+
+    /// Buffer a record and write the row group once it is full.
+    ///
+    /// # Errors
+    /// Returns an error if a full row group cannot be written or a new file cannot be created.
+    pub fn append(&mut self, record: SerializableLogRecord) -> Result<(), ArchiveError> {
+        self.buffer.push(record);
+        if self.buffer.len() >= self.row_group_size.min(self.max_rows_per_file - self.rows_in_file) {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Write the buffered records as a row group, even if it is not full.
+    ///
+    /// # Errors
+    /// Returns an error if the row group cannot be written or a new file cannot be created.
+    pub fn flush(&mut self) -> Result<(), ArchiveError> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let batch = arrow::to_record_batch(&self.buffer).map_err(ArchiveError::RecordBatch)?;
+        let writer = if let Some(writer) = &mut self.current {
+            writer
+        } else {
+            let file = self.create_file()?;
+            let properties = WriterProperties::builder()
+                .set_compression(self.compression)
+                .set_max_row_group_size(self.row_group_size)
+                .build();
+            let writer = ArrowWriter::try_new(file, batch.schema(), Some(properties)).map_err(ArchiveError::Parquet)?;
+            self.current.insert(writer)
+        };
+        writer.write(&batch).map_err(ArchiveError::Parquet)?;
+        writer.flush().map_err(ArchiveError::Parquet)?;
+        self.rows_in_file += self.buffer.len();
+        self.buffer.clear();
+        let size_reached = self
+            .max_file_size
+            .is_some_and(|max_file_size| writer.bytes_written() >= max_file_size);
+        if self.rows_in_file >= self.max_rows_per_file || size_reached {
+            self.finish_file()?;
+        }
+        Ok(())
+    }
+
+    /// The files created so far, including the one currently written to.
+    #[must_use]
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Write the buffered records, finish the current file and return all files that were created.
+    ///
+    /// # Errors
+    /// Returns an error if the buffered records or the file footer cannot be written.
+    pub fn close(mut self) -> Result<Vec<PathBuf>, ArchiveError> {
+        self.flush()?;
+        self.finish_file()?;
+        Ok(core::mem::take(&mut self.files))
+    }
+
+    fn create_file(&mut self) -> Result<File, ArchiveError> {
+        let mut sequence = match self.next_sequence {
+            Some(sequence) => sequence,
+            None => self.scan_sequence()?,
+        };
+        loop {
+            let path = self.directory.join(format!("{}-{:06}.parquet", self.prefix, sequence));
+            let next_sequence = sequence.checked_add(1).ok_or(ArchiveError::SequenceExhausted);
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => {
+                    // The last number can still be used, only the file after it fails.
+                    self.next_sequence = next_sequence.ok();
+                    self.files.push(path);
+                    return Ok(file);
+                }
+                // Another writer created the file since the directory was scanned.
+                Err(error) if error.kind() == io::ErrorKind::AlreadyExists => sequence = next_sequence?,
+                Err(error) => return Err(ArchiveError::Io(error)),
+            }
+        }
+    }
+
+    /// The number after the highest number of the existing files with this prefix, 0 if there are none.
+    fn scan_sequence(&self) -> Result<u32, ArchiveError> {
+        let mut highest = None;
+        for entry in std::fs::read_dir(&self.directory).map_err(ArchiveError::Io)? {
+            let name = entry.map_err(ArchiveError::Io)?.file_name();
+            let sequence = name
+                .to_str()
+                .and_then(|name| {
+                    name.strip_prefix(self.prefix.as_str())?
+                        .strip_prefix('-')?
+                        .strip_suffix(".parquet")
+                })
+                .filter(|digits| !digits.is_empty() && digits.bytes().all(|byte| byte.is_ascii_digit()))
+                .and_then(|digits| digits.parse::<u32>().ok());
+            highest = highest.max(sequence);
+        }
+        match highest {
+            Some(highest) => highest.checked_add(1).ok_or(ArchiveError::SequenceExhausted),
+            None => Ok(0),
+        }
+    }
+
+    fn finish_file(&mut self) -> Result<(), ArchiveError> {
+        self.rows_in_file = 0;
+        if let Some(writer) = self.current.take() {
+            writer.close().map_err(ArchiveError::Parquet)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for RollingParquetWriter {
+    fn drop(&mut self) {
+        let _ = self.flush();
+        let _ = self.finish_file();
+    }
+}
+
+/// Selects the records that a `ParquetRecordReader` returns. The default selects all records.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RecordFilter {
+    max_level: LevelFilter,
+    unknown_levels: bool,
+    target_prefix: Option<String>,
+}
+
+impl Default for RecordFilter {
+    fn default() -> Self {
+        Self {
+            max_level: LevelFilter::Trace,
+            unknown_levels: false,
+            target_prefix: None,
+        }
+    }
+}
+
+impl RecordFilter {
+    /// Only select records at this level or more severe, like `log::set_max_level`. Below `Trace`, records with an
+    /// `Unknown`, empty or missing level are only selected with `with_unknown_levels`.
+    #[must_use]
+    pub fn with_max_level(mut self, max_level: LevelFilter) -> Self {
+        self.max_level = max_level;
+        self
+    }
+
+    /// Whether records with an `Unknown`, empty or missing level pass a `with_max_level` below `Trace`. Not by default,
+    /// as their severity is not known.
+    #[must_use]
+    pub fn with_unknown_levels(mut self, unknown_levels: bool) -> Self {
+        self.unknown_levels = unknown_levels;
+        self
+    }
+
+    /// Only select records whose target starts with `target_prefix`.
+    #[must_use]
+    pub fn with_target_prefix(mut self, target_prefix: impl Into<String>) -> Self {
+        self.target_prefix = Some(target_prefix.into());
+        self
+    }
+
+    fn is_active(&self) -> bool {
+        self.max_level < LevelFilter::Trace || self.target_prefix.is_some()
+    }
+
+    fn level_matches(&self, level: Option<&str>) -> bool {
+        if self.max_level == LevelFilter::Off {
+            return false;
+        }
+        match level.and_then(|level| RecordLevel::parse(level, LevelFallback::Keep).ok()?.to_level()) {
+            Some(level) => level <= self.max_level,
+            None => self.max_level == LevelFilter::Trace || self.unknown_levels,
+        }
+    }
+
+    fn target_matches(&self, target: &str) -> bool {
+        self.target_prefix
+            .as_ref()
+            .is_none_or(|prefix| target.starts_with(prefix.as_str()))
+    }
+
+    /// Whether the statistics of a row group allow a matching record.
+    fn may_match(&self, row_group: &RowGroupMetaData, level: usize, target: usize) -> bool {
+        let bounds = |column: usize| {
+            let statistics = row_group.column(column).statistics()?;
+            let min = core::str::from_utf8(statistics.min_bytes_opt()?).ok()?;
+            let max = core::str::from_utf8(statistics.max_bytes_opt()?).ok()?;
+            Some((min, max, statistics.min_is_exact() && statistics.max_is_exact()))
+        };
+        if let (Some(prefix), Some((min, max, _))) = (&self.target_prefix, bounds(target)) {
+            // Every target in the row group is at least `min`, so none starts with the prefix if `min` sorts after them.
+            if max < prefix.as_str() || (min > prefix.as_str() && !min.starts_with(prefix.as_str())) {
+                return false;
+            }
+        }
+        match bounds(level) {
+            Some((min, max, true)) if min == max => self.level_matches(Some(min)),
+            _ => true,
+        }
+    }
+}
+
+/// Streams the records of a Parquet file written by `RollingParquetWriter`, see the module documentation.
+pub struct ParquetRecordReader {
+    batches: ParquetRecordBatchReader,
+    pending: vec::IntoIter<SerializableLogRecord>,
+}
+
+impl ParquetRecordReader {
+    /// Open a file and read the records that match `filter`.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be opened or is not a Parquet file with the columns of the `arrow` module.
+    pub fn open(path: impl AsRef<Path>, filter: RecordFilter) -> Result<Self, ArchiveError> {
+        Self::new(File::open(path).map_err(ArchiveError::Io)?, filter)
+    }
+
+    /// Read the records that match `filter` from any Parquet input, e.g. a `File` or `bytes::Bytes`.
+    ///
+    /// # Errors
+    /// Returns an error if the input is not a Parquet file with the columns of the `arrow` module.
+    pub fn new<T: ChunkReader + 'static>(input: T, filter: RecordFilter) -> Result<Self, ArchiveError> {
+        let mut builder = ParquetRecordBatchReaderBuilder::try_new(input).map_err(ArchiveError::Parquet)?;
+        if filter.is_active() {
+            let schema = builder.parquet_schema();
+            let level = leaf_column(schema, "level")?;
+            let target = leaf_column(schema, "target")?;
+            let row_groups = builder
+                .metadata()
+                .row_groups()
+                .iter()
+                .enumerate()
+                .filter(|(_, row_group)| filter.may_match(row_group, level, target))
+                .map(|(index, _)| index)
+                .collect();
+            let mask = ProjectionMask::leaves(schema, [level, target]);
+            let predicate = ArrowPredicateFn::new(mask, move |batch: RecordBatch| matching_rows(&filter, &batch));
+            builder = builder
+                .with_row_groups(row_groups)
+                .with_row_filter(RowFilter::new(vec![Box::new(predicate)]));
+        }
+        Ok(Self {
+            batches: builder.build().map_err(ArchiveError::Parquet)?,
+            pending: Vec::new().into_iter(),
+        })
+    }
+
+    /// Replay every remaining record into `logger` and return how many were replayed.
+    ///
+    /// # Errors
+    /// Returns the first error, the records before it have been replayed.
+    pub fn replay_all(self, logger: &dyn Log) -> Result<usize, ArchiveError> {
+        let mut count = 0;
+        for record in self {
+            record?.replay_into(logger);
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+impl Iterator for ParquetRecordReader {
+    type Item = Result<SerializableLogRecord, ArchiveError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(record) = self.pending.next() {
+                return Some(Ok(record));
+            }
+            let records = self
+                .batches
+                .next()?
+                .map_err(RecordBatchError::Arrow)
+                .and_then(|batch| arrow::from_record_batch(&batch));
+            match records {
+                Ok(records) => self.pending = records.into_iter(),
+                Err(error) => return Some(Err(ArchiveError::RecordBatch(error))),
+            }
+        }
+    }
+}
+
+impl fmt::Debug for ParquetRecordReader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ParquetRecordReader")
+            .field("pending", &self.pending.len())
+            .finish_non_exhaustive()
+    }
+}
+
+fn leaf_column(schema: &SchemaDescriptor, name: &'static str) -> Result<usize, ArchiveError> {
+    schema
+        .columns()
+        .iter()
+        .position(|column| column.path().string() == name)
+        .ok_or(ArchiveError::RecordBatch(RecordBatchError::MissingColumn(name)))
+}
+
+fn matching_rows(filter: &RecordFilter, batch: &RecordBatch) -> Result<BooleanArray, ArrowError> {
+    let column = |name| {
+        let array = batch
+            .column_by_name(name)
+            .ok_or_else(|| ArrowError::SchemaError(format!("no {name} column")))?;
+        arrow::strings(array.as_ref(), name).map_err(|error| ArrowError::SchemaError(error.to_string()))
+    };
+    let levels = column("level")?;
+    let targets = column("target")?;
+    Ok(levels
+        .into_iter()
+        .zip(targets)
+        .map(|(level, target)| Some(filter.level_matches(level) && filter.target_matches(target.unwrap_or_default())))
+        .collect())
+}
+
+/// The error returned when records cannot be archived or read back.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ArchiveError {
+    Parquet(ParquetError),
+    /// A file cannot be created or opened.
+    Io(io::Error),
+    /// Records cannot be converted into a record batch or back.
+    RecordBatch(RecordBatchError),
+    /// A file with the highest possible number, `u32::MAX`, already exists for the prefix.
+    SequenceExhausted,
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parquet(error) => write!(f, "Parquet error: {error}"),
+            Self::Io(error) => write!(f, "Parquet archive I/O error: {error}"),
+            Self::RecordBatch(error) => error.fmt(f),
+            Self::SequenceExhausted => f.write_str("no file numbers are left for the Parquet archive"),
+        }
+    }
+}
+
+impl core::error::Error for ArchiveError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Parquet(error) => Some(error),
+            Self::Io(error) => Some(error),
+            Self::RecordBatch(error) => Some(error),
+            Self::SequenceExhausted => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::{ArrayRef, StringArray};
+    use log::Level;
+    use std::sync::Arc;
+
+    /// A directory that is removed again when the test ends.
+    struct Directory(PathBuf);
+
+    impl Directory {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("parquet-test-{}-{name}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for Directory {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn record(level: RecordLevel, target: &str) -> SerializableLogRecord {
+        let mut record = SerializableLogRecord::new(Level::Info, "Hi".into(), target.into(), None, None, None);
+        record.level = level;
+        record
+    }
+
+    fn matching_row_groups(path: &Path, filter: &RecordFilter) -> Vec<usize> {
+        let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(path).unwrap()).unwrap();
+        let schema = builder.parquet_schema();
+        let (level, target) = (leaf_column(schema, "level").unwrap(), leaf_column(schema, "target").unwrap());
+        builder
+            .metadata()
+            .row_groups()
+            .iter()
+            .enumerate()
+            .filter(|(_, row_group)| filter.may_match(row_group, level, target))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    fn count(path: &Path, filter: RecordFilter) -> usize {
+        ParquetRecordReader::open(path, filter).unwrap().map(Result::unwrap).count()
+    }
+
+    #[test]
+    fn unknown_levels() {
+        let warn = RecordFilter::default().with_max_level(LevelFilter::Warn);
+        for level in [None, Some(""), Some("notice")] {
+            assert!(!warn.level_matches(level), "{:?}", level);
+            assert!(warn.clone().with_unknown_levels(true).level_matches(level), "{:?}", level);
+            assert!(RecordFilter::default().level_matches(level), "{:?}", level);
+        }
+        assert!(warn.level_matches(Some("ERROR")));
+        assert!(!warn.level_matches(Some("debug")));
+
+        let off = RecordFilter::default()
+            .with_max_level(LevelFilter::Off)
+            .with_unknown_levels(true);
+        for level in [None, Some(""), Some("notice"), Some("error")] {
+            assert!(!off.level_matches(level), "{:?}", level);
+        }
+    }
+
+    #[test]
+    fn row_groups_are_pruned() {
+        let directory = Directory::new("pruning");
+        let mut writer = RollingParquetWriter::new(&directory.0, "app").with_row_group_size(2);
+        let records = [
+            (RecordLevel::Error, "a::x"),
+            (RecordLevel::Error, "a::y"),
+            (RecordLevel::Info, "b"),
+            (RecordLevel::Info, "b"),
+            (RecordLevel::Unknown("notice".into()), "a"),
+            (RecordLevel::Unknown("notice".into()), "a"),
+        ];
+        for (level, target) in records {
+            writer.append(record(level, target)).unwrap();
+        }
+        let files = writer.close().unwrap();
+        let file = files[0].as_path();
+
+        let warn = RecordFilter::default().with_max_level(LevelFilter::Warn);
+        assert_eq!(matching_row_groups(file, &warn), [0]);
+        assert_eq!(count(file, warn.clone()), 2);
+        assert_eq!(matching_row_groups(file, &warn.clone().with_unknown_levels(true)), [0, 2]);
+        assert_eq!(count(file, warn.with_unknown_levels(true)), 4);
+        assert_eq!(
+            matching_row_groups(file, &RecordFilter::default().with_target_prefix("b")),
+            [1]
+        );
+        assert_eq!(
+            matching_row_groups(file, &RecordFilter::default().with_target_prefix("a::")),
+            [0]
+        );
+        assert_eq!(count(file, RecordFilter::default().with_target_prefix("a")), 4);
+        assert_eq!(count(file, RecordFilter::default()), 6);
+    }
+
+    #[test]
+    fn files_roll_over() {
+        let directory = Directory::new("rollover");
+        let mut writer = RollingParquetWriter::new(&directory.0, "app")
+            .with_row_group_size(2)
+            .with_max_rows_per_file(3);
+        for _ in 0..7 {
+            writer.append(record(RecordLevel::Info, "app")).unwrap();
+        }
+        let files = writer.close().unwrap();
+        let counts: Vec<_> = files.iter().map(|file| count(file, RecordFilter::default())).collect();
+        assert_eq!(counts, [3, 3, 1]);
+
+        // A restarted writer does not overwrite the existing files.
+        let mut writer = RollingParquetWriter::new(&directory.0, "app")
+            .with_row_group_size(2)
+            .with_max_file_size(1);
+        for _ in 0..4 {
+            writer.append(record(RecordLevel::Info, "app")).unwrap();
+        }
+        let files = writer.close().unwrap();
+        assert!(files[0].ends_with("app-000003.parquet"));
+        assert!(files[1].ends_with("app-000004.parquet"));
+        assert_eq!(files.len(), 2);
+        assert_eq!(count(&directory.0.join("app-000002.parquet"), RecordFilter::default()), 1);
+    }
+
+    #[test]
+    fn continues_after_the_highest_file_number() {
+        let directory = Directory::new("sequence");
+        for name in [
+            "app-000041.parquet",
+            "app-7.parquet",
+            "app-x.parquet",
+            "other-000099.parquet",
+            "app-000099.txt",
+        ] {
+            std::fs::write(directory.0.join(name), b"").unwrap();
+        }
+        let mut writer = RollingParquetWriter::new(&directory.0, "app").with_row_group_size(1);
+        writer.append(record(RecordLevel::Info, "app")).unwrap();
+        let files = writer.close().unwrap();
+        assert_eq!(files, [directory.0.join("app-000042.parquet")]);
+
+        // The last number is used, the file after it fails.
+        std::fs::write(directory.0.join(format!("app-{}.parquet", u32::MAX - 1)), b"").unwrap();
+        let mut writer = RollingParquetWriter::new(&directory.0, "app")
+            .with_row_group_size(1)
+            .with_max_rows_per_file(1);
+        writer.append(record(RecordLevel::Info, "app")).unwrap();
+        assert!(matches!(
+            writer.append(record(RecordLevel::Info, "app")),
+            Err(ArchiveError::SequenceExhausted)
+        ));
+        assert!(directory.0.join(format!("app-{}.parquet", u32::MAX)).exists());
+    }
+
+    #[test]
+    fn errors() {
+        let directory = Directory::new("errors");
+        let mut writer = RollingParquetWriter::new(directory.0.join("missing"), "app").with_row_group_size(1);
+        assert!(matches!(
+            writer.append(record(RecordLevel::Info, "app")),
+            Err(ArchiveError::Io(_))
+        ));
+
+        let not_parquet = directory.0.join("not.parquet");
+        std::fs::write(&not_parquet, b"not parquet").unwrap();
+        assert!(matches!(
+            ParquetRecordReader::open(&not_parquet, RecordFilter::default()),
+            Err(ArchiveError::Parquet(_))
+        ));
+        assert!(matches!(
+            ParquetRecordReader::open(directory.0.join("missing.parquet"), RecordFilter::default()),
+            Err(ArchiveError::Io(_))
+        ));
+
+        let other = directory.0.join("other.parquet");
+        let batch = RecordBatch::try_from_iter([("message", Arc::new(StringArray::from(vec!["Hi"])) as ArrayRef)]).unwrap();
+        let mut writer = ArrowWriter::try_new(File::create(&other).unwrap(), batch.schema(), None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+        let filter = RecordFilter::default().with_max_level(LevelFilter::Warn);
+        assert!(matches!(
+            ParquetRecordReader::open(&other, filter),
+            Err(ArchiveError::RecordBatch(RecordBatchError::MissingColumn("level")))
+        ));
+        let mut reader = ParquetRecordReader::open(&other, RecordFilter::default()).unwrap();
+        assert!(matches!(
+            reader.next(),
+            Some(Err(ArchiveError::RecordBatch(RecordBatchError::MissingColumn(_))))
+        ));
+    }
+}
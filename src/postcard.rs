@@ -0,0 +1,371 @@
+//! Compact encoding of records with `postcard` for embedded transports.
+//!
+//! The encode side does not need a heap: `PostcardRecord` borrows the fields of a `log::Record` or, with the `heapless`
+//! feature, a `FixedLogRecord`, and writes them into a caller-provided buffer, formatting the message directly into it.
+//! So firmware without an allocator can forward records over UART or USB. `PostcardRecord::to_slice_cobs` produces a
+//! COBS frame terminated by a zero byte, the same as `framing::Framing::Cobs`, so the host can find the frame
+//! boundaries in the byte stream with a `framing::FrameDecoder`.
+//!
+//! A `PostcardRecord` encodes to the same bytes as a `SerializableLogRecordRef` without key-values, thread or process.
+//! Decoding, `PostcardCodec` and encoding a `SerializableLogRecordRef` also need the `serde` feature, which needs `alloc`.
+//!
+//! ```rust
+//! use log::Level;
+//! use serializable_log_record::postcard::{self, PostcardRecord};
+//!
+//! // On the microcontroller
+//! let record = log::Record::builder().args(format_args!("Hi")).level(Level::Warn).target("app").line(Some(7)).build();
+//! let mut buf = [0; 256];
+//! let frame = PostcardRecord::from(&record).to_slice_cobs(&mut buf).unwrap();
+//! assert_eq!(frame, [0x09, 0x01, 0x02, b'H', b'i', 0x03, b'a', b'p', b'p', 0x01, 0x03, 0x01, 0x07, 0x01, 0x01, 0x01, 0x01,
+//!     0x01, 0x01, 0x01, 0x00]);
+//! assert_eq!(PostcardRecord::from(&record).to_slice(&mut [0; 8]), Err(postcard::Error::SerializeBufferFull));
+//! # let uart = frame.to_vec();
+//!
+//! // On the host
+//! # #[cfg(feature = "serde")]
+//! # {
+//! use serializable_log_record::framing::{FrameDecoder, Framing};
+//! use serializable_log_record::into_log_record;
+//!
+//! let mut decoder = FrameDecoder::new(Framing::Cobs);
+//! decoder.push(&uart);
+//! while let Some(frame) = decoder.next_frame().unwrap() {
+//!     let record = postcard::from_bytes(&frame).unwrap();
+//!     assert_eq!(record.args, "Hi");
+//!     let mut builder = log::Record::builder();
+//!     log::logger().log(&into_log_record!(builder, record));
+//! }
+//! # }
+//! ```
+
+#[cfg(feature = "serde")]
+use crate::{codec::Codec, SerializableLogRecord, SerializableLogRecordRef};
+#[cfg(feature = "serde")]
+use alloc::vec::Vec;
+use core::fmt;
+use log::{Level, Record};
+use serde::{ser::SerializeStruct, Serialize, Serializer};
+
+pub use ::postcard::Error;
+
+/// A record borrowed for encoding without a heap, see the module documentation.
+#[derive(Clone, Copy)]
+pub struct PostcardRecord<'a> {
+    level: Level,
+    args: &'a dyn fmt::Display,
+    target: &'a str,
+    module_path: Option<&'a str>,
+    file: Option<&'a str>,
+    line: Option<u32>,
+    module_path_static: bool,
+    file_static: bool,
+    timestamp: Option<u64>,
+}
+
+impl PostcardRecord<'_> {
+    /// The time of the record in nanoseconds since the Unix epoch, none by default.
+    #[must_use]
+    pub fn with_timestamp(mut self, timestamp: Option<u64>) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    /// Encode the record into `buf` and return the used part of it.
+    ///
+    /// # Errors
+    /// Returns an error if the record does not fit into `buf`.
+    pub fn to_slice<'b>(&self, buf: &'b mut [u8]) -> Result<&'b mut [u8], Error> {
+        ::postcard::to_slice(self, buf)
+    }
+
+    /// Encode the record into `buf` as a COBS frame terminated by a zero byte and return the used part of it.
+    ///
+    /// # Errors
+    /// Returns an error if the record does not fit into `buf`.
+    pub fn to_slice_cobs<'b>(&self, buf: &'b mut [u8]) -> Result<&'b mut [u8], Error> {
+        ::postcard::to_slice_cobs(self, buf)
+    }
+}
+
+impl<'a> From<&'a Record<'_>> for PostcardRecord<'a> {
+    fn from(record: &'a Record<'_>) -> Self {
+        Self {
+            level: record.level(),
+            args: record.args(),
+            target: record.target(),
+            module_path: record.module_path(),
+            file: record.file(),
+            line: record.line(),
+            module_path_static: record.module_path_static().is_some(),
+            file_static: record.file_static().is_some(),
+            timestamp: None,
+        }
+    }
+}
+
+#[cfg(feature = "heapless")]
+impl<'a, const N: usize> From<&'a crate::fixed::FixedLogRecord<N>> for PostcardRecord<'a> {
+    fn from(record: &'a crate::fixed::FixedLogRecord<N>) -> Self {
+        Self {
+            level: record.level,
+            args: &record.args,
+            target: &record.target,
+            module_path: record.module_path.as_deref(),
+            file: record.file.as_deref(),
+            line: record.line,
+            module_path_static: record.module_path_static,
+            file_static: record.file_static,
+            timestamp: None,
+        }
+    }
+}
+
+impl fmt::Debug for PostcardRecord<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PostcardRecord")
+            .field("level", &self.level)
+            .field("args", &format_args!("{}", self.args))
+            .field("target", &self.target)
+            .field("module_path", &self.module_path)
+            .field("file", &self.file)
+            .field("line", &self.line)
+            .field("timestamp", &self.timestamp)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Serialize for PostcardRecord<'_> {
+    /// The layout of `SerializableLogRecordRef` for compact formats.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        /// `RecordLevel` writes its variant index in compact formats.
+        struct CompactLevel(Level);
+
+        impl Serialize for CompactLevel {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let name = self.0.as_str();
+                serializer.serialize_unit_variant("RecordLevel", self.0 as u32 - 1, name)
+            }
+        }
+
+        /// The `fmt::Display` of the message, formatted straight into the output.
+        struct Args<'a>(&'a dyn fmt::Display);
+
+        impl Serialize for Args<'_> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self.0)
+            }
+        }
+
+        /// No key-values.
+        struct Empty;
+
+        impl Serialize for Empty {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_seq(core::iter::empty::<()>())
+            }
+        }
+
+        let mut record = serializer.serialize_struct("SerializableLogRecord", 13)?;
+        record.serialize_field("level", &CompactLevel(self.level))?;
+        record.serialize_field("args", &Args(self.args))?;
+        record.serialize_field("target", self.target)?;
+        record.serialize_field("module_path", &self.module_path)?;
+        record.serialize_field("file", &self.file)?;
+        record.serialize_field("line", &self.line)?;
+        record.serialize_field("key_values", &Empty)?;
+        record.serialize_field("module_path_static", &self.module_path_static)?;
+        record.serialize_field("file_static", &self.file_static)?;
+        record.serialize_field("timestamp", &self.timestamp)?;
+        record.serialize_field("thread_name", &None::<&str>)?;
+        record.serialize_field("thread_id", &None::<u64>)?;
+        record.serialize_field("process_id", &None::<u32>)?;
+        record.end()
+    }
+}
+
+/// Encode a record into `buf` and return the used part of it.
+///
+/// # Errors
+/// Returns an error if the record does not fit into `buf`.
+#[cfg(feature = "serde")]
+pub fn to_slice<'a>(record: &SerializableLogRecordRef<'_>, buf: &'a mut [u8]) -> Result<&'a mut [u8], Error> {
+    ::postcard::to_slice(record, buf)
+}
+
+/// Encode a record into `buf` as a COBS frame terminated by a zero byte and return the used part of it.
+///
+/// # Errors
+/// Returns an error if the record does not fit into `buf`.
+#[cfg(feature = "serde")]
+pub fn to_slice_cobs<'a>(record: &SerializableLogRecordRef<'_>, buf: &'a mut [u8]) -> Result<&'a mut [u8], Error> {
+    ::postcard::to_slice_cobs(record, buf)
+}
+
+/// Decode a record from exactly the given bytes, which must not be COBS encoded.
+///
+/// # Errors
+/// Returns an error if the bytes are not a valid record.
+#[cfg(feature = "serde")]
+pub fn from_bytes(bytes: &[u8]) -> Result<SerializableLogRecord, PostcardError> {
+    from_bytes_borrowed(bytes).map(SerializableLogRecordRef::into_owned)
+}
+
+/// Decode a record from exactly the given bytes, borrowing its strings.
+///
+/// # Errors
+/// Returns an error if the bytes are not a valid record.
+#[cfg(feature = "serde")]
+pub fn from_bytes_borrowed(bytes: &[u8]) -> Result<SerializableLogRecordRef<'_>, PostcardError> {
+    match ::postcard::take_from_bytes(bytes).map_err(PostcardError::Postcard)? {
+        (record, []) => Ok(record),
+        (_, rest) => Err(PostcardError::TrailingBytes(rest.len())),
+    }
+}
+
+/// Encodes records with `postcard`, without COBS. Use it with `framing::Framing::Cobs` to produce the same bytes as
+/// `to_slice_cobs`.
+///
+/// ```rust
+/// use serializable_log_record::{codec::Codec, framing::Framing, postcard, SerializableLogRecordRef};
+///
+/// let record = log::Record::builder().args(format_args!("Hi")).build();
+/// let record = SerializableLogRecordRef::from(&record);
+/// let mut payload = Vec::new();
+/// postcard::PostcardCodec.encode(&record, &mut payload).unwrap();
+/// let mut framed = Vec::new();
+/// Framing::Cobs.encode(&payload, &mut framed).unwrap();
+/// assert_eq!(framed, postcard::to_slice_cobs(&record, &mut [0; 64]).unwrap());
+/// ```
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PostcardCodec;
+
+#[cfg(feature = "serde")]
+impl Codec for PostcardCodec {
+    type Error = PostcardError;
+
+    fn encode(&self, record: &SerializableLogRecordRef<'_>, buf: &mut Vec<u8>) -> Result<(), Self::Error> {
+        buf.extend_from_slice(&::postcard::to_allocvec(record).map_err(PostcardError::Postcard)?);
+        Ok(())
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<SerializableLogRecord, Self::Error> {
+        from_bytes(bytes)
+    }
+}
+
+/// The error returned when a record cannot be encoded or decoded with `postcard`.
+#[cfg(feature = "serde")]
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum PostcardError {
+    Postcard(Error),
+    /// The bytes contained more than one record.
+    TrailingBytes(usize),
+}
+
+#[cfg(feature = "serde")]
+impl fmt::Display for PostcardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Postcard(error) => write!(f, "postcard error: {error}"),
+            Self::TrailingBytes(count) => write!(f, "{count} trailing bytes after the record"),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl core::error::Error for PostcardError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Postcard(error) => Some(error),
+            Self::TrailingBytes(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_the_message_into_the_buffer() {
+        fn check(record: &log::Record<'_>) {
+            let mut buf = [0; 64];
+            let bytes = PostcardRecord::from(record).to_slice(&mut buf).unwrap();
+            assert_eq!(bytes[..2], [0x02, 0x0d]);
+            assert_eq!(&bytes[2..15], b"Hello, world!");
+            // postcard reports a message that does not fit as a formatting error.
+            assert_eq!(
+                PostcardRecord::from(record).to_slice(&mut [0; 10]),
+                Err(Error::CollectStrError)
+            );
+        }
+
+        let name = "world";
+        check(&log::Record::builder().args(format_args!("Hello, {name}!")).build());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn encodes_like_serializable_log_record_ref() {
+        fn check(record: &log::Record<'_>) {
+            let mut expected = SerializableLogRecord::from_record_with_timestamp(record, 7);
+            (expected.thread_name, expected.thread_id, expected.process_id) = (None, None, None);
+            let (mut buf, mut expected_buf) = ([0; 128], [0; 128]);
+            assert_eq!(
+                PostcardRecord::from(record)
+                    .with_timestamp(Some(7))
+                    .to_slice_cobs(&mut buf)
+                    .unwrap(),
+                to_slice_cobs(&SerializableLogRecordRef::from(&expected), &mut expected_buf).unwrap()
+            );
+            let decoded = from_bytes(PostcardRecord::from(record).to_slice(&mut buf).unwrap()).unwrap();
+            assert_eq!(decoded.args, expected.args);
+            assert_eq!(decoded.module_path_static, record.module_path_static().is_some());
+        }
+
+        let count = 3;
+        for level in [Level::Error, Level::Warn, Level::Info, Level::Debug, Level::Trace] {
+            check(
+                &log::Record::builder()
+                    .args(format_args!("{count} retries"))
+                    .level(level)
+                    .target("app")
+                    .module_path_static(Some("app::net"))
+                    .file(Some("src/net.rs"))
+                    .line(Some(42))
+                    .build(),
+            );
+        }
+        check(&log::Record::builder().args(format_args!("Hi")).build());
+    }
+
+    #[cfg(all(feature = "heapless", feature = "serde"))]
+    #[test]
+    fn encodes_fixed_log_records() {
+        let record = log::Record::builder()
+            .args(format_args!("Hi"))
+            .level(Level::Warn)
+            .target("app")
+            .build();
+        let fixed = crate::fixed::FixedLogRecord::<16>::from(&record);
+        let mut buf = [0; 64];
+        let decoded = from_bytes(PostcardRecord::from(&fixed).to_slice(&mut buf).unwrap()).unwrap();
+        assert_eq!(crate::fixed::FixedLogRecord::<16>::from(&decoded), fixed);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn errors() {
+        use core::error::Error as _;
+        assert!(matches!(from_bytes(&[0x09]), Err(PostcardError::Postcard(_))));
+        assert!(from_bytes(&[0x09]).unwrap_err().source().is_some());
+        let mut buf = [0; 64];
+        let record = log::Record::builder().args(format_args!("Hi")).build();
+        let mut bytes = PostcardRecord::from(&record).to_slice(&mut buf).unwrap().to_vec();
+        bytes.push(0);
+        assert!(matches!(from_bytes(&bytes), Err(PostcardError::TrailingBytes(1))));
+    }
+}
@@ -0,0 +1,503 @@
+//! Protocol Buffers encoding of records with `prost`, for collectors written in other languages.
+//!
+//! The schema is published as `schema/log_record.proto` in the repository and is available as `SCHEMA`.
+//! The messages of this module are written by hand so that building the crate does not need `protoc`,
+//! they encode exactly as the code generated from the schema.
+//!
+//! ```rust
+//! use log::Level;
+//! use prost::Message;
+//! use serializable_log_record::protobuf::{self, LogRecord};
+//! use serializable_log_record::SerializableLogRecord;
+//! use std::convert::TryFrom;
+//!
+//! let mut record = SerializableLogRecord::new(Level::Warn, "Hi".into(), "app".into(), None, None, Some(7));
+//! record.key_values.push("user", "alice");
+//!
+//! let message = LogRecord::from(&record);
+//! assert_eq!(message.level(), protobuf::Level::Warn);
+//! let bytes = message.encode_to_vec();
+//! assert_eq!(bytes[..6], [0x08, 0x02, 0x12, 0x02, b'H', b'i']);
+//!
+//! let decoded = SerializableLogRecord::try_from(LogRecord::decode(bytes.as_slice()).unwrap()).unwrap();
+//! assert_eq!(decoded, record);
+//! ```
+//!
+//! Every field of `SerializableLogRecord` has a field of the same name in the schema, and `unknown_level` holds the
+//! text of an `Unknown` level. A test checks this and the field numbers against `SCHEMA`.
+
+use crate::{codec::Codec, kv, level::RecordLevel, SerializableLogRecord, SerializableLogRecordRef};
+use alloc::{string::String, vec::Vec};
+use core::{convert::TryFrom, fmt};
+use prost::Message;
+
+/// The schema of the messages of this module.
+pub const SCHEMA: &str = include_str!("../schema/log_record.proto");
+
+/// The `LogRecord` message.
+#[derive(Clone, PartialEq, Message)]
+pub struct LogRecord {
+    #[prost(enumeration = "Level", tag = "1")]
+    pub level: i32,
+    #[prost(string, tag = "2")]
+    pub args: String,
+    #[prost(string, tag = "3")]
+    pub target: String,
+    #[prost(string, optional, tag = "4")]
+    pub module_path: Option<String>,
+    #[prost(string, optional, tag = "5")]
+    pub file: Option<String>,
+    #[prost(uint32, optional, tag = "6")]
+    pub line: Option<u32>,
+    #[prost(message, repeated, tag = "7")]
+    pub key_values: Vec<KeyValue>,
+    #[prost(bool, tag = "8")]
+    pub module_path_static: bool,
+    #[prost(bool, tag = "9")]
+    pub file_static: bool,
+    #[prost(uint64, optional, tag = "10")]
+    pub timestamp: Option<u64>,
+    #[prost(string, optional, tag = "11")]
+    pub thread_name: Option<String>,
+    #[prost(uint64, optional, tag = "12")]
+    pub thread_id: Option<u64>,
+    #[prost(uint32, optional, tag = "13")]
+    pub process_id: Option<u32>,
+    #[prost(string, optional, tag = "14")]
+    pub unknown_level: Option<String>,
+}
+
+/// The `Level` enum.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum Level {
+    Unspecified = 0,
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+    Trace = 5,
+}
+
+/// The `KeyValue` message.
+#[derive(Clone, PartialEq, Message)]
+pub struct KeyValue {
+    #[prost(string, tag = "1")]
+    pub key: String,
+    #[prost(message, optional, tag = "2")]
+    pub value: Option<Value>,
+}
+
+/// The `KeyValues` message.
+#[derive(Clone, PartialEq, Message)]
+pub struct KeyValues {
+    #[prost(message, repeated, tag = "1")]
+    pub pairs: Vec<KeyValue>,
+}
+
+/// The `Value` message.
+#[derive(Clone, PartialEq, Message)]
+pub struct Value {
+    #[prost(oneof = "value::Kind", tags = "1, 2, 3, 4, 5, 6")]
+    pub kind: Option<value::Kind>,
+}
+
+/// The oneof of the `Value` message.
+pub mod value {
+    use super::KeyValues;
+    use alloc::string::String;
+
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Kind {
+        #[prost(string, tag = "1")]
+        Str(String),
+        #[prost(sint64, tag = "2")]
+        I64(i64),
+        #[prost(uint64, tag = "3")]
+        U64(u64),
+        #[prost(double, tag = "4")]
+        F64(f64),
+        #[prost(bool, tag = "5")]
+        Bool(bool),
+        #[prost(message, tag = "6")]
+        Nested(KeyValues),
+    }
+}
+
+/// The error returned when a message cannot be converted into a `SerializableLogRecord`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProtobufError {
+    /// The bytes are not a valid message.
+    Decode(prost::DecodeError),
+    /// The level is not a value of the `Level` enum.
+    InvalidLevel(i32),
+    /// The level is `LEVEL_UNSPECIFIED` and `unknown_level` is not set.
+    MissingLevel,
+    /// A key-value pair has no value.
+    MissingValue(String),
+}
+
+impl fmt::Display for ProtobufError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Decode(error) => write!(f, "protobuf decoding failed: {error}"),
+            Self::InvalidLevel(level) => write!(f, "invalid level {level}"),
+            Self::MissingLevel => f.write_str("the level is unspecified"),
+            Self::MissingValue(key) => write!(f, "the key-value pair {key:?} has no value"),
+        }
+    }
+}
+
+impl core::error::Error for ProtobufError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Decode(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<&SerializableLogRecordRef<'_>> for LogRecord {
+    fn from(record: &SerializableLogRecordRef<'_>) -> Self {
+        // Destructure exhaustively so that a new field cannot be forgotten here.
+        let SerializableLogRecordRef {
+            level,
+            args,
+            target,
+            module_path,
+            file,
+            line,
+            key_values,
+            module_path_static,
+            file_static,
+            timestamp,
+            thread_name,
+            thread_id,
+            process_id,
+        } = record;
+        let (level, unknown_level) = match level {
+            RecordLevel::Error => (Level::Error, None),
+            RecordLevel::Warn => (Level::Warn, None),
+            RecordLevel::Info => (Level::Info, None),
+            RecordLevel::Debug => (Level::Debug, None),
+            RecordLevel::Trace => (Level::Trace, None),
+            RecordLevel::Unknown(text) => (Level::Unspecified, Some(text.clone())),
+        };
+        Self {
+            level: level.into(),
+            args: args.as_ref().into(),
+            target: target.as_ref().into(),
+            module_path: module_path.as_deref().map(Into::into),
+            file: file.as_deref().map(Into::into),
+            line: *line,
+            key_values: key_values_to_proto(key_values),
+            module_path_static: *module_path_static,
+            file_static: *file_static,
+            timestamp: *timestamp,
+            thread_name: thread_name.as_deref().map(Into::into),
+            thread_id: *thread_id,
+            process_id: *process_id,
+            unknown_level,
+        }
+    }
+}
+
+impl From<&SerializableLogRecord> for LogRecord {
+    fn from(record: &SerializableLogRecord) -> Self {
+        Self::from(&SerializableLogRecordRef::from(record))
+    }
+}
+
+impl TryFrom<LogRecord> for SerializableLogRecord {
+    type Error = ProtobufError;
+
+    fn try_from(message: LogRecord) -> Result<Self, ProtobufError> {
+        let level = match Level::try_from(message.level).map_err(|_| ProtobufError::InvalidLevel(message.level))? {
+            Level::Error => RecordLevel::Error,
+            Level::Warn => RecordLevel::Warn,
+            Level::Info => RecordLevel::Info,
+            Level::Debug => RecordLevel::Debug,
+            Level::Trace => RecordLevel::Trace,
+            Level::Unspecified => RecordLevel::Unknown(message.unknown_level.ok_or(ProtobufError::MissingLevel)?),
+        };
+        Ok(Self {
+            level,
+            args: message.args,
+            target: message.target,
+            module_path: message.module_path,
+            file: message.file,
+            line: message.line,
+            key_values: key_values_from_proto(message.key_values)?,
+            module_path_static: message.module_path_static,
+            file_static: message.file_static,
+            timestamp: message.timestamp,
+            thread_name: message.thread_name,
+            thread_id: message.thread_id,
+            process_id: message.process_id,
+        })
+    }
+}
+
+fn key_values_to_proto(key_values: &kv::KeyValues) -> Vec<KeyValue> {
+    key_values
+        .iter()
+        .map(|(key, value)| {
+            let kind = match value {
+                kv::Value::Str(value) => value::Kind::Str(value.clone()),
+                kv::Value::I64(value) => value::Kind::I64(*value),
+                kv::Value::U64(value) => value::Kind::U64(*value),
+                kv::Value::F64(value) => value::Kind::F64(*value),
+                kv::Value::Bool(value) => value::Kind::Bool(*value),
+                kv::Value::Nested(value) => value::Kind::Nested(KeyValues {
+                    pairs: key_values_to_proto(value),
+                }),
+            };
+            KeyValue {
+                key: key.into(),
+                value: Some(Value { kind: Some(kind) }),
+            }
+        })
+        .collect()
+}
+
+fn key_values_from_proto(pairs: Vec<KeyValue>) -> Result<kv::KeyValues, ProtobufError> {
+    pairs
+        .into_iter()
+        .map(|pair| {
+            let value = match pair.value.and_then(|value| value.kind) {
+                Some(value::Kind::Str(value)) => kv::Value::Str(value),
+                Some(value::Kind::I64(value)) => kv::Value::I64(value),
+                Some(value::Kind::U64(value)) => kv::Value::U64(value),
+                Some(value::Kind::F64(value)) => kv::Value::F64(value),
+                Some(value::Kind::Bool(value)) => kv::Value::Bool(value),
+                Some(value::Kind::Nested(value)) => kv::Value::Nested(key_values_from_proto(value.pairs)?),
+                None => return Err(ProtobufError::MissingValue(pair.key)),
+            };
+            Ok((pair.key, value))
+        })
+        .collect()
+}
+
+/// Encodes records as `LogRecord` messages.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProtobufCodec;
+
+impl Codec for ProtobufCodec {
+    type Error = ProtobufError;
+
+    fn encode(&self, record: &SerializableLogRecordRef<'_>, buf: &mut Vec<u8>) -> Result<(), Self::Error> {
+        buf.extend_from_slice(&LogRecord::from(record).encode_to_vec());
+        Ok(())
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<SerializableLogRecord, Self::Error> {
+        SerializableLogRecord::try_from(LogRecord::decode(bytes).map_err(ProtobufError::Decode)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Level as LogLevel;
+
+    /// The fields that are set, in field number order like the code generated by `protoc`.
+    #[rustfmt::skip]
+    const FULL: &[u8] = &[
+        0x08, 0x02,                         // level: LEVEL_WARN
+        0x12, 0x02, b'H', b'i',             // args
+        0x1A, 0x03, b'a', b'p', b'p',       // target
+        0x22, 0x01, b'm',                   // module_path
+        0x2A, 0x01, b'f',                   // file
+        0x30, 0x07,                         // line
+        0x3A, 0x07,                         // key_values { key: "i" value { i64: -1 } }
+            0x0A, 0x01, b'i',
+            0x12, 0x02, 0x10, 0x01,
+        0x3A, 0x10,                         // key_values { key: "n" value { nested { pairs { key: "b" value { bool: true } } } } }
+            0x0A, 0x01, b'n',
+            0x12, 0x0B, 0x32, 0x09, 0x0A, 0x07,
+                0x0A, 0x01, b'b',
+                0x12, 0x02, 0x28, 0x01,
+        0x40, 0x01,                         // module_path_static
+        0x50, 0x01,                         // timestamp
+        0x5A, 0x01, b't',                   // thread_name
+        0x60, 0x02,                         // thread_id
+        0x68, 0x03,                         // process_id
+    ];
+
+    fn full_record() -> SerializableLogRecord {
+        let mut record = SerializableLogRecord::new(
+            LogLevel::Warn,
+            "Hi".into(),
+            "app".into(),
+            Some("m".into()),
+            Some("f".into()),
+            Some(7),
+        )
+        .with_timestamp(Some(1));
+        record.module_path_static = true;
+        record.thread_name = Some("t".into());
+        record.thread_id = Some(2);
+        record.process_id = Some(3);
+        let mut nested = kv::KeyValues::new();
+        nested.push("b", true);
+        record.key_values.push("i", -1_i64);
+        record.key_values.push("n", kv::Value::Nested(nested));
+        record
+    }
+
+    fn encode(record: &SerializableLogRecord) -> Vec<u8> {
+        let mut buf = Vec::new();
+        ProtobufCodec
+            .encode(&SerializableLogRecordRef::from(record), &mut buf)
+            .unwrap();
+        buf
+    }
+
+    #[test]
+    fn encodes_like_generated_code() {
+        let record = full_record();
+        assert_eq!(encode(&record), FULL);
+        assert_eq!(ProtobufCodec.decode(FULL).unwrap(), record);
+    }
+
+    #[test]
+    fn writes_optional_fields_with_default_values() {
+        let record = SerializableLogRecord::new(
+            LogLevel::Error,
+            String::new(),
+            String::new(),
+            Some(String::new()),
+            None,
+            Some(0),
+        );
+        assert_eq!(encode(&record), [0x08, 0x01, 0x22, 0x00, 0x30, 0x00]);
+        assert_eq!(ProtobufCodec.decode(&[0x08, 0x01, 0x22, 0x00, 0x30, 0x00]).unwrap(), record);
+    }
+
+    #[test]
+    fn writes_unknown_level_as_text() {
+        let mut record = SerializableLogRecord::new(LogLevel::Info, String::new(), String::new(), None, None, None);
+        record.level = RecordLevel::Unknown("NOTICE".into());
+        let bytes = [0x72, 0x06, b'N', b'O', b'T', b'I', b'C', b'E'];
+        assert_eq!(encode(&record), bytes);
+        assert_eq!(ProtobufCodec.decode(&bytes).unwrap(), record);
+    }
+
+    /// The names and numbers of the fields of `message LogRecord` in `SCHEMA`.
+    fn schema_fields() -> Vec<(&'static str, u32)> {
+        let body = SCHEMA.split("message LogRecord {").nth(1).unwrap();
+        let body = &body[..body.find('}').unwrap()];
+        body.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with("//"))
+            .map(|line| {
+                let (declaration, number) = line.trim_end_matches(';').split_once('=').unwrap();
+                (declaration.split_whitespace().last().unwrap(), number.trim().parse().unwrap())
+            })
+            .collect()
+    }
+
+    /// The field number `LogRecord` encodes a field with, from the key of a message with only that field set.
+    fn prost_tag(name: &str) -> u32 {
+        let mut message = LogRecord::default();
+        match name {
+            "level" => message.level = 1,
+            "args" => message.args = "a".into(),
+            "target" => message.target = "t".into(),
+            "module_path" => message.module_path = Some(String::new()),
+            "file" => message.file = Some(String::new()),
+            "line" => message.line = Some(0),
+            "key_values" => message.key_values.push(KeyValue::default()),
+            "module_path_static" => message.module_path_static = true,
+            "file_static" => message.file_static = true,
+            "timestamp" => message.timestamp = Some(0),
+            "thread_name" => message.thread_name = Some(String::new()),
+            "thread_id" => message.thread_id = Some(0),
+            "process_id" => message.process_id = Some(0),
+            "unknown_level" => message.unknown_level = Some(String::new()),
+            name => panic!("LogRecord has no field {}", name),
+        }
+        u32::from(message.encode_to_vec()[0] >> 3)
+    }
+
+    /// The names of the fields of a struct, from a destructure that fails to compile when a field is added.
+    macro_rules! field_names {
+        ($value:expr, $type:ident { $($field:ident),* $(,)? }) => {{
+            let $type { $($field: _),* } = $value;
+            [$(stringify!($field)),*]
+        }};
+    }
+
+    #[test]
+    fn schema_matches_messages() {
+        let message_fields = field_names!(
+            LogRecord::default(),
+            LogRecord {
+                level,
+                args,
+                target,
+                module_path,
+                file,
+                line,
+                key_values,
+                module_path_static,
+                file_static,
+                timestamp,
+                thread_name,
+                thread_id,
+                process_id,
+                unknown_level,
+            }
+        );
+        let record_fields = field_names!(
+            full_record(),
+            SerializableLogRecord {
+                level,
+                args,
+                target,
+                module_path,
+                file,
+                line,
+                key_values,
+                module_path_static,
+                file_static,
+                timestamp,
+                thread_name,
+                thread_id,
+                process_id,
+            }
+        );
+
+        let schema = schema_fields();
+        let mut names: Vec<_> = schema.iter().map(|(name, _)| *name).collect();
+        names.sort_unstable();
+        let mut expected = message_fields.to_vec();
+        expected.sort_unstable();
+        assert_eq!(names, expected);
+        for (name, number) in schema {
+            assert_eq!(prost_tag(name), number, "{name}");
+        }
+        for name in record_fields {
+            assert!(names.contains(&name), "{} is missing from the schema", name);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_messages() {
+        assert_eq!(ProtobufCodec.decode(&[0x08, 0x09]), Err(ProtobufError::InvalidLevel(9)));
+        assert_eq!(ProtobufCodec.decode(&[]), Err(ProtobufError::MissingLevel));
+        assert_eq!(
+            ProtobufCodec.decode(&[0x08, 0x01, 0x3A, 0x03, 0x0A, 0x01, b'k']),
+            Err(ProtobufError::MissingValue("k".into()))
+        );
+        assert!(matches!(
+            ProtobufCodec.decode(&FULL[..FULL.len() - 1]),
+            Err(ProtobufError::Decode(_))
+        ));
+        assert!(matches!(
+            ProtobufCodec.decode(&[0x12, 0x05, b'a']),
+            Err(ProtobufError::Decode(_))
+        ));
+    }
+}
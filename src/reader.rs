@@ -0,0 +1,265 @@
+//! Reading framed records from any `std::io::Read` source, the receiving end of `logger::SerializingLogger`.
+//!
+//! ```rust
+//! # #[cfg(feature = "json")]
+//! # {
+//! use log::{Level, Log};
+//! use serializable_log_record::codec::JsonCodec;
+//! use serializable_log_record::logger::SerializingLogger;
+//! use serializable_log_record::reader::{replay_all, RecordReader};
+//!
+//! let logger = SerializingLogger::new(Vec::new(), JsonCodec);
+//! logger.log(&log::Record::builder().args(format_args!("Hello")).level(Level::Info).build());
+//! logger.log(&log::Record::builder().args(format_args!("World")).level(Level::Warn).build());
+//! let bytes = logger.into_inner();
+//!
+//! let records: Vec<_> = RecordReader::new(bytes.as_slice(), JsonCodec).collect::<Result<_, _>>().unwrap();
+//! assert_eq!(records[1].args, "World");
+//!
+//! let replayed = replay_all(RecordReader::new(bytes.as_slice(), JsonCodec), log::logger()).unwrap();
+//! assert_eq!(replayed, 2);
+//! # }
+//! ```
+
+use crate::{
+    codec::Codec,
+    framing::{FrameDecoder, FrameError, Framing, DEFAULT_MAX_FRAME_SIZE},
+    SerializableLogRecord,
+};
+use alloc::{boxed::Box, vec::Vec};
+use core::fmt;
+use log::Log;
+use std::io::{self, Read};
+
+/// The error returned when a record cannot be read from a stream.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum DecodeError {
+    Io(io::Error),
+    /// The stream ended in the middle of a frame.
+    UnexpectedEof,
+    /// The frame is invalid or larger than the maximum frame size, which usually means the stream is corrupted.
+    Frame(FrameError),
+    /// The frame could not be decoded by the codec.
+    Codec(Box<dyn core::error::Error + Send + Sync>),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "reading the stream failed: {error}"),
+            Self::UnexpectedEof => f.write_str("the stream ended in the middle of a frame"),
+            Self::Frame(error) => write!(f, "reading the frame failed: {error}"),
+            Self::Codec(error) => write!(f, "decoding the record failed: {error}"),
+        }
+    }
+}
+
+impl core::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Frame(error) => Some(error),
+            Self::Codec(error) => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<FrameError> for DecodeError {
+    fn from(error: FrameError) -> Self {
+        Self::Frame(error)
+    }
+}
+
+impl From<io::Error> for DecodeError {
+    fn from(error: io::Error) -> Self {
+        if error.kind() == io::ErrorKind::UnexpectedEof {
+            Self::UnexpectedEof
+        } else {
+            Self::Io(error)
+        }
+    }
+}
+
+/// An iterator over the records of a framed stream.
+///
+/// The iterator ends at the end of the stream. A codec error only affects its own frame, and the delimited framings
+/// `Framing::Newline` and `Framing::Cobs` resynchronize at the next delimiter after a frame error, so the iterator can
+/// be advanced past these errors. After any other error the stream position is lost and the iterator ends.
+/// With `Framing::Newline`, a last line that is not terminated by a newline is read as the last record.
+#[derive(Debug)]
+pub struct RecordReader<R: Read, C: Codec> {
+    reader: R,
+    codec: C,
+    framing: Framing,
+    max_frame_size: usize,
+    decoder: FrameDecoder,
+    done: bool,
+}
+
+impl<R: Read, C: Codec> RecordReader<R, C> {
+    /// Create a reader with `Framing::U32LengthPrefix` and `DEFAULT_MAX_FRAME_SIZE`.
+    pub fn new(reader: R, codec: C) -> Self {
+        Self {
+            reader,
+            codec,
+            framing: Framing::default(),
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            decoder: FrameDecoder::new(Framing::default()),
+            done: false,
+        }
+    }
+
+    #[must_use]
+    pub fn with_framing(mut self, framing: Framing) -> Self {
+        self.framing = framing;
+        self.decoder = FrameDecoder::new(framing).with_max_frame_size(self.max_frame_size);
+        self
+    }
+
+    #[must_use]
+    pub fn with_max_frame_size(mut self, max_frame_size: usize) -> Self {
+        self.max_frame_size = max_frame_size;
+        self.decoder = FrameDecoder::new(self.framing).with_max_frame_size(max_frame_size);
+        self
+    }
+
+    /// Read until the next frame is complete. Returns `None` at the end of the stream.
+    fn read_frame(&mut self) -> Result<Option<Vec<u8>>, DecodeError> {
+        let mut chunk = [0; 8192];
+        loop {
+            if let Some(frame) = self.decoder.next_frame()? {
+                return Ok(Some(frame));
+            }
+            let read = match self.reader.read(&mut chunk) {
+                Ok(read) => read,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error.into()),
+            };
+            if read == 0 {
+                if let Some(frame) = self.decoder.finish() {
+                    return Ok(Some(frame));
+                }
+                return if self.decoder.is_empty() {
+                    Ok(None)
+                } else {
+                    Err(DecodeError::UnexpectedEof)
+                };
+            }
+            self.decoder.push(&chunk[..read]);
+        }
+    }
+}
+
+impl<R: Read, C: Codec> Iterator for RecordReader<R, C> {
+    type Item = Result<SerializableLogRecord, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.read_frame() {
+            Ok(Some(frame)) => Some(self.codec.decode(&frame).map_err(|error| DecodeError::Codec(Box::new(error)))),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(error) => {
+                let resynchronizes = matches!(self.framing, Framing::Newline | Framing::Cobs);
+                self.done = !(resynchronizes && matches!(error, DecodeError::Frame(_)));
+                Some(Err(error))
+            }
+        }
+    }
+}
+
+/// Replay every record of the stream into the logger, see `SerializableLogRecord::replay_into`.
+/// Returns the number of replayed records.
+///
+/// # Errors
+/// Stops at the first record that cannot be read and returns its error.
+pub fn replay_all<R: Read, C: Codec>(reader: RecordReader<R, C>, logger: &dyn Log) -> Result<usize, DecodeError> {
+    let mut count = 0;
+    for record in reader {
+        record?.replay_into(logger);
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SerializableLogRecordRef;
+    use alloc::string::String;
+    use log::Level;
+
+    /// Stores only the message, as UTF-8.
+    struct ArgsCodec;
+
+    impl Codec for ArgsCodec {
+        type Error = core::str::Utf8Error;
+
+        fn encode(&self, record: &SerializableLogRecordRef<'_>, buf: &mut Vec<u8>) -> Result<(), Self::Error> {
+            buf.extend_from_slice(record.args.as_bytes());
+            Ok(())
+        }
+
+        fn decode(&self, bytes: &[u8]) -> Result<SerializableLogRecord, Self::Error> {
+            let args = core::str::from_utf8(bytes)?.into();
+            Ok(SerializableLogRecord::new(Level::Info, args, String::new(), None, None, None))
+        }
+    }
+
+    fn read_args(bytes: &[u8], framing: Framing) -> Vec<Result<String, DecodeError>> {
+        RecordReader::new(bytes, ArgsCodec)
+            .with_framing(framing)
+            .map(|record| record.map(|record| record.args))
+            .collect()
+    }
+
+    #[test]
+    fn reads_last_line_without_newline() {
+        let records = read_args(b"first\nlast", Framing::Newline);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].as_ref().unwrap(), "last");
+    }
+
+    #[test]
+    fn reads_no_empty_record_after_final_newline() {
+        let records = read_args(b"first\nlast\n", Framing::Newline);
+        assert_eq!(records.len(), 2);
+    }
+
+    #[test]
+    fn skips_oversized_last_line() {
+        let mut reader = RecordReader::new(&b"ok\ntoo long"[..], ArgsCodec)
+            .with_framing(Framing::Newline)
+            .with_max_frame_size(4);
+        assert_eq!(reader.next().unwrap().unwrap().args, "ok");
+        assert!(matches!(
+            reader.next(),
+            Some(Err(DecodeError::Frame(FrameError::TooLarge(_))))
+        ));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn reports_truncated_length_prefixed_frame() {
+        let mut bytes = Vec::new();
+        Framing::U32LengthPrefix.encode(b"complete", &mut bytes).unwrap();
+        Framing::U32LengthPrefix.encode(b"truncated", &mut bytes).unwrap();
+        bytes.pop();
+        let records = read_args(&bytes, Framing::U32LengthPrefix);
+        assert_eq!(records.len(), 2);
+        assert!(matches!(records[1], Err(DecodeError::UnexpectedEof)));
+    }
+
+    #[test]
+    fn reports_codec_error_and_continues() {
+        let records = read_args(b"\xFF\nnext\n", Framing::Newline);
+        assert!(matches!(records[0], Err(DecodeError::Codec(_))));
+        assert_eq!(records[1].as_ref().unwrap(), "next");
+    }
+}
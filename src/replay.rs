@@ -0,0 +1,36 @@
+//! Building a `log::Record` from the fields of any record type.
+
+use crate::statics::StaticStrings;
+use log::{Level, RecordBuilder};
+
+/// The fields of a record that are set on a `log::RecordBuilder`, borrowed from the record being replayed.
+#[derive(Clone, Copy)]
+pub(crate) struct Fields<'b> {
+    pub level: Level,
+    pub target: &'b str,
+    pub module_path: Option<&'b str>,
+    pub module_path_static: bool,
+    pub file: Option<&'b str>,
+    pub file_static: bool,
+    pub line: Option<u32>,
+}
+
+/// Set every field of the builder except `args` and the key-values. `module_path` and `file` are passed as
+/// `'static` strings if they were static in the original `log::Record` and the registry knows them.
+pub(crate) fn prepare_builder<'b, 'c>(
+    builder: &'c mut RecordBuilder<'b>,
+    fields: Fields<'b>,
+    statics: Option<&dyn StaticStrings>,
+) -> &'c mut RecordBuilder<'b> {
+    let intern = |value: Option<&str>, is_static: bool| value.filter(|_| is_static).and_then(|value| statics?.get(value));
+    builder.level(fields.level).target(fields.target).line(fields.line);
+    match intern(fields.module_path, fields.module_path_static) {
+        Some(module_path) => builder.module_path_static(Some(module_path)),
+        None => builder.module_path(fields.module_path),
+    };
+    match intern(fields.file, fields.file_static) {
+        Some(file) => builder.file_static(Some(file)),
+        None => builder.file(fields.file),
+    };
+    builder
+}
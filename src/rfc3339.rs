@@ -0,0 +1,137 @@
+//! Formatting and parsing of RFC 3339 timestamps in UTC, for the text formats that carry the timestamp as a date.
+
+use core::{convert::TryFrom, fmt};
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// Displays nanoseconds since the Unix epoch as `YYYY-MM-DDTHH:MM:SS.fffZ` with `digits` fractional digits (at most 9).
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Rfc3339 {
+    pub(crate) nanos: u64,
+    pub(crate) digits: u32,
+}
+
+impl fmt::Display for Rfc3339 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = self.nanos / NANOS_PER_SECOND;
+        let (year, month, day) = civil_from_days(seconds / SECONDS_PER_DAY);
+        let time = seconds % SECONDS_PER_DAY;
+        write!(
+            f,
+            "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}",
+            time / 3600,
+            time / 60 % 60,
+            time % 60
+        )?;
+        if self.digits > 0 {
+            let digits = self.digits.min(9);
+            let fraction = self.nanos % NANOS_PER_SECOND / 10_u64.pow(9 - digits);
+            write!(f, ".{fraction:0width$}", width = digits as usize)?;
+        }
+        f.write_str("Z")
+    }
+}
+
+/// Parse `YYYY-MM-DDTHH:MM:SS[.f+](Z|±HH:MM)` into nanoseconds since the Unix epoch. Returns `None` if the text is
+/// not a valid timestamp or lies before the epoch.
+pub(crate) fn parse(text: &str) -> Option<u64> {
+    let bytes = text.as_bytes();
+    if !text.is_ascii() || bytes.len() < 20 || bytes[4] != b'-' || bytes[7] != b'-' || !matches!(bytes[10], b'T' | b't' | b' ') {
+        return None;
+    }
+    if bytes[13] != b':' || bytes[16] != b':' {
+        return None;
+    }
+    let year = number(&text[0..4])?;
+    let month = number(&text[5..7])?;
+    let day = number(&text[8..10])?;
+    let hour = number(&text[11..13])?;
+    let minute = number(&text[14..16])?;
+    let second = number(&text[17..19])?;
+    if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let mut rest = &text[19..];
+    let mut nanos = 0;
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let digits = fraction.bytes().take_while(u8::is_ascii_digit).count();
+        if digits == 0 {
+            return None;
+        }
+        let mut scale = NANOS_PER_SECOND;
+        for digit in fraction.bytes().take(digits.min(9)) {
+            scale /= 10;
+            nanos += u64::from(digit - b'0') * scale;
+        }
+        rest = &fraction[digits..];
+    }
+
+    let offset = match rest.as_bytes() {
+        [b'Z' | b'z'] => 0,
+        [sign @ (b'+' | b'-'), _, _, b':', _, _] => {
+            let offset = number(&rest[1..3])? * 3600 + number(&rest[4..6])? * 60;
+            if *sign == b'+' {
+                offset
+            } else {
+                -offset
+            }
+        }
+        _ => return None,
+    };
+
+    let seconds = days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second - offset;
+    let seconds = u64::try_from(seconds).ok()?;
+    seconds.checked_mul(NANOS_PER_SECOND)?.checked_add(nanos)
+}
+
+fn number(text: &str) -> Option<i64> {
+    if text.bytes().all(|byte| byte.is_ascii_digit()) {
+        text.parse().ok()
+    } else {
+        None
+    }
+}
+
+fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// The days since the Unix epoch of a date in the proleptic Gregorian calendar, negative before the epoch.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    // Shift the year to start in March so that the leap day is the last day of the year.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The date of a number of days since the Unix epoch.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+    (year, month, day)
+}
@@ -0,0 +1,270 @@
+//! Zero-copy access to records archived with `rkyv`.
+//!
+//! `to_bytes` archives a record. `access` validates archived bytes, e.g. a memory-mapped file, and returns an
+//! `ArchivedSerializableLogRecord` that reads directly from them. The archived record can be replayed into a
+//! `log::Log` just like an owned record, without deserializing it first.
+//!
+//! ```rust
+//! use log::Level;
+//! use serializable_log_record::{rkyv, SerializableLogRecord};
+//!
+//! let record = SerializableLogRecord::new(Level::Warn, "Hi".into(), "app".into(), Some("app::net".into()), None, Some(7));
+//! let bytes = rkyv::to_bytes(&record).unwrap();
+//!
+//! let archived = rkyv::access(&bytes).unwrap();
+//! assert_eq!(archived.args.as_str(), "Hi");
+//! archived.with_log_record(|replayed| {
+//!     assert_eq!(replayed.level(), Level::Warn);
+//!     assert_eq!(replayed.args().to_string(), "Hi");
+//!     assert_eq!(replayed.module_path(), Some("app::net"));
+//!     assert_eq!(replayed.line(), Some(7));
+//! });
+//! archived.replay_into(log::logger());
+//! assert_eq!(rkyv::deserialize(archived).unwrap(), record);
+//!
+//! assert!(rkyv::access(&bytes[..bytes.len() - 4]).is_err());
+//! ```
+//!
+//! With the `kv` feature, the key-value pairs and the timestamp are attached as well:
+//!
+//! ```rust
+//! # #[cfg(feature = "kv")]
+//! # {
+//! # use log::Level;
+//! # use serializable_log_record::{rkyv, SerializableLogRecord};
+//! let mut record = SerializableLogRecord::new(Level::Info, "Hi".into(), "app".into(), None, None, None).with_timestamp(Some(5));
+//! record.key_values.push("user", "alice");
+//! let bytes = rkyv::to_bytes(&record).unwrap();
+//!
+//! let archived = rkyv::access(&bytes).unwrap();
+//! let (user, timestamp) = archived.with_log_record(|replayed| {
+//!     let key_values = replayed.key_values();
+//!     (key_values.get("user".into()).map(|v| v.to_string()), key_values.get("timestamp".into()).and_then(|v| v.to_u64()))
+//! });
+//! assert_eq!(user.as_deref(), Some("alice"));
+//! assert_eq!(timestamp, Some(5));
+//! # }
+//! ```
+//!
+//! An archive of many records is accessed with `rkyv` directly:
+//!
+//! ```rust
+//! # use log::Level;
+//! # use serializable_log_record::{ArchivedSerializableLogRecord, SerializableLogRecord};
+//! let records = vec![SerializableLogRecord::new(Level::Info, "Hi".into(), "app".into(), None, None, None); 3];
+//! let bytes = ::rkyv::to_bytes::<::rkyv::rancor::Error>(&records).unwrap();
+//!
+//! let archived = ::rkyv::access::<::rkyv::vec::ArchivedVec<ArchivedSerializableLogRecord>, ::rkyv::rancor::Error>(&bytes).unwrap();
+//! for record in archived.iter() {
+//!     record.replay_into(log::logger());
+//! }
+//! ```
+
+use crate::{
+    level::{ArchivedRecordLevel, LevelFallback, ParseLevelError, DEFAULT_REPLAY_LEVEL},
+    replay,
+    statics::StaticStrings,
+    ArchivedSerializableLogRecord, SerializableLogRecord,
+};
+use log::{Level, Log, Record, RecordBuilder};
+
+pub use ::rkyv::{rancor::Error, util::AlignedVec};
+
+/// Archive a record.
+///
+/// # Errors
+/// Returns an error if the record cannot be archived.
+pub fn to_bytes(record: &SerializableLogRecord) -> Result<AlignedVec, Error> {
+    ::rkyv::to_bytes::<Error>(record)
+}
+
+/// Validate the archived bytes and access the record in place.
+///
+/// # Errors
+/// Returns an error if the bytes are not a valid archived record.
+pub fn access(bytes: &[u8]) -> Result<&ArchivedSerializableLogRecord, Error> {
+    ::rkyv::access::<ArchivedSerializableLogRecord, Error>(bytes)
+}
+
+/// Deserialize an archived record into an owned record.
+///
+/// # Errors
+/// Returns an error if the record cannot be deserialized.
+pub fn deserialize(archived: &ArchivedSerializableLogRecord) -> Result<SerializableLogRecord, Error> {
+    ::rkyv::deserialize::<SerializableLogRecord, Error>(archived)
+}
+
+impl ArchivedRecordLevel {
+    /// The `log::Level`, or `None` if the level is `Unknown`.
+    #[must_use]
+    pub fn to_level(&self) -> Option<Level> {
+        self.to_level_or(LevelFallback::Error).ok()
+    }
+
+    /// The `log::Level`, applying the fallback policy if the level is `Unknown`, see `RecordLevel::to_level_or`.
+    ///
+    /// # Errors
+    /// Returns a `ParseLevelError` if the level is `Unknown` and the policy does not provide a level.
+    pub fn to_level_or(&self, fallback: LevelFallback) -> Result<Level, ParseLevelError> {
+        match self {
+            Self::Error => Ok(Level::Error),
+            Self::Warn => Ok(Level::Warn),
+            Self::Info => Ok(Level::Info),
+            Self::Debug => Ok(Level::Debug),
+            Self::Trace => Ok(Level::Trace),
+            Self::Unknown(text) => match fallback {
+                LevelFallback::Default(level) => Ok(level),
+                LevelFallback::Error | LevelFallback::Keep => Err(ParseLevelError::new(text)),
+            },
+        }
+    }
+}
+
+impl ArchivedSerializableLogRecord {
+    /// Convert this record into a `log::Record` and pass it to the given closure,
+    /// see `SerializableLogRecord::with_log_record`.
+    pub fn with_log_record<R>(&self, f: impl FnOnce(&Record<'_>) -> R) -> R {
+        let mut builder = Record::builder();
+        f(&crate::into_log_record!(builder, self))
+    }
+
+    /// Like `with_log_record`, but applies the given fallback policy to an `Unknown` level,
+    /// see `SerializableLogRecord::try_with_log_record`.
+    ///
+    /// # Errors
+    /// Returns a `ParseLevelError` without calling the closure if the level is `Unknown` and the policy does not
+    /// provide a level.
+    pub fn try_with_log_record<R>(
+        &self,
+        fallback: LevelFallback,
+        f: impl FnOnce(&Record<'_>) -> R,
+    ) -> Result<R, ParseLevelError> {
+        let level = self.level.to_level_or(fallback)?;
+        let mut builder = Record::builder();
+        Ok(f(&self
+            .prepare_builder(&mut builder, None)
+            .level(level)
+            .args(format_args!("{}", self.args))
+            .build()))
+    }
+
+    /// Like `with_log_record`, but restores `'static` strings through the registry,
+    /// see `SerializableLogRecord::with_log_record_interned`.
+    pub fn with_log_record_interned<R>(&self, statics: &dyn StaticStrings, f: impl FnOnce(&Record<'_>) -> R) -> R {
+        let mut builder = Record::builder();
+        f(&self
+            .prepare_builder(&mut builder, Some(statics))
+            .args(format_args!("{}", self.args))
+            .build())
+    }
+
+    /// Convert this record into a `log::Record` and pass it to the `log` method of the given logger.
+    pub fn replay_into(&self, logger: &dyn Log) {
+        self.with_log_record(|record| logger.log(record));
+    }
+
+    /// Like `replay_into`, but applies the given fallback policy to an `Unknown` level, see `try_with_log_record`.
+    ///
+    /// # Errors
+    /// Returns a `ParseLevelError` without logging if the level is `Unknown` and the policy does not provide a level.
+    pub fn try_replay_into(&self, logger: &dyn Log, fallback: LevelFallback) -> Result<(), ParseLevelError> {
+        self.try_with_log_record(fallback, |record| logger.log(record))
+    }
+
+    /// Like `replay_into`, but restores `'static` strings through the registry.
+    pub fn replay_into_interned(&self, logger: &dyn Log, statics: &dyn StaticStrings) {
+        self.with_log_record_interned(statics, |record| logger.log(record));
+    }
+
+    /// Set every field of the builder except `args`, see `SerializableLogRecord::prepare_builder`.
+    /// Internal macro use only.
+    #[doc(hidden)]
+    pub fn prepare_builder<'b, 'c>(
+        &'b self,
+        builder: &'c mut RecordBuilder<'b>,
+        statics: Option<&dyn StaticStrings>,
+    ) -> &'c mut RecordBuilder<'b> {
+        let fields = replay::Fields {
+            level: self.level.to_level().unwrap_or(DEFAULT_REPLAY_LEVEL),
+            target: &self.target,
+            module_path: self.module_path.as_deref(),
+            module_path_static: self.module_path_static,
+            file: self.file.as_deref(),
+            file_static: self.file_static,
+            line: self.line.as_ref().map(|line| line.to_native()),
+        };
+        let builder = replay::prepare_builder(builder, fields, statics);
+        #[cfg(feature = "kv")]
+        builder.key_values(self);
+        builder
+    }
+}
+
+#[cfg(feature = "kv")]
+mod log_kv {
+    use crate::{
+        kv::{fmt_nested, ArchivedKeyValues, ArchivedValue, TIMESTAMP_KEY},
+        ArchivedSerializableLogRecord,
+    };
+    use core::fmt;
+    use log::kv::{self, Key, Source, ToValue, VisitSource};
+
+    impl fmt::Display for ArchivedValue {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::Str(value) => f.write_str(value),
+                Self::I64(value) => value.fmt(f),
+                Self::U64(value) => value.fmt(f),
+                Self::F64(value) => value.fmt(f),
+                Self::Bool(value) => value.fmt(f),
+                Self::Nested(value) => fmt_nested(f, value.iter()),
+            }
+        }
+    }
+
+    impl ToValue for ArchivedValue {
+        fn to_value(&self) -> kv::Value<'_> {
+            match self {
+                Self::Str(value) => kv::Value::from(value.as_str()),
+                Self::I64(value) => kv::Value::from(value.to_native()),
+                Self::U64(value) => kv::Value::from(value.to_native()),
+                Self::F64(value) => kv::Value::from(value.to_native()),
+                Self::Bool(value) => kv::Value::from(*value),
+                Self::Nested(_) => kv::Value::from_dyn_display(self),
+            }
+        }
+    }
+
+    impl Source for ArchivedKeyValues {
+        fn visit<'kvs>(&'kvs self, visitor: &mut dyn VisitSource<'kvs>) -> Result<(), kv::Error> {
+            for (key, value) in self.iter() {
+                visitor.visit_pair(Key::from_str(key), value.to_value())?;
+            }
+            Ok(())
+        }
+
+        fn count(&self) -> usize {
+            self.len()
+        }
+    }
+
+    /// The same key-value pairs as `Source for SerializableLogRecord`.
+    impl Source for ArchivedSerializableLogRecord {
+        fn visit<'kvs>(&'kvs self, visitor: &mut dyn VisitSource<'kvs>) -> Result<(), kv::Error> {
+            self.key_values.visit(visitor)?;
+            if let Some(timestamp) = replayed_timestamp(self) {
+                visitor.visit_pair(Key::from_str(TIMESTAMP_KEY), kv::Value::from(timestamp))?;
+            }
+            Ok(())
+        }
+
+        fn count(&self) -> usize {
+            self.key_values.count() + usize::from(replayed_timestamp(self).is_some())
+        }
+    }
+
+    fn replayed_timestamp(record: &ArchivedSerializableLogRecord) -> Option<u64> {
+        let timestamp = record.timestamp.as_ref().map(|timestamp| timestamp.to_native());
+        timestamp.filter(|_| record.key_values.get(TIMESTAMP_KEY).is_none())
+    }
+}
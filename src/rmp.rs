@@ -0,0 +1,232 @@
+//! MessagePack encoding of records with `rmp-serde`, for readers written in other languages.
+//!
+//! Two layouts are available:
+//!
+//! * `RmpLayout::Compact` encodes a record as an array of its fields in declaration order.
+//! * `RmpLayout::Named` encodes a record as a map from field names to values.
+//!
+//! The fields, in order, are:
+//!
+//! | # | name                 | type                                                        |
+//! |---|----------------------|-------------------------------------------------------------|
+//! | 0 | `level`              | str `ERROR`, `WARN`, `INFO`, `DEBUG` or `TRACE`, or map `{"Unknown": str}` |
+//! | 1 | `args`               | str                                                         |
+//! | 2 | `target`             | str                                                         |
+//! | 3 | `module_path`        | str or nil                                                  |
+//! | 4 | `file`               | str or nil                                                  |
+//! | 5 | `line`               | uint or nil                                                 |
+//! | 6 | `key_values`         | array of `[key: str, value]` pairs, value is a map `{"Str" \| "I64" \| "U64" \| "F64" \| "Bool" \| "Nested": value}` |
+//! | 7 | `module_path_static` | bool                                                        |
+//! | 8 | `file_static`        | bool                                                        |
+//! | 9 | `timestamp`          | uint (nanoseconds since the Unix epoch) or nil              |
+//! | 10 | `thread_name`       | str or nil                                                  |
+//! | 11 | `thread_id`         | uint or nil                                                 |
+//! | 12 | `process_id`        | uint or nil                                                 |
+//!
+//! Decoding accepts both layouts. Fields 6 to 12 may be left out, at the end of the array or from the map.
+//! Fields 3 to 5 may also be left out of the map.
+//!
+//! The golden vectors below are part of the test suite, so other implementations can rely on them.
+//!
+//! ```rust
+//! use log::Level;
+//! use serializable_log_record::rmp::{self, RmpLayout};
+//! use serializable_log_record::SerializableLogRecord;
+//!
+//! let record = SerializableLogRecord::new(Level::Warn, "Hi".into(), "app".into(), None, None, Some(7));
+//!
+//! let compact = b"\x9D\xA4WARN\xA2Hi\xA3app\xC0\xC0\x07\x90\xC2\xC2\xC0\xC0\xC0\xC0";
+//! assert_eq!(rmp::to_vec(&record, RmpLayout::Compact).unwrap(), compact);
+//! assert_eq!(rmp::from_slice(compact).unwrap(), record);
+//!
+//! let named = b"\x8D\xA5level\xA4WARN\xA4args\xA2Hi\xA6target\xA3app\xABmodule_path\xC0\xA4file\xC0\xA4line\x07\
+//!     \xAAkey_values\x90\xB2module_path_static\xC2\xABfile_static\xC2\xA9timestamp\xC0\
+//!     \xABthread_name\xC0\xA9thread_id\xC0\xAAprocess_id\xC0";
+//! assert_eq!(rmp::to_vec(&record, RmpLayout::Named).unwrap(), named);
+//! assert_eq!(rmp::from_slice(named).unwrap(), record);
+//!
+//! // What a minimal writer in another language would produce.
+//! assert_eq!(rmp::from_slice(b"\x96\xA4WARN\xA2Hi\xA3app\xC0\xC0\x07").unwrap(), record);
+//! assert_eq!(rmp::from_slice(b"\x84\xA5level\xA4WARN\xA4args\xA2Hi\xA6target\xA3app\xA4line\x07").unwrap(), record);
+//! ```
+//!
+//! Key-values and unknown levels:
+//!
+//! ```rust
+//! # use log::Level;
+//! # use serializable_log_record::rmp::{self, RmpLayout};
+//! # use serializable_log_record::{level::RecordLevel, SerializableLogRecord};
+//! let mut record = SerializableLogRecord::new(Level::Info, "Hi".into(), "app".into(), None, None, None);
+//! record.level = RecordLevel::Unknown("X".into());
+//! record.key_values.push("k", 1u64);
+//!
+//! let compact = b"\x9D\x81\xA7Unknown\xA1X\xA2Hi\xA3app\xC0\xC0\xC0\x91\x92\xA1k\x81\xA3U64\x01\xC2\xC2\xC0\xC0\xC0\xC0";
+//! assert_eq!(rmp::to_vec(&record, RmpLayout::Compact).unwrap(), compact);
+//! assert_eq!(rmp::from_slice(compact).unwrap(), record);
+//! ```
+
+use crate::{codec::Codec, SerializableLogRecord, SerializableLogRecordRef};
+use alloc::vec::Vec;
+use serde::Serialize;
+
+pub use rmp_serde::{decode::Error as DecodeError, encode::Error as EncodeError};
+
+/// The MessagePack layout of a record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum RmpLayout {
+    /// An array of the fields in declaration order.
+    #[default]
+    Compact,
+    /// A map from field names to values.
+    Named,
+}
+
+/// Encode a record as MessagePack.
+///
+/// # Errors
+/// Returns an error if the record cannot be encoded.
+pub fn to_vec(record: &SerializableLogRecord, layout: RmpLayout) -> Result<Vec<u8>, EncodeError> {
+    let mut buf = Vec::new();
+    encode(record, layout, &mut buf)?;
+    Ok(buf)
+}
+
+/// Decode a record from MessagePack in either layout.
+///
+/// # Errors
+/// Returns an error if the bytes are not a valid record.
+pub fn from_slice(bytes: &[u8]) -> Result<SerializableLogRecord, DecodeError> {
+    rmp_serde::from_slice(bytes)
+}
+
+fn encode(record: &impl Serialize, layout: RmpLayout, buf: &mut Vec<u8>) -> Result<(), EncodeError> {
+    match layout {
+        RmpLayout::Compact => record.serialize(&mut rmp_serde::Serializer::new(buf)),
+        RmpLayout::Named => record.serialize(&mut rmp_serde::Serializer::new(buf).with_struct_map()),
+    }
+}
+
+/// Encodes records as MessagePack in the given layout.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RmpCodec {
+    pub layout: RmpLayout,
+}
+
+impl Codec for RmpCodec {
+    type Error = RmpError;
+
+    fn encode(&self, record: &SerializableLogRecordRef<'_>, buf: &mut Vec<u8>) -> Result<(), Self::Error> {
+        encode(record, self.layout, buf).map_err(RmpError::Encode)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<SerializableLogRecord, Self::Error> {
+        from_slice(bytes).map_err(RmpError::Decode)
+    }
+}
+
+/// The error of `RmpCodec`.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum RmpError {
+    Encode(EncodeError),
+    Decode(DecodeError),
+}
+
+impl core::fmt::Display for RmpError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Encode(error) => write!(f, "MessagePack encoding failed: {error}"),
+            Self::Decode(error) => write!(f, "MessagePack decoding failed: {error}"),
+        }
+    }
+}
+
+impl core::error::Error for RmpError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Encode(error) => Some(error),
+            Self::Decode(error) => Some(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        kv::{KeyValues, Value},
+        level::RecordLevel,
+    };
+    use log::Level;
+
+    /// A record with every field set and every kind of key-value.
+    fn full_record() -> SerializableLogRecord {
+        let mut record = SerializableLogRecord::new(
+            Level::Error,
+            "Boom".into(),
+            "db".into(),
+            Some("db::pool".into()),
+            Some("src/pool.rs".into()),
+            Some(300),
+        )
+        .with_timestamp(Some(1_700_000_000_000_000_000));
+        record.module_path_static = true;
+        record.thread_name = Some("main".into());
+        record.thread_id = Some(1);
+        record.process_id = Some(42);
+        let mut nested = KeyValues::new();
+        nested.push("ok", true);
+        record.key_values.push("i", -1_i64);
+        record.key_values.push("f", 0.5_f64);
+        record.key_values.push("s", "x");
+        record.key_values.push("n", Value::Nested(nested));
+        record
+    }
+
+    const FULL_COMPACT: &[u8] = b"\x9D\xA5ERROR\xA4Boom\xA2db\xA8db::pool\xABsrc/pool.rs\xCD\x01\x2C\
+        \x94\x92\xA1i\x81\xA3I64\xFF\x92\xA1f\x81\xA3F64\xCB\x3F\xE0\x00\x00\x00\x00\x00\x00\x92\xA1s\x81\xA3Str\xA1x\
+        \x92\xA1n\x81\xA6Nested\x91\x92\xA2ok\x81\xA4Bool\xC3\
+        \xC3\xC2\xCF\x17\x97\x9C\xFE\x36\x2A\x00\x00\xA4main\x01\x2A";
+
+    #[test]
+    fn full_record_matches_golden_vector() {
+        let record = full_record();
+        assert_eq!(to_vec(&record, RmpLayout::Compact).unwrap(), FULL_COMPACT);
+        assert_eq!(from_slice(FULL_COMPACT).unwrap(), record);
+    }
+
+    #[test]
+    fn unknown_level_in_named_layout() {
+        let mut record = SerializableLogRecord::new(Level::Info, "Hi".into(), "app".into(), None, None, None);
+        record.level = RecordLevel::Unknown("NOTICE".into());
+        let named = b"\x8D\xA5level\x81\xA7Unknown\xA6NOTICE\xA4args\xA2Hi\xA6target\xA3app\xABmodule_path\xC0\
+            \xA4file\xC0\xA4line\xC0\xAAkey_values\x90\xB2module_path_static\xC2\xABfile_static\xC2\xA9timestamp\xC0\
+            \xABthread_name\xC0\xA9thread_id\xC0\xAAprocess_id\xC0";
+        assert_eq!(to_vec(&record, RmpLayout::Named).unwrap(), named);
+        assert_eq!(from_slice(named).unwrap(), record);
+    }
+
+    #[test]
+    fn codec_encodes_borrowed_record_like_owned_record() {
+        let record = full_record();
+        for layout in [RmpLayout::Compact, RmpLayout::Named] {
+            let codec = RmpCodec { layout };
+            let mut buf = Vec::new();
+            codec.encode(&SerializableLogRecordRef::from(&record), &mut buf).unwrap();
+            assert_eq!(buf, to_vec(&record, layout).unwrap());
+            assert_eq!(codec.decode(&buf).unwrap(), record);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        assert!(from_slice(b"").is_err());
+        assert!(from_slice(&FULL_COMPACT[..FULL_COMPACT.len() - 1]).is_err());
+        // Too few fields: `module_path`, `file` and `line` are required in the compact layout.
+        assert!(from_slice(b"\x92\xA4WARN\xA2Hi").is_err());
+        // Level names are not parsed leniently in the compact layouts.
+        assert!(from_slice(b"\x96\xA4NOPE\xA2Hi\xA3app\xC0\xC0\xC0").is_err());
+        assert!(from_slice(b"\x96\x09\xA2Hi\xA3app\xC0\xC0\xC0").is_err());
+        assert!(matches!(RmpCodec::default().decode(b"\xC1"), Err(RmpError::Decode(_))));
+    }
+}
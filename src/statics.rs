@@ -0,0 +1,47 @@
+//! Restoring the `'static`-ness of `module_path` and `file` when replaying a record.
+//!
+//! `log::Record::module_path_static` and `log::Record::file_static` only return `Some` if the record was built
+//! from `'static` strings. A `SerializableLogRecord` remembers whether this was the case in `module_path_static`
+//! and `file_static`, but after deserialization the strings are owned by the record. A `StaticStrings` registry
+//! maps them back to the `'static` originals so that loggers relying on the static accessors keep working.
+//! `log::Record` has no static accessor for the target, so the target is always replayed as a borrowed string.
+//!
+//! ```rust
+//! # #[cfg(feature = "alloc")]
+//! # {
+//! use serializable_log_record::SerializableLogRecord;
+//!
+//! let record = log::Record::builder().args(format_args!("Hello")).module_path_static(Some("my_crate::net")).build();
+//! let serializable_record = SerializableLogRecord::from(&record);
+//! assert!(serializable_record.module_path_static);
+//!
+//! let registry = ["my_crate::net", "my_crate::db"];
+//! let module_path = serializable_record.with_log_record_interned(&registry, |record| record.module_path_static());
+//! assert_eq!(module_path, Some("my_crate::net"));
+//! assert_eq!(serializable_record.with_log_record(|record| record.module_path_static()), None);
+//! # }
+//! ```
+
+/// A registry of known `'static` strings.
+pub trait StaticStrings {
+    /// Look up the `'static` string equal to `value`.
+    fn get(&self, value: &str) -> Option<&'static str>;
+}
+
+impl StaticStrings for &[&'static str] {
+    fn get(&self, value: &str) -> Option<&'static str> {
+        self.iter().copied().find(|known| *known == value)
+    }
+}
+
+impl<const N: usize> StaticStrings for [&'static str; N] {
+    fn get(&self, value: &str) -> Option<&'static str> {
+        StaticStrings::get(&self.as_slice(), value)
+    }
+}
+
+impl<F: Fn(&str) -> Option<&'static str>> StaticStrings for F {
+    fn get(&self, value: &str) -> Option<&'static str> {
+        self(value)
+    }
+}
@@ -0,0 +1,610 @@
+//! Syslog messages as specified by RFC 5424, e.g. to forward records to rsyslog.
+//!
+//! `SyslogCodec::format` renders a record as
+//!
+//! ```text
+//! <PRI>1 TIMESTAMP HOSTNAME APP-NAME PROCID - [log@32473 ...][kv@32473 ...] BOM MSG
+//! ```
+//!
+//! * `PRI` combines the configured `Facility` with the severity of the level, see `severity`. `Trace` and `Unknown`
+//!   levels, which have no severity of their own, are also written to the `level` parameter.
+//! * `TIMESTAMP` has microsecond precision, the most that RFC 5424 allows, or is `-` without a timestamp.
+//! * `APP-NAME` is the target if it is a valid `APP-NAME` and `TargetField::AppName` is configured, otherwise the
+//!   configured app name and the target is written to the `target` parameter.
+//! * `PROCID` is the process id.
+//! * The `log@32473` element holds the `target`, `module_path`, `file`, `line`, `thread_name` and `thread_id`
+//!   parameters, the `kv@32473` element holds the key-value pairs as strings. Keys that are not valid `PARAM-NAME`s
+//!   have their invalid characters replaced with `_` and are cut to 32 characters. The number after the `@` is the
+//!   enterprise number reserved for documentation by default, configure your own with `with_enterprise_number`.
+//!
+//! `module_path_static` and `file_static` are not written.
+//!
+//! `SyslogCodec::parse` turns such messages back into records, so that syslog streams can be replayed. Key-value
+//! pairs become strings, other SD-ELEMENTs become nested key-values named after their SD-ID. Lines that are not
+//! RFC 5424 messages are parsed as RFC 3164 messages on a best-effort basis: the tag becomes the target and the
+//! process id is taken from `tag[pid]:`, but the timestamp is dropped because it has no year.
+//!
+//! ```rust
+//! use log::Level;
+//! use serializable_log_record::syslog::{Facility, SyslogCodec};
+//! use serializable_log_record::{into_log_record, SerializableLogRecord, SerializableLogRecordRef};
+//!
+//! let mut record = SerializableLogRecord::new(Level::Warn, "Disk full".into(), "app".into(), None, Some("src/main.rs".into()), Some(7))
+//!     .with_timestamp(Some(1_700_000_000_123_456_789));
+//! record.key_values.push("free", 0_u64);
+//!
+//! let codec = SyslogCodec::default().with_facility(Facility::Local0).with_hostname("web-1");
+//! let message = codec.format(&SerializableLogRecordRef::from(&record));
+//! assert_eq!(
+//!     message,
+//!     "<132>1 2023-11-14T22:13:20.123456Z web-1 app - - [log@32473 file=\"src/main.rs\" line=\"7\"][kv@32473 free=\"0\"] \u{feff}Disk full"
+//! );
+//!
+//! let parsed = codec.parse(&message).unwrap();
+//! assert_eq!(parsed.level, Level::Warn.into());
+//! assert_eq!(parsed.target, "app");
+//! assert_eq!(parsed.line, Some(7));
+//! assert_eq!(parsed.timestamp, Some(1_700_000_000_123_456_000));
+//! assert_eq!(parsed.key_values.get("free").unwrap().to_string(), "0");
+//! let mut builder = log::Record::builder();
+//! log::logger().log(&into_log_record!(builder, parsed));
+//!
+//! let legacy = codec.parse("<30>Oct 11 22:14:15 web-1 sshd[42]: Accepted publickey").unwrap();
+//! assert_eq!(legacy.level, Level::Info.into());
+//! assert_eq!(legacy.target, "sshd");
+//! assert_eq!(legacy.process_id, Some(42));
+//! assert_eq!(legacy.args, "Accepted publickey");
+//! ```
+
+use crate::{
+    codec::Codec,
+    kv::KeyValues,
+    level::{LevelFallback, RecordLevel},
+    rfc3339::{self, Rfc3339},
+    SerializableLogRecord, SerializableLogRecordRef,
+};
+use alloc::{format, string::String, vec::Vec};
+use core::fmt::{self, Display, Write};
+
+/// The enterprise number reserved for documentation by RFC 5612, used in the SD-IDs by default.
+pub const DEFAULT_ENTERPRISE_NUMBER: u32 = 32473;
+
+const BOM: char = '\u{feff}';
+const NIL: &str = "-";
+
+/// The facility of a syslog message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Facility {
+    Kern = 0,
+    #[default]
+    User = 1,
+    Mail = 2,
+    Daemon = 3,
+    Auth = 4,
+    Syslog = 5,
+    Lpr = 6,
+    News = 7,
+    Uucp = 8,
+    Cron = 9,
+    Authpriv = 10,
+    Ftp = 11,
+    Ntp = 12,
+    Audit = 13,
+    Alert = 14,
+    Clock = 15,
+    Local0 = 16,
+    Local1 = 17,
+    Local2 = 18,
+    Local3 = 19,
+    Local4 = 20,
+    Local5 = 21,
+    Local6 = 22,
+    Local7 = 23,
+}
+
+/// Where the target of a record is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TargetField {
+    /// In `APP-NAME`, unless it is not a valid `APP-NAME`.
+    #[default]
+    AppName,
+    /// In the `target` parameter of the structured data.
+    StructuredData,
+}
+
+/// The syslog severity of a level: `Error` is 3 (error), `Warn` is 4 (warning), `Info` is 6 (informational), `Debug`
+/// and `Trace` are 7 (debug) and `Unknown` levels are 5 (notice).
+#[must_use]
+pub fn severity(level: &RecordLevel) -> u8 {
+    match level {
+        RecordLevel::Error => 3,
+        RecordLevel::Warn => 4,
+        RecordLevel::Unknown(_) => 5,
+        RecordLevel::Info => 6,
+        RecordLevel::Debug | RecordLevel::Trace => 7,
+    }
+}
+
+pub(crate) fn level_from_severity(severity: u8) -> RecordLevel {
+    match severity {
+        0..=3 => RecordLevel::Error,
+        4 => RecordLevel::Warn,
+        5 | 6 => RecordLevel::Info,
+        _ => RecordLevel::Debug,
+    }
+}
+
+/// Formats records as RFC 5424 syslog messages and parses them back, see the module documentation.
+///
+/// As a `Codec`, it encodes one message without a trailing newline. Use it with `framing::Framing::Newline` to write
+/// a stream that rsyslog can read, as long as the messages do not contain newlines themselves.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SyslogCodec {
+    facility: Facility,
+    hostname: Option<String>,
+    app_name: Option<String>,
+    target: TargetField,
+    enterprise_number: u32,
+    bom: bool,
+}
+
+impl Default for SyslogCodec {
+    fn default() -> Self {
+        Self {
+            facility: Facility::default(),
+            hostname: None,
+            app_name: None,
+            target: TargetField::default(),
+            enterprise_number: DEFAULT_ENTERPRISE_NUMBER,
+            bom: true,
+        }
+    }
+}
+
+impl SyslogCodec {
+    #[must_use]
+    pub fn with_facility(mut self, facility: Facility) -> Self {
+        self.facility = facility;
+        self
+    }
+
+    /// The `HOSTNAME` of every message, `-` by default.
+    #[must_use]
+    pub fn with_hostname(mut self, hostname: impl Into<String>) -> Self {
+        self.hostname = Some(hostname.into());
+        self
+    }
+
+    /// The `APP-NAME` of messages whose target is not written to `APP-NAME`, `-` by default.
+    #[must_use]
+    pub fn with_app_name(mut self, app_name: impl Into<String>) -> Self {
+        self.app_name = Some(app_name.into());
+        self
+    }
+
+    #[must_use]
+    pub fn with_target_field(mut self, target: TargetField) -> Self {
+        self.target = target;
+        self
+    }
+
+    /// The enterprise number of the SD-IDs `log@<number>` and `kv@<number>`, `DEFAULT_ENTERPRISE_NUMBER` by default.
+    #[must_use]
+    pub fn with_enterprise_number(mut self, enterprise_number: u32) -> Self {
+        self.enterprise_number = enterprise_number;
+        self
+    }
+
+    /// Whether `MSG` starts with a byte order mark to declare it as UTF-8, as RFC 5424 requires. Enabled by default.
+    #[must_use]
+    pub fn with_bom(mut self, bom: bool) -> Self {
+        self.bom = bom;
+        self
+    }
+
+    /// Render a record as a syslog message.
+    #[must_use]
+    pub fn format(&self, record: &SerializableLogRecordRef<'_>) -> String {
+        let mut message = String::new();
+        // Writing to a String cannot fail.
+        let _ = self.write(record, &mut message);
+        message
+    }
+
+    /// Render a record as a syslog message into `out`.
+    ///
+    /// # Errors
+    /// Returns an error if `out` fails.
+    pub fn write(&self, record: &SerializableLogRecordRef<'_>, out: &mut impl Write) -> fmt::Result {
+        let priority = self.facility as u8 * 8 + severity(&record.level);
+        write!(out, "<{priority}>1 ")?;
+        match record.timestamp {
+            Some(nanos) => write!(out, "{} ", Rfc3339 { nanos, digits: 6 })?,
+            None => out.write_str("- ")?,
+        }
+        write_header_field(out, self.hostname.as_deref().unwrap_or(NIL), 255)?;
+        out.write_char(' ')?;
+        let target_in_app_name = self.target == TargetField::AppName && is_header_field(&record.target, 48);
+        if target_in_app_name {
+            out.write_str(&record.target)?;
+        } else {
+            write_header_field(out, self.app_name.as_deref().unwrap_or(NIL), 48)?;
+        }
+        match record.process_id {
+            Some(process_id) => write!(out, " {process_id} - ")?,
+            None => out.write_str(" - - ")?,
+        }
+
+        let mut params = Vec::<(&str, &dyn Display)>::new();
+        if !target_in_app_name {
+            params.push(("target", &record.target));
+        }
+        if let Some(module_path) = &record.module_path {
+            params.push(("module_path", module_path));
+        }
+        if let Some(file) = &record.file {
+            params.push(("file", file));
+        }
+        if let Some(line) = &record.line {
+            params.push(("line", line));
+        }
+        if let Some(thread_name) = &record.thread_name {
+            params.push(("thread_name", thread_name));
+        }
+        if let Some(thread_id) = &record.thread_id {
+            params.push(("thread_id", thread_id));
+        }
+        let level = record.level.as_str();
+        if matches!(record.level, RecordLevel::Trace | RecordLevel::Unknown(_)) {
+            params.push(("level", &level));
+        }
+        if params.is_empty() && record.key_values.is_empty() {
+            out.write_str(NIL)?;
+        }
+        if !params.is_empty() {
+            write!(out, "[log@{}", self.enterprise_number)?;
+            for (name, value) in params {
+                write_param(out, name, value)?;
+            }
+            out.write_str("]")?;
+        }
+        if !record.key_values.is_empty() {
+            write!(out, "[kv@{}", self.enterprise_number)?;
+            for (key, value) in record.key_values.iter() {
+                write_param(out, key, value)?;
+            }
+            out.write_str("]")?;
+        }
+
+        if !record.args.is_empty() {
+            out.write_char(' ')?;
+            if self.bom {
+                out.write_char(BOM)?;
+            }
+            out.write_str(&record.args)?;
+        }
+        Ok(())
+    }
+
+    /// Parse an RFC 5424 message, or an RFC 3164 message on a best-effort basis. A trailing newline is ignored.
+    ///
+    /// # Errors
+    /// Returns an error if the priority is invalid or the line is a malformed RFC 5424 message.
+    pub fn parse(&self, line: &str) -> Result<SerializableLogRecord, SyslogError> {
+        let line = line.trim_end_matches(&['\r', '\n'][..]);
+        let (priority, rest) = match line.strip_prefix('<') {
+            Some(rest) => {
+                let end = rest
+                    .find('>')
+                    .filter(|end| (1..=3).contains(end))
+                    .ok_or(SyslogError::InvalidPriority)?;
+                let priority = rest[..end]
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|priority| *priority <= 191)
+                    .ok_or(SyslogError::InvalidPriority)?;
+                (priority, &rest[end + 1..])
+            }
+            // RFC 3164 assigns user.notice to messages without a priority.
+            None => (13, line),
+        };
+        let level = level_from_severity(priority % 8);
+        match rest.strip_prefix("1 ") {
+            Some(rest) => self.parse_rfc5424(level, rest),
+            None => Ok(parse_rfc3164(level, rest)),
+        }
+    }
+
+    fn parse_rfc5424(&self, mut level: RecordLevel, rest: &str) -> Result<SerializableLogRecord, SyslogError> {
+        let mut fields = rest.splitn(6, ' ');
+        let mut field = |name| fields.next().ok_or(SyslogError::MissingField(name));
+        let timestamp = field("TIMESTAMP")?;
+        let _hostname = field("HOSTNAME")?;
+        let app_name = field("APP-NAME")?;
+        let process_id = field("PROCID")?;
+        let _message_id = field("MSGID")?;
+        let rest = field("STRUCTURED-DATA")?;
+
+        let timestamp = match timestamp {
+            NIL => None,
+            timestamp => Some(rfc3339::parse(timestamp).ok_or(SyslogError::InvalidTimestamp)?),
+        };
+        let mut record = SerializableLogRecord::new(log::Level::Info, String::new(), String::new(), None, None, None)
+            .with_timestamp(timestamp);
+        record.process_id = process_id.parse().ok();
+
+        let (elements, rest) = match rest.strip_prefix(NIL) {
+            Some(rest) => (Vec::new(), rest),
+            None => structured_data(rest)?,
+        };
+        record.args = match rest.strip_prefix(' ') {
+            Some(message) => message.strip_prefix(BOM).unwrap_or(message).into(),
+            None if rest.is_empty() => String::new(),
+            None => return Err(SyslogError::InvalidStructuredData),
+        };
+
+        let log_id = format!("log@{}", self.enterprise_number);
+        let kv_id = format!("kv@{}", self.enterprise_number);
+        let mut target = None;
+        for (id, params) in elements {
+            if id == log_id {
+                for (name, value) in params {
+                    match name.as_str() {
+                        "target" => target = Some(value),
+                        "module_path" => record.module_path = Some(value),
+                        "file" => record.file = Some(value),
+                        "line" => record.line = value.parse().ok(),
+                        "thread_name" => record.thread_name = Some(value),
+                        "thread_id" => record.thread_id = value.parse().ok(),
+                        "level" => level = RecordLevel::parse(&value, LevelFallback::Keep).unwrap_or(level),
+                        _ => {}
+                    }
+                }
+            } else if id == kv_id {
+                for (name, value) in params {
+                    record.key_values.push(name, value);
+                }
+            } else {
+                let params = params.into_iter().collect::<KeyValues>();
+                record.key_values.push(id, params);
+            }
+        }
+        record.level = level;
+        record.target = target.unwrap_or_else(|| if app_name == NIL { String::new() } else { app_name.into() });
+        Ok(record)
+    }
+}
+
+/// Parse `[Mmm dd hh:mm:ss ][HOSTNAME ]TAG[[PID]]: MSG`, falling back to the whole line as the message.
+fn parse_rfc3164(level: RecordLevel, rest: &str) -> SerializableLogRecord {
+    let mut record = SerializableLogRecord::new(log::Level::Info, rest.into(), String::new(), None, None, None);
+    record.level = level;
+    let Some(rest) = strip_rfc3164_timestamp(rest) else {
+        return record;
+    };
+    let (first, after_first) = rest.split_once(' ').unwrap_or((rest, ""));
+    // The hostname is missing in messages from the local syslog socket.
+    let (tag, message) = if let Some(tag) = parse_tag(first) {
+        (Some(tag), after_first)
+    } else {
+        let (second, after_second) = after_first.split_once(' ').unwrap_or((after_first, ""));
+        parse_tag(second).map_or((None, after_first), |tag| (Some(tag), after_second))
+    };
+    if let Some((tag, process_id)) = tag {
+        record.target = tag.into();
+        record.process_id = process_id;
+    }
+    record.args = message.into();
+    record
+}
+
+/// Split `TAG:` or `TAG[PID]:` into the tag and the process id.
+fn parse_tag(word: &str) -> Option<(&str, Option<u32>)> {
+    let word = word.strip_suffix(':')?;
+    match word.strip_suffix(']').and_then(|word| word.split_once('[')) {
+        Some((tag, process_id)) => Some((tag, process_id.parse().ok())),
+        None if !word.contains('[') => Some((word, None)),
+        None => None,
+    }
+}
+
+fn strip_rfc3164_timestamp(text: &str) -> Option<&str> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let timestamp = text.as_bytes().get(..16)?;
+    let is_digit = |i: usize| timestamp[i].is_ascii_digit();
+    let day_ok = (timestamp[4] == b' ' || is_digit(4)) && is_digit(5);
+    let time_ok = [7, 8, 10, 11, 13, 14].iter().all(|&i| is_digit(i)) && timestamp[9] == b':' && timestamp[12] == b':';
+    let separators_ok = timestamp[3] == b' ' && timestamp[6] == b' ' && timestamp[15] == b' ';
+    let month_ok = MONTHS.iter().any(|month| month.as_bytes() == &timestamp[..3]);
+    // The checks above only accept ASCII, so byte 16 is a char boundary if they pass.
+    (month_ok && day_ok && time_ok && separators_ok)
+        .then(|| text.get(16..))
+        .flatten()
+}
+
+/// Parse one or more SD-ELEMENTs and return them with the rest of the text.
+#[allow(clippy::type_complexity)]
+fn structured_data(mut text: &str) -> Result<(Vec<(String, Vec<(String, String)>)>, &str), SyslogError> {
+    let mut elements = Vec::new();
+    while let Some(rest) = text.strip_prefix('[') {
+        let end = rest.find([' ', ']'].as_ref()).ok_or(SyslogError::InvalidStructuredData)?;
+        let id = &rest[..end];
+        text = &rest[end..];
+        let mut params = Vec::new();
+        loop {
+            if let Some(rest) = text.strip_prefix(']') {
+                text = rest;
+                break;
+            }
+            let rest = text.strip_prefix(' ').ok_or(SyslogError::InvalidStructuredData)?;
+            let (name, rest) = rest.split_once("=\"").ok_or(SyslogError::InvalidStructuredData)?;
+            let mut value = String::new();
+            let mut chars = rest.char_indices();
+            text = loop {
+                match chars.next().ok_or(SyslogError::InvalidStructuredData)? {
+                    (_, '\\') => match chars.next().ok_or(SyslogError::InvalidStructuredData)? {
+                        (_, escaped @ ('"' | '\\' | ']')) => value.push(escaped),
+                        (_, other) => {
+                            value.push('\\');
+                            value.push(other);
+                        }
+                    },
+                    (i, '"') => break &rest[i + 1..],
+                    (_, other) => value.push(other),
+                }
+            };
+            params.push((name.into(), value));
+        }
+        if id.is_empty() {
+            return Err(SyslogError::InvalidStructuredData);
+        }
+        elements.push((id.into(), params));
+    }
+    if elements.is_empty() {
+        return Err(SyslogError::InvalidStructuredData);
+    }
+    Ok((elements, text))
+}
+
+fn is_header_field(value: &str, max_len: usize) -> bool {
+    (1..=max_len).contains(&value.len()) && value.bytes().all(|byte| byte.is_ascii_graphic())
+}
+
+/// Write a header field, replacing characters that are not printable US-ASCII with `_` and cutting it to `max_len`.
+fn write_header_field(out: &mut impl Write, value: &str, max_len: usize) -> fmt::Result {
+    if value.is_empty() {
+        return out.write_str(NIL);
+    }
+    for c in value.chars().take(max_len) {
+        out.write_char(if c.is_ascii_graphic() { c } else { '_' })?;
+    }
+    Ok(())
+}
+
+/// Write ` name="value"`, with the name restricted to a valid `PARAM-NAME` and the value escaped.
+fn write_param(out: &mut impl Write, name: &str, value: &dyn Display) -> fmt::Result {
+    out.write_char(' ')?;
+    if name.is_empty() {
+        out.write_char('_')?;
+    }
+    for c in name.chars().take(32) {
+        let valid = c.is_ascii_graphic() && !matches!(c, '=' | ']' | '"');
+        out.write_char(if valid { c } else { '_' })?;
+    }
+    out.write_str("=\"")?;
+    write!(EscapeParamValue(out), "{value}")?;
+    out.write_char('"')
+}
+
+/// Escapes `"`, `\` and `]` in a `PARAM-VALUE`.
+struct EscapeParamValue<'a, W>(&'a mut W);
+
+impl<W: Write> Write for EscapeParamValue<'_, W> {
+    fn write_str(&mut self, value: &str) -> fmt::Result {
+        for c in value.chars() {
+            if matches!(c, '"' | '\\' | ']') {
+                self.0.write_char('\\')?;
+            }
+            self.0.write_char(c)?;
+        }
+        Ok(())
+    }
+}
+
+impl Codec for SyslogCodec {
+    type Error = SyslogError;
+
+    fn encode(&self, record: &SerializableLogRecordRef<'_>, buf: &mut Vec<u8>) -> Result<(), Self::Error> {
+        buf.extend_from_slice(self.format(record).as_bytes());
+        Ok(())
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<SerializableLogRecord, Self::Error> {
+        self.parse(core::str::from_utf8(bytes).map_err(SyslogError::InvalidUtf8)?)
+    }
+}
+
+/// The error returned when a syslog message cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SyslogError {
+    /// The message is not valid UTF-8.
+    InvalidUtf8(core::str::Utf8Error),
+    /// The `<PRI>` at the start of the message is not a number from 0 to 191.
+    InvalidPriority,
+    /// The RFC 5424 header ends before the given field.
+    MissingField(&'static str),
+    /// The RFC 5424 timestamp is not a valid RFC 3339 timestamp.
+    InvalidTimestamp,
+    /// The RFC 5424 structured data is malformed.
+    InvalidStructuredData,
+}
+
+impl fmt::Display for SyslogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidUtf8(error) => write!(f, "the syslog message is not valid UTF-8: {error}"),
+            Self::InvalidPriority => f.write_str("invalid syslog priority"),
+            Self::MissingField(field) => write!(f, "the syslog message has no {field}"),
+            Self::InvalidTimestamp => f.write_str("invalid syslog timestamp"),
+            Self::InvalidStructuredData => f.write_str("invalid syslog structured data"),
+        }
+    }
+}
+
+impl core::error::Error for SyslogError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::InvalidUtf8(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rfc3164_with_non_ascii_text() {
+        let codec = SyslogCodec::default();
+        assert_eq!(
+            codec.parse("<13>ab€aaaaaaaaaaaaaaaaaa").unwrap().args,
+            "ab€aaaaaaaaaaaaaaaaaa"
+        );
+        assert_eq!(
+            codec.parse("<13>Jan  1 00:00:0€ host app: Hi").unwrap().args,
+            "Jan  1 00:00:0€ host app: Hi"
+        );
+        assert_eq!(codec.parse("<13>Jan  1 00:00:00 hôst äpp: Hi €").unwrap().args, "Hi €");
+        assert_eq!(codec.parse("€€€€€€€€").unwrap().args, "€€€€€€€€");
+    }
+
+    #[test]
+    fn rejects_invalid_priorities() {
+        let codec = SyslogCodec::default();
+        for line in ["<>x", "<192>x", "<1234>x", "<-1>x", "<€>x", "<13"] {
+            assert!(matches!(codec.parse(line), Err(SyslogError::InvalidPriority)), "{}", line);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_rfc5424() {
+        let codec = SyslogCodec::default();
+        assert!(matches!(codec.parse("<13>1 - - app"), Err(SyslogError::MissingField(_))));
+        assert!(matches!(
+            codec.parse("<13>1 yesterday - app - - - Hi"),
+            Err(SyslogError::InvalidTimestamp)
+        ));
+        assert!(matches!(
+            codec.parse("<13>1 - - app - - [id k=\"v] Hi"),
+            Err(SyslogError::InvalidStructuredData)
+        ));
+        assert!(matches!(
+            codec.parse("<13>1 - - app - - [id k=\"€"),
+            Err(SyslogError::InvalidStructuredData)
+        ));
+    }
+}